@@ -0,0 +1,123 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Kinds of access whose dialogs are destructive unless `prompt_styles` says otherwise
+const DESTRUCTIVE_PERMISSIONS: &[&str] = &["remote-desktop", "input-capture"];
+// IDs of options granting the least, most restrictive first, after `restrictive_options`
+const RESTRICTIVE_OPTIONS: &[&str] = &["deny", "never", "no", "none", "once", "session", "ask"];
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Access {
+    /// Kind of surface for access dialogs that don't ask to be modal or not
+    pub dialog_surface: DialogSurface,
+    /// Show access dialogs on every output at once, unless the caller asks otherwise
+    pub all_outputs: bool,
+    /// Automatic answers for sessions nobody is there to answer, like kiosks. Requests are
+    /// left to the user while unset.
+    pub unattended: Unattended,
+    /// Handling of requests from callers that didn't identify their app
+    pub unidentified_app: UnidentifiedApp,
+    /// Milliseconds a dialog waits before its surface is created, so requests cancelled
+    /// or answered by then never show one. 0 shows dialogs at once.
+    pub grace_period_ms: u32,
+    /// Style of dialogs by the kind of access asked for, like `remote-desktop`, over the
+    /// defaults
+    pub prompt_styles: BTreeMap<String, PromptStyle>,
+    /// Pre-select the most restrictive option of choices the caller leaves for the portal
+    /// to choose, rather than the first
+    pub restrictive_defaults: bool,
+    /// IDs of options taken to be the most restrictive, most restrictive first, over the
+    /// defaults. Used for choices whose caller doesn't name one.
+    pub restrictive_options: Vec<String>,
+    /// Leave out the note on what an app can do with access, shown in dialogs asking for
+    /// some kinds of it like the camera
+    pub hide_privacy_notes: bool,
+}
+
+impl Access {
+    /// Style of dialogs asking for `permission`
+    pub fn prompt_style(&self, permission: Option<&str>) -> PromptStyle {
+        let Some(permission) = permission else {
+            return PromptStyle::Standard;
+        };
+        match self.prompt_styles.get(permission) {
+            Some(style) => *style,
+            None if DESTRUCTIVE_PERMISSIONS.contains(&permission) => PromptStyle::Destructive,
+            None => PromptStyle::Standard,
+        }
+    }
+
+    /// Position of the most restrictive of a choice's `option_ids`, if any is known to be
+    pub fn most_restrictive(&self, option_ids: &[&str]) -> Option<usize> {
+        self.restrictive_options
+            .iter()
+            .map(String::as_str)
+            .chain(RESTRICTIVE_OPTIONS.iter().copied())
+            .find_map(|restrictive| {
+                option_ids
+                    .iter()
+                    .position(|id| id.eq_ignore_ascii_case(restrictive))
+            })
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum PromptStyle {
+    /// The grant button is suggested, and Ctrl+Enter grants
+    #[default]
+    Standard,
+    /// For access that hands over control, like remote desktop. The grant button is
+    /// styled as destructive, deny is suggested, and Enter denies.
+    Destructive,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum UnidentifiedApp {
+    /// Answer with an error, without showing a dialog
+    Reject,
+    /// Show the dialog with a warning that the app is unidentified
+    #[default]
+    Caution,
+    /// Show the dialog as for any other app
+    Proceed,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Unattended {
+    /// Seconds a dialog waits for the user before `timeout_answer` is sent, or no limit
+    pub timeout_secs: Option<u32>,
+    /// Answer sent for dialogs nobody answered in `timeout_secs`
+    pub timeout_answer: Answer,
+    /// Answers sent without showing a dialog, by the kind of access asked for, like `camera`
+    pub permissions: BTreeMap<String, Answer>,
+    /// Show a brief notice when a request is answered from `permissions`
+    pub notify_answered: bool,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum Answer {
+    /// Deny access, as if the user had cancelled the dialog
+    #[default]
+    Deny,
+    /// Grant access, with each choice as the dialog would have preselected it
+    Allow,
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum DialogSurface {
+    /// Modal window if the requesting app is focused and fullscreen, otherwise a layer surface
+    #[default]
+    Automatic,
+    /// Always a layer surface above other windows
+    Layer,
+    /// Always a modal window
+    Modal,
+}
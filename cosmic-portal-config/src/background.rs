@@ -0,0 +1,21 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Background {
+    /// Periodically notify about apps allowed to run in the background or at login
+    pub review: bool,
+    /// Days between reviews
+    pub review_interval_days: u32,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self {
+            review: false,
+            review_interval_days: 30,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+// Longest glob accepted in a pinned filter
+const MAX_GLOB_LEN: usize = 255;
+// Most `*` in a pinned glob, since each one multiplies the work of matching it
+const MAX_GLOB_WILDCARDS: usize = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileChooser {
+    /// Filters offered in every file chooser after the app's own, like
+    /// `RAW photos` for `*.arw` and `*.cr3`
+    pub pinned_filters: Vec<PinnedFilter>,
+    /// How names are sorted and searched in lists of our own, like the contents of an
+    /// archive
+    pub collation: Collation,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Collation {
+    /// Sort `Apple` apart from `apple`, and only find names of the same case
+    pub case_sensitive: bool,
+    /// Sort and search `é` as `e`. Unset, this follows the language, as some treat
+    /// accented letters as letters of their own.
+    pub ignore_diacritics: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PinnedFilter {
+    pub label: String,
+    /// Glob patterns matched against file names, like `*.arw`
+    pub patterns: Vec<String>,
+}
+
+impl PinnedFilter {
+    /// Why the filter can't be offered, if it can't
+    pub fn validate(&self) -> Result<(), String> {
+        if self.label.trim().is_empty() {
+            return Err("the label is empty".to_string());
+        }
+        if self.patterns.is_empty() {
+            return Err("there are no patterns".to_string());
+        }
+        for pattern in &self.patterns {
+            validate_glob(pattern).map_err(|reason| format!("{pattern:?} {reason}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Why `glob` can't be used in a pinned filter, if it can't
+pub fn validate_glob(glob: &str) -> Result<(), &'static str> {
+    if glob.trim().is_empty() {
+        return Err("is empty");
+    }
+    if glob.len() > MAX_GLOB_LEN {
+        return Err("is too long");
+    }
+    if glob.contains(['/', '\0']) {
+        return Err("can only match file names");
+    }
+    if glob.chars().all(|c| matches!(c, '*' | '?' | '.')) {
+        return Err("matches every file");
+    }
+    if glob.matches('*').count() > MAX_GLOB_WILDCARDS {
+        return Err("has too many wildcards");
+    }
+    let mut depth = 0;
+    for c in glob.chars() {
+        match c {
+            '[' if depth == 0 => depth = 1,
+            '[' => return Err("has a nested character class"),
+            ']' if depth == 1 => depth = 0,
+            _ => {}
+        }
+    }
+    if depth != 0 {
+        return Err("has an unclosed character class");
+    }
+    Ok(())
+}
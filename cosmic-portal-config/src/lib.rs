@@ -1,11 +1,27 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
+pub mod access;
+pub mod background;
+pub mod file_chooser;
+pub mod metrics;
+pub mod open_uri;
+pub mod requests;
+pub mod screencast;
 pub mod screenshot;
+pub mod shortcuts;
 
 use cosmic_config::{cosmic_config_derive::CosmicConfigEntry, CosmicConfigEntry};
 use serde::{Deserialize, Serialize};
 
+use access::Access;
+use background::Background;
+use file_chooser::FileChooser;
+use metrics::Metrics;
+use open_uri::OpenUri;
+use requests::Requests;
+use screencast::Screencast;
 use screenshot::Screenshot;
+use shortcuts::Shortcuts;
 
 pub const APP_ID: &str = "com.system76.CosmicPortal";
 pub const CONFIG_VERSION: u64 = 1;
@@ -17,6 +33,26 @@ pub const CONFIG_VERSION: u64 = 1;
 pub struct Config {
     /// Interactive screenshot settings
     pub screenshot: Screenshot,
+    /// Screen sharing settings
+    pub screencast: Screencast,
+    /// Access dialog settings
+    pub access: Access,
+    /// Review of background permissions
+    pub background: Background,
+    /// Keyboard shortcuts for dialog actions
+    pub shortcuts: Shortcuts,
+    /// Confirmation of URIs opened by apps
+    pub open_uri: OpenUri,
+    /// Limits on pending requests
+    pub requests: Requests,
+    /// File chooser settings
+    pub file_chooser: FileChooser,
+    /// Local counts of how the portal is used
+    pub metrics: Metrics,
+    /// Show dialogs as quickly as possible, for answering many prompts in a row. Dialogs
+    /// skip any delay before they appear, take keyboard focus when mapped, and leave out
+    /// content that loads after they're shown.
+    pub instant_dialogs: bool,
 }
 
 impl Config {
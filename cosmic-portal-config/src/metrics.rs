@@ -0,0 +1,15 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+/// Counts of how the portal is used, kept on this machine only
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Metrics {
+    /// Count requests by interface, answers of access dialogs, and time spent sharing the
+    /// screen. Counts are kept in the state directory, shown by
+    /// `xdg-desktop-portal-cosmic --stats`, and never sent anywhere.
+    pub enabled: bool,
+    /// Also count by app ID, while `enabled`
+    pub per_app: bool,
+}
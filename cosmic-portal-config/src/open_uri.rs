@@ -0,0 +1,28 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenUri {
+    /// URI schemes to confirm before opening, naming the app that will handle them.
+    ///
+    /// `file`, `http` and `https` are never confirmed. Only URIs the frontend asks us to
+    /// choose an app for are checked: it opens the URI itself, without asking, when the
+    /// scheme has a single or default handler.
+    pub confirm_schemes: Vec<String>,
+    /// URI schemes apps are never allowed to open, with the same gap as `confirm_schemes`
+    pub blocked_schemes: Vec<String>,
+}
+
+impl Default for OpenUri {
+    fn default() -> Self {
+        Self {
+            confirm_schemes: ["ssh", "sftp", "telnet", "vnc", "rdp", "spice", "smb"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            blocked_schemes: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Limits on requests waiting for the user, per portal interface
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Requests {
+    /// Limit for interfaces not in `interfaces`
+    pub default: Limit,
+    /// Limits by interface name without the `org.freedesktop.impl.portal.` prefix, like `Access`
+    pub interfaces: BTreeMap<String, Limit>,
+}
+
+impl Requests {
+    pub fn limit(&self, interface: &str) -> Limit {
+        self.interfaces
+            .get(interface)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limit {
+    /// Most requests of the interface shown or waiting to be shown at once, or no limit.
+    ///
+    /// Requests beyond it are held before reaching the dialog, so a burst of requests
+    /// can't flood the event queue shared by all interfaces.
+    pub capacity: Option<usize>,
+    /// What happens to a request arriving when `capacity` requests are pending
+    pub overflow: Overflow,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum Overflow {
+    /// Wait until a pending request is answered. The caller's D-Bus call stays open, and
+    /// may time out if the user takes long to answer.
+    #[default]
+    Block,
+    /// Cancel the oldest pending request to make room. The app that sent it gets a
+    /// cancelled response, as if the user had dismissed the dialog.
+    CancelOldest,
+}
@@ -0,0 +1,35 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Screencast {
+    /// What consumers see while sharing is paused
+    pub paused_frame: PausedFrame,
+    /// Notify when a session ends, with what was shared with which app and for how long
+    pub session_summary: bool,
+    /// Stream to old consumers that only support BGRA, with a single shm buffer at up to
+    /// 30 fps. Otherwise their streams fail with an error naming the missing format.
+    pub legacy_consumers: bool,
+}
+
+impl Default for Screencast {
+    fn default() -> Self {
+        Self {
+            paused_frame: PausedFrame::default(),
+            session_summary: true,
+            legacy_consumers: false,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum PausedFrame {
+    /// Keep showing the last frame captured
+    #[default]
+    Frozen,
+    /// Show a blank slate
+    Slate,
+}
@@ -2,11 +2,47 @@
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
-#[serde(deny_unknown_fields)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Screenshot {
     pub save_location: ImageSaveLocation,
     pub choice: Choice,
+    /// Longest side of a screenshot in pixels. Larger screenshots are downscaled to fit.
+    pub max_dimension: u32,
+    /// Command run on each screenshot saved to a file before it's returned, like to
+    /// annotate or upload it. Screenshots copied to the clipboard aren't passed to it.
+    pub hook: Option<Hook>,
+}
+
+impl Default for Screenshot {
+    fn default() -> Self {
+        Self {
+            save_location: ImageSaveLocation::default(),
+            choice: Choice::default(),
+            max_dimension: 16384,
+            hook: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hook {
+    /// Program and its arguments, to which the screenshot's path is added. If the first
+    /// line it prints is the path of another file, like an edited copy, that's returned
+    /// instead.
+    pub command: Vec<String>,
+    /// Seconds the hook may run before it's killed, and the screenshot returned as saved
+    pub timeout_secs: u32,
+}
+
+impl Default for Hook {
+    fn default() -> Self {
+        Self {
+            command: Vec::new(),
+            timeout_secs: 30,
+        }
+    }
 }
 
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
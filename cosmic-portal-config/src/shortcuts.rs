@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: GPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Shortcuts {
+    /// Shortcuts in addition to Enter and Escape, like `Ctrl+Enter` or `D`
+    pub bindings: Vec<Shortcut>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Shortcut {
+    /// Modifiers and a key, joined by `+`
+    pub keys: String,
+    pub action: Action,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub enum Action {
+    /// Allow, share, or capture
+    Accept,
+    /// Deny or cancel
+    Cancel,
+}
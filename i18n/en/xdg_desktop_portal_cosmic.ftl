@@ -1,5 +1,25 @@
 allow = Allow
+    .mnemonic = A
 cancel = Cancel
+    .mnemonic = C
+button-mnemonic = {$label} (Alt+{$key})
+open = Open
+show-details = Show details
+hide-details = Hide details
+show-more = Show more
+show-less = Show less
+apply-to-similar = Apply to {$count ->
+    [one] 1 more similar request
+    *[other] {$count} more similar requests
+}
+unattended-deny = Denied automatically after {$seconds ->
+    [one] 1 second
+    *[other] {$seconds} seconds
+}
+unattended-allow = Allowed automatically after {$seconds ->
+    [one] 1 second
+    *[other] {$seconds} seconds
+}
 capture = Capture
 share = Share
 save-to = Save to
@@ -7,9 +27,90 @@ save-to = Save to
     .pictures = { save-to } Pictures
     .documents = { save-to } Documents
 choose-folder = Choose folder
+archive-contents = Files in "{$archive}"
+    .description = The file you choose will be extracted from the archive, and a copy will be opened.
+archive-extract = Extract
+archive-search = Search files
+being-written = {$count ->
+    [one] This file may still be being written
+    *[other] {$count} files may still be being written
+}
+    .description = Another program seems to be writing to it, like a download in progress. Opening it now may give the app an incomplete file.
+    .open = Open anyway
+file-category = Category
+    .images = Images
+    .documents = Documents
+    .video = Video
+    .audio = Audio
+file-category-active = Only {$category} are shown, which may leave folders looking empty.
+open-copy = Open a copy
+    .description = The app gets a copy of each file, so it can't change the originals.
+    .copying = {$count ->
+        [one] Copying the file to open
+        *[other] Copying {$count} files to open
+    }
 
 share-screen = Share your screen
     .description = The system wants to share the contents of your screen with "{$app_name}". Select a screen or window to share.
+show-cursor-on = Show pointer on {$source}
+share-summary = Stopped sharing
+    .description = Shared {$sources} with {$app_name} for {$duration}
+share-duration-minutes = {$minutes ->
+    [one] 1 minute
+    *[other] {$minutes} minutes
+}
+share-duration-seconds = {$seconds} seconds
 unknown-application = Unknown Application
+option-unavailable = {$label} (unavailable)
+    .no-audio = No audio device is available
+access-answered = Answered automatically
+    .allowed = Allowed "{$app_name}" without asking: {$title}
+    .denied = Denied "{$app_name}" without asking: {$title}
+choices-summary = You're allowing: {$choices}
+    .option = {$label}: {$option}
+list-pair = {$first} and {$second}
+list-middle = {$first}, {$rest}
+list-end = {$first}, and {$second}
+unidentified-app = The requesting app didn't identify itself. Only allow this if you expected it.
 output = Output
 window = Window
+select-region = Select a region…
+copy-text = Copy text
+recognizing-text = Recognizing text…
+compare = Compare
+keyboard-selection-hint = Arrow keys move the selection, Shift+arrow keys resize it
+comparison-skipped = Screenshots not compared
+    .description = The selection doesn't match the previous screenshot, so no comparison was saved.
+
+usb-access = Allow access to USB devices?
+    .description = "{$app_name}" wants to access {$devices}.
+usb-remember = Remember while connected
+unknown-device = Unknown device
+
+choose-application = Choose an application
+    .description = "{$app_name}" wants to open {$subject}.
+open-with = Open with
+open-uri = Open link in another application?
+    .description = "{$app_name}" wants to open {$uri} with "{$target_name}".
+open-uri-always = Always allow for this application
+
+background-review = Apps running in the background
+    .description = These apps can run in the background or when you log in: {$app_names}
+background-keep = Keep { $app_name }
+background-revoke = Revoke { $app_name }
+
+prompt-template-device =
+    .title = Allow access to your {$permission}?
+    .body = "{$app_name}" wants to use your {$permission}.
+prompt-template-background =
+    .title = Allow "{$app_name}" to run in the background?
+    .body = "{$app_name}" wants to keep running after its windows are closed.
+permission-camera = camera
+permission-microphone = microphone
+permission-speakers = speakers
+permission-location = location
+privacy-note = What this allows
+    .camera = The app can record video and take pictures with your camera while it has access.
+    .microphone = The app can record and listen to anything your microphone picks up while it has access.
+    .screen = The app can see everything shown on what you share, including notifications and anything private you open while sharing.
+    .location = The app can learn where you are, and follow where you go while it has access.
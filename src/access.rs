@@ -1,102 +1,889 @@
 #![allow(dead_code, unused_variables)]
 
-use cosmic::iced::wayland::actions::layer_surface::SctkLayerSurfaceSettings;
-use cosmic::iced::wayland::actions::window::SctkWindowSettings;
+use cosmic::iced::wayland::actions::{
+    layer_surface::{IcedOutput, SctkLayerSurfaceSettings},
+    window::SctkWindowSettings,
+};
 use cosmic::iced_sctk::commands::layer_surface::{destroy_layer_surface, get_layer_surface};
 use cosmic::iced_sctk::commands::window::{close_window, get_window};
-use cosmic::widget::{button, container, dropdown, horizontal_space, icon, text, Row};
+use cosmic::widget::{
+    button, checkbox, container, dropdown, horizontal_space, icon, scrollable, text, Column, Row,
+};
 use cosmic::{
     iced::{
+        keyboard,
         widget::{column, row},
         window, Length,
     },
     iced_core::Alignment,
 };
+use cosmic_client_toolkit::sctk::shell::wlr_layer::Anchor;
+use futures::future::abortable;
 use once_cell::sync::Lazy;
-use tokio::sync::mpsc::Sender;
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::sync::{broadcast, mpsc::Sender};
+use wayland_client::protocol::wl_output::WlOutput;
 use zbus::zvariant;
 
+use crate::config::{
+    self,
+    access::{Answer, DialogSurface, PromptStyle, UnidentifiedApp},
+    shortcuts::Action,
+};
+use crate::ordered_dict::OrderedDict;
+use crate::request_limit::RequestLimit;
+use crate::responder::Responder;
+use crate::shortcuts;
 use crate::wayland::WaylandHelper;
+use crate::widget::keyboard_wrapper::KeyboardWrapper;
 use crate::{app::CosmicPortal, fl};
-use crate::{subscription, PortalResponse};
+use crate::{subscription, PortalResponse, Request};
 
-#[derive(zvariant::DeserializeDict, zvariant::Type, Debug, Clone)]
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug, Clone, Default, PartialEq)]
 #[zvariant(signature = "a{sv}")]
 pub(crate) struct AccessDialogOptions {
-    modal: Option<bool>,
-    deny_label: Option<String>,
-    grant_label: Option<String>,
-    icon: Option<String>,
+    pub modal: Option<bool>,
+    pub deny_label: Option<String>,
+    pub grant_label: Option<String>,
+    pub icon: Option<String>,
     //(ID returned with the response, choices (ID, label), label, initial selection or "" meaning the portal should choose)
     #[allow(clippy::type_complexity)]
-    choices: Option<Vec<(String, String, Vec<(String, String)>, String)>>,
+    pub choices: Option<Vec<(String, String, Vec<(String, String)>, String)>>,
+    /// Show choices in the button row instead of above it, for compact prompts
+    pub inline_choices: Option<bool>,
+    /// Edge or corner to show the dialog at, like `top` or `bottom-right`, instead of centered
+    #[zvariant(rename = "com.system76.cosmic.placement")]
+    pub placement: Option<String>,
+    /// Text too long for the body, like a full URI, shown when the user expands it
+    #[zvariant(rename = "com.system76.cosmic.details")]
+    pub details: Option<String>,
+    /// Show the dialog on every output, for prompts the user mustn't miss
+    #[zvariant(rename = "com.system76.cosmic.all-outputs")]
+    pub all_outputs: Option<bool>,
+    /// Types of choices' values by choice ID, `boolean`, `integer` or `double`, to also
+    /// return them typed
+    #[zvariant(rename = "com.system76.cosmic.choice-types")]
+    pub choice_types: Option<HashMap<String, String>>,
+    /// Localized template for the title and body, used instead of the text passed in
+    #[zvariant(rename = "com.system76.cosmic.template")]
+    pub template: Option<String>,
+    /// Kind of access asked for, like `camera`, for templates to name
+    #[zvariant(rename = "com.system76.cosmic.permission")]
+    pub permission: Option<String>,
+    /// Most restrictive option by choice ID, pre-selected instead of the first for choices
+    /// without an initial selection if `restrictive_defaults` is configured
+    #[zvariant(rename = "com.system76.cosmic.restrictive-choices")]
+    pub restrictive_choices: Option<HashMap<String, String>>,
+    /// Options that can't be chosen, by choice ID then option ID, with why. `true` is the
+    /// option of a boolean choice that can't be checked. The portal adds those that can't
+    /// work on this system.
+    #[zvariant(rename = "com.system76.cosmic.unavailable-options")]
+    pub unavailable: Option<HashMap<String, HashMap<String, String>>>,
+    /// Width in logical pixels the dialog should have instead of fitting its content, for
+    /// wide content like tables. Kept within what fits the output.
+    #[zvariant(rename = "com.system76.cosmic.width")]
+    pub width: Option<u32>,
+}
+
+impl AccessDialogOptions {
+    /// The icon passed, or else the default for the kind of access asked for
+    fn dialog_icon(&self) -> &str {
+        if let Some(icon) = self.icon.as_deref() {
+            return icon;
+        }
+        let permission = self.permission.as_deref();
+        SEMANTIC_ICONS
+            .iter()
+            .find(|(semantic, _)| Some(*semantic) == permission)
+            .map_or(DEFAULT_ICON, |(_, icon)| icon)
+    }
+
+    fn placement_anchor(&self) -> Option<Anchor> {
+        let placement = self.placement.as_deref()?;
+        let mut anchor = Anchor::empty();
+        for edge in placement.split('-') {
+            anchor |= match edge {
+                "top" => Anchor::TOP,
+                "bottom" => Anchor::BOTTOM,
+                "left" => Anchor::LEFT,
+                "right" => Anchor::RIGHT,
+                _ => {
+                    log::warn!("Unknown access dialog placement {placement:?}");
+                    return None;
+                }
+            };
+        }
+        Some(anchor)
+    }
+
+    /// Parse the known keys of a raw options dict
+    fn from_raw(raw: &HashMap<String, zvariant::OwnedValue>) -> zvariant::Result<Self> {
+        let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+        let encoded = zvariant::to_bytes(ctxt, raw)?;
+        encoded.deserialize().map(|(options, _)| options)
+    }
 }
 
 pub static ACCESS_ID: Lazy<window::Id> = Lazy::new(window::Id::unique);
 
-#[derive(zvariant::SerializeDict, zvariant::Type, Debug, Clone)]
+// The `access` config, kept current as the config changes so D-Bus queries don't read it
+// from disk
+static SETTINGS: Lazy<RwLock<config::access::Access>> = Lazy::new(Default::default);
+
+/// Apply `config` to queries from now on
+pub fn configure(config: &config::Config) {
+    *SETTINGS.write().unwrap() = config.access.clone();
+}
+
+// Longest body from the requesting app shown before "Show more"
+const MAX_REASON_CHARS: usize = 200;
+// Longest summary of choices shown in full, beyond which it's in a tooltip
+const MAX_SUMMARY_CHARS: usize = 120;
+// Version of the Access interface we implement
+const VERSION: u32 = 1;
+// Times a dialog is shown again after the compositor closes it, before giving up
+const MAX_RELOCATIONS: u32 = 3;
+// Longest icon name taken from a caller
+const MAX_ICON_NAME_LEN: usize = 128;
+// Our icons for semantic names callers may pass instead of an icon name, also the default
+// icon of dialogs asking for that kind of access
+const SEMANTIC_ICONS: &[(&str, &str)] = &[
+    ("camera", "camera-web-symbolic"),
+    ("microphone", "audio-input-microphone-symbolic"),
+    ("location", "find-location-symbolic"),
+    ("folder", "folder-symbolic"),
+];
+// Icon of dialogs without one, for access of other kinds
+const DEFAULT_ICON: &str = "dialog-question-symbolic";
+// Resolutions kept for observers that fall behind, which miss older ones
+const RESOLUTION_CAPACITY: usize = 64;
+
+static RESOLUTIONS: Lazy<broadcast::Sender<Resolution>> =
+    Lazy::new(|| broadcast::channel(RESOLUTION_CAPACITY).0);
+
+/// How a dialog answered a request, for observers besides the request itself
+#[derive(Clone, Debug)]
+pub(crate) struct Resolution {
+    pub handle: zvariant::ObjectPath<'static>,
+    pub app_id: String,
+    pub title: String,
+    pub permission: Option<String>,
+    pub response: PortalResponse<AccessDialogResult>,
+}
+
+/// Observe the resolution of each dialog from now on.
+///
+/// Observers don't hold up or change responses. One that falls behind by more than
+/// `RESOLUTION_CAPACITY` misses the oldest, and its next `recv` reports how many.
+pub(crate) fn subscribe_resolutions() -> broadcast::Receiver<Resolution> {
+    RESOLUTIONS.subscribe()
+}
+
+/// Log the resolution of each dialog
+pub(crate) async fn log_resolutions() {
+    let mut resolutions = subscribe_resolutions();
+    loop {
+        match resolutions.recv().await {
+            Ok(resolution) => {
+                let outcome = match &resolution.response {
+                    PortalResponse::Success(_) => "granted",
+                    PortalResponse::Cancelled => "denied",
+                    PortalResponse::Other | PortalResponse::Error(_) => "failed",
+                };
+                log::debug!(
+                    "access {} for {:?}, {:?}: {outcome}",
+                    resolution.handle,
+                    resolution.app_id,
+                    resolution.title
+                );
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                log::warn!("missed logging {missed} access resolutions");
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+#[derive(
+    zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, Clone, PartialEq,
+)]
 #[zvariant(signature = "a{sv}")]
 pub struct AccessDialogResult {
+    /// In the order the request gave them
     choices: Vec<(String, String)>,
+    /// Values of choices typed `boolean` in the request's `choice-types`
+    #[zvariant(rename = "com.system76.cosmic.boolean-choices")]
+    boolean_choices: Option<OrderedDict<bool>>,
+    /// Values of choices typed `integer`
+    #[zvariant(rename = "com.system76.cosmic.integer-choices")]
+    integer_choices: Option<OrderedDict<i64>>,
+    /// Values of choices typed `double`
+    #[zvariant(rename = "com.system76.cosmic.double-choices")]
+    double_choices: Option<OrderedDict<f64>>,
+}
+
+impl AccessDialogResult {
+    /// Result with `choices` as strings, and also typed as `choice_types` asks, all in
+    /// the order of `choices`
+    fn new(choices: Vec<(String, String)>, choice_types: Option<&HashMap<String, String>>) -> Self {
+        let mut boolean_choices = OrderedDict::new();
+        let mut integer_choices = OrderedDict::new();
+        let mut double_choices = OrderedDict::new();
+        for (id, value) in &choices {
+            let Some(kind) = choice_types.and_then(|x| x.get(id)) else {
+                continue;
+            };
+            let parsed = match kind.as_str() {
+                "boolean" => value
+                    .parse()
+                    .map(|x| boolean_choices.insert(id.clone(), x))
+                    .is_ok(),
+                "integer" => value
+                    .parse()
+                    .map(|x| integer_choices.insert(id.clone(), x))
+                    .is_ok(),
+                // Not NaN or infinity, which callers are unlikely to handle
+                "double" => value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|x| x.is_finite())
+                    .map(|x| double_choices.insert(id.clone(), x))
+                    .is_some(),
+                _ => {
+                    log::warn!("unknown type {kind:?} of choice {id:?}");
+                    continue;
+                }
+            };
+            if !parsed {
+                log::warn!("value {value:?} of choice {id:?} isn't a {kind}");
+            }
+        }
+        fn non_empty<T>(dict: OrderedDict<T>) -> Option<OrderedDict<T>> {
+            (!dict.is_empty()).then_some(dict)
+        }
+        Self {
+            choices,
+            boolean_choices: non_empty(boolean_choices),
+            integer_choices: non_empty(integer_choices),
+            double_choices: non_empty(double_choices),
+        }
+    }
+
+    /// Value selected for the choice with `id`
+    pub fn choice(&self, id: &str) -> Option<&str> {
+        self.choices
+            .iter()
+            .find(|(choice_id, _)| choice_id == id)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A choice offered by the dialog, with the user's current selection
+#[derive(Debug, Clone)]
+pub enum Choice {
+    /// One of several options, shown as a dropdown
+    Options {
+        id: String,
+        label: String,
+        ids: Vec<String>,
+        labels: Vec<String>,
+        /// Why each option can't be chosen, if it can't
+        unavailable: Vec<Option<String>>,
+        selected: usize,
+    },
+    /// A choice without options is boolean, shown as a checkbox
+    Boolean {
+        id: String,
+        label: String,
+        checked: bool,
+        /// Why it can't be checked, if it can't
+        unavailable: Option<String>,
+    },
+}
+
+impl Choice {
+    /// Choice from its descriptor, with the option at `fallback` selected if the descriptor
+    /// has no initial selection, or else the first. Options `unavailable` by ID are never
+    /// selected, unless all are.
+    fn new(
+        (id, label, options, initial): (String, String, Vec<(String, String)>, String),
+        fallback: Option<usize>,
+        unavailable: Option<&HashMap<String, String>>,
+    ) -> Self {
+        let reason = |option_id: &str| unavailable.and_then(|x| x.get(option_id)).cloned();
+        if options.is_empty() {
+            let unavailable = reason("true");
+            return Choice::Boolean {
+                id,
+                label,
+                checked: initial == "true" && unavailable.is_none(),
+                unavailable,
+            };
+        }
+        let unavailable: Vec<_> = options.iter().map(|(x, _)| reason(x)).collect();
+        let selected = options
+            .iter()
+            .position(|(option_id, _)| *option_id == initial)
+            .or(fallback.filter(|_| initial.is_empty()))
+            .filter(|x| unavailable[*x].is_none())
+            .or_else(|| unavailable.iter().position(Option::is_none))
+            .unwrap_or(0);
+        let (ids, labels): (Vec<_>, Vec<_>) = options.into_iter().unzip();
+        // The dropdown can't grey out options, so they're marked in their labels
+        let labels = labels
+            .into_iter()
+            .zip(&unavailable)
+            .map(|(label, reason)| match reason {
+                Some(_) => fl!("option-unavailable", label = label),
+                None => label,
+            })
+            .collect();
+        Choice::Options {
+            id,
+            label,
+            ids,
+            labels,
+            unavailable,
+            selected,
+        }
+    }
+
+    /// `(choice ID, selected option ID)`, as returned with the response
+    fn result(&self) -> (String, String) {
+        match self {
+            Choice::Options {
+                id, ids, selected, ..
+            } => (id.clone(), ids[*selected].clone()),
+            Choice::Boolean { id, checked, .. } => (id.clone(), checked.to_string()),
+        }
+    }
+
+    fn view(&self, i: usize) -> cosmic::Element<Msg> {
+        match self {
+            Choice::Options {
+                label,
+                labels,
+                unavailable,
+                selected,
+                ..
+            } => {
+                let picker =
+                    selected_outline(dropdown(labels.as_slice(), Some(*selected), move |j| {
+                        Msg::Choice(i, j)
+                    }));
+                let mut reasons: Vec<&str> =
+                    unavailable.iter().flatten().map(String::as_str).collect();
+                reasons.sort_unstable();
+                reasons.dedup();
+                row![
+                    text(label.as_str()),
+                    unavailable_tooltip(picker, reasons.join("\n"))
+                ]
+                .spacing(8)
+                .align_items(Alignment::Center)
+                .into()
+            }
+            Choice::Boolean {
+                label,
+                checked,
+                unavailable,
+                ..
+            } => {
+                let toggle = checkbox(label.as_str(), *checked);
+                match unavailable {
+                    // Disabled, without `on_toggle`
+                    Some(reason) => unavailable_tooltip(toggle, reason.clone()),
+                    None => toggle
+                        .on_toggle(move |checked| Msg::Toggle(i, checked))
+                        .into(),
+                }
+            }
+        }
+    }
+
+    /// What the selection grants, for the summary of choices, or `None` if it grants nothing
+    fn summary(&self) -> Option<String> {
+        match self {
+            Choice::Options {
+                label,
+                labels,
+                selected,
+                ..
+            } => Some(fl!(
+                "choices-summary",
+                "option",
+                label = label.trim_end_matches(':'),
+                option = labels[*selected].as_str()
+            )),
+            Choice::Boolean { label, checked, .. } => {
+                checked.then(|| label.trim_end_matches(':').to_string())
+            }
+        }
+    }
+
+    /// Whether the option `j` of the choice can't be chosen
+    fn is_unavailable(&self, j: usize) -> bool {
+        match self {
+            Choice::Options { unavailable, .. } => unavailable.get(j).is_some_and(Option::is_some),
+            Choice::Boolean { unavailable, .. } => unavailable.is_some(),
+        }
+    }
+}
+
+/// `content` with a tooltip telling why some of what it offers is unavailable, if anything is
+fn unavailable_tooltip<'a>(
+    content: impl Into<cosmic::Element<'a, Msg>>,
+    reasons: String,
+) -> cosmic::Element<'a, Msg> {
+    if reasons.is_empty() {
+        return content.into();
+    }
+    cosmic::widget::tooltip(
+        content,
+        text(reasons),
+        cosmic::widget::tooltip::Position::Bottom,
+    )
+    .into()
+}
+
+/// `dropdown` outlined in the accent color, so the option it holds reads as the selected
+/// one in light and dark themes. Its menu marks the same option with the accent color.
+fn selected_outline<'a>(dropdown: impl Into<cosmic::Element<'a, Msg>>) -> cosmic::Element<'a, Msg> {
+    container(dropdown)
+        .padding(1)
+        .style(cosmic::theme::Container::Custom(Box::new(|theme| {
+            let theme = theme.cosmic();
+            cosmic::iced_style::container::Appearance {
+                border: cosmic::iced_core::Border {
+                    color: theme.accent.base.into(),
+                    width: 1.0,
+                    radius: theme.corner_radii.radius_s.into(),
+                },
+                ..Default::default()
+            }
+        })))
+        .into()
 }
 
 pub struct Access {
     wayland_helper: WaylandHelper,
     tx: Sender<subscription::Event>,
+    limit: RequestLimit,
 }
 
 impl Access {
     pub fn new(wayland_helper: WaylandHelper, tx: Sender<subscription::Event>) -> Self {
-        Self { wayland_helper, tx }
+        Self {
+            wayland_helper,
+            tx,
+            limit: RequestLimit::new("Access"),
+        }
     }
 }
 
-#[zbus::interface(name = "")]
 impl Access {
     #[allow(clippy::too_many_arguments)]
-    async fn access_dialog(
+    async fn access_dialog_inner(
         &self,
+        connection: &zbus::Connection,
         handle: zvariant::ObjectPath<'_>,
         app_id: &str,
         parent_window: &str,
         title: &str,
         subtitle: &str,
         body: &str,
-        options: AccessDialogOptions,
+        raw_options: HashMap<String, zvariant::OwnedValue>,
     ) -> PortalResponse<AccessDialogResult> {
         // TODO send event to subscription via channel
         // await response via channel
-        log::debug!("Access dialog {app_id} {parent_window} {title} {subtitle} {body} {options:?}");
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        if let Err(err) = self
-            .tx
-            .send(subscription::Event::Access(AccessDialogArgs {
-                handle: handle.to_owned(),
-                app_id: app_id.to_string(),
-                parent_window: parent_window.to_string(),
-                title: title.to_string(),
-                subtitle: subtitle.to_string(),
-                body: body.to_string(),
-                options,
-                tx,
-            }))
-            .await
-        {
-            log::error!("Failed to send access dialog event, {err}");
+        log::debug!(
+            "Access dialog {app_id} {parent_window} {title} {subtitle} {body} {raw_options:?}"
+        );
+        let mut options = match AccessDialogOptions::from_raw(&raw_options) {
+            Ok(options) => options,
+            Err(err) => {
+                log::error!("Invalid access dialog options: {err}");
+                return PortalResponse::Other;
+            }
+        };
+        options.icon = options.icon.as_deref().and_then(sanitize_icon);
+        let (mut title, mut body) = (title.to_string(), body.to_string());
+        let templated = apply_template(app_id, &mut title, &mut body, &mut options).await;
+        mark_unavailable(&mut options).await;
+        let (tx, mut rx) = Responder::channel();
+        let args = AccessDialogArgs {
+            handle: handle.to_owned(),
+            app_id: app_id.to_string(),
+            parent_window: parent_window.to_string(),
+            title,
+            subtitle: subtitle.to_string(),
+            body,
+            // Templates are our own text
+            body_from_app: !templated,
+            options,
+            raw_options: Arc::new(raw_options),
+            tx,
+            modal: false,
+            outputs: Vec::new(),
+            relocations: 0,
+            shown: false,
+            instant: false,
+            requested: Some(Instant::now()),
+            deadline: None,
+            attached: Vec::new(),
+        };
+        let (abortable, abort_handle) = abortable(async {
+            let _slot = self.limit.acquire(&handle, &self.tx).await;
+            if let Err(err) = self.tx.send(subscription::Event::Access(args)).await {
+                log::error!("Failed to send access dialog event, {err}");
+            }
+            rx.recv().await
+        });
+        let _ = connection
+            .object_server()
+            .at(&handle, Request(abort_handle))
+            .await;
+        let resp = abortable.await;
+        let _ = connection
+            .object_server()
+            .remove::<Request, _>(&handle)
+            .await;
+        match resp {
+            Ok(Some(res)) => res,
+            Ok(None) => PortalResponse::Cancelled::<AccessDialogResult>,
+            Err(_) => {
+                // Closed with `Request.Close`
+                if let Err(err) = self
+                    .tx
+                    .send(subscription::Event::CancelAccess(handle.to_owned()))
+                    .await
+                {
+                    log::error!("Failed to send access dialog cancel event, {err}");
+                }
+                PortalResponse::Cancelled::<AccessDialogResult>
+            }
         }
-        if let Some(res) = rx.recv().await {
-            res
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.Access")]
+impl Access {
+    #[allow(clippy::too_many_arguments)]
+    #[zbus(out_args("response", "results"))]
+    async fn access_dialog(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        app_id: &str,
+        parent_window: &str,
+        title: &str,
+        subtitle: &str,
+        body: &str,
+        raw_options: HashMap<String, zvariant::OwnedValue>,
+    ) -> PortalResponse<AccessDialogResult> {
+        let response = self
+            .access_dialog_inner(
+                connection,
+                handle.clone(),
+                app_id,
+                parent_window,
+                title,
+                subtitle,
+                body,
+                raw_options,
+            )
+            .await;
+        crate::emit_response(connection, &handle, &response).await;
+        response
+    }
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        VERSION
+    }
+}
+
+/// Replace `title` and `body` with the template named by `options.template`.
+///
+/// Templates are Fluent messages named `prompt-template-<template>`, with `title` and
+/// `body` attributes. They can only use the variables `app_name` and `permission`, the
+/// latter localized by a `permission-<permission>` message if there is one. The dialog's
+/// icon defaults to the app's. Unknown templates leave the text as passed in.
+///
+/// Returns whether a template was applied.
+async fn apply_template(
+    app_id: &str,
+    title: &mut String,
+    body: &mut String,
+    options: &mut AccessDialogOptions,
+) -> bool {
+    let Some(template) = options.template.as_deref() else {
+        return false;
+    };
+    let loader = &*crate::localize::LANGUAGE_LOADER;
+    let message_id = format!("prompt-template-{template}");
+    if !is_message_suffix(template)
+        || !loader.has_attr(&message_id, "title")
+        || !loader.has_attr(&message_id, "body")
+    {
+        log::warn!("unknown access dialog template {template:?}");
+        return false;
+    }
+    let app_name = if app_id.is_empty() {
+        fl!("unknown-application")
+    } else {
+        crate::screencast_dialog::app_name(app_id)
+            .await
+            .unwrap_or_else(|| app_id.to_string())
+    };
+    let permission = options
+        .permission
+        .as_deref()
+        .map_or_else(String::new, |permission| {
+            let permission_id = format!("permission-{permission}");
+            if is_message_suffix(permission) && loader.has(&permission_id) {
+                loader.get(&permission_id)
+            } else {
+                permission.to_string()
+            }
+        });
+    let args = HashMap::from([("app_name", app_name), ("permission", permission)]);
+    *title = loader.get_attr_args(&message_id, "title", args.clone());
+    *body = loader.get_attr_args(&message_id, "body", args);
+    if options.icon.is_none() && !app_id.is_empty() {
+        options.icon = crate::screencast_dialog::app_icon(app_id).await;
+    }
+    true
+}
+
+// Whether `s` can only complete the ID of a message with a known prefix
+fn is_message_suffix(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// A caller's icon name, mapped to ours if it's a semantic name like `camera`, or `None` if
+/// it isn't a plain icon name.
+///
+/// Names are looked up in the icon theme, so paths and other characters outside those of
+/// the freedesktop icon naming spec would let a caller probe the theme or files.
+fn sanitize_icon(name: &str) -> Option<String> {
+    if let Some((_, icon)) = SEMANTIC_ICONS
+        .iter()
+        .find(|(semantic, _)| *semantic == name)
+    {
+        return Some(icon.to_string());
+    }
+    let valid = !name.is_empty()
+        && name.len() <= MAX_ICON_NAME_LEN
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        log::warn!("ignoring invalid access dialog icon {name:?}");
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// Mark options of `options`' choices that can't work on this system as unavailable, like
+/// sharing audio without an audio device. Boolean choices are looked up by their ID, and
+/// other choices by the IDs of their options.
+async fn mark_unavailable(options: &mut AccessDialogOptions) {
+    let AccessDialogOptions {
+        choices: Some(choices),
+        unavailable,
+        ..
+    } = options
+    else {
+        return;
+    };
+    for (id, _, choice_options, _) in choices.iter() {
+        let subjects: Vec<(&str, &str)> = if choice_options.is_empty() {
+            vec![(id.as_str(), "true")]
         } else {
-            PortalResponse::Cancelled::<AccessDialogResult>
+            choice_options
+                .iter()
+                .map(|(option_id, _)| (option_id.as_str(), option_id.as_str()))
+                .collect()
+        };
+        for (subject, option_id) in subjects {
+            let Some(reason) = system_unavailable(subject).await else {
+                continue;
+            };
+            unavailable
+                .get_or_insert_with(HashMap::new)
+                .entry(id.clone())
+                .or_default()
+                .entry(option_id.to_string())
+                .or_insert(reason);
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Why what `id` names can't work on this system, for the IDs the portal knows
+async fn system_unavailable(id: &str) -> Option<String> {
+    match id {
+        "audio" if !has_audio_device().await => Some(fl!("option-unavailable", "no-audio")),
+        _ => None,
+    }
+}
+
+async fn has_audio_device() -> bool {
+    let pcm = tokio::task::spawn_blocking(|| match std::fs::read_dir("/dev/snd") {
+        Ok(entries) => Some(
+            entries
+                .flatten()
+                .any(|x| x.file_name().to_string_lossy().starts_with("pcm")),
+        ),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Some(false),
+        Err(_) => None,
+    });
+    // Assume there is one if it can't be told
+    pcm.await.ok().flatten().unwrap_or(true)
+}
+
+/// Show the access dialog on behalf of another portal
+///
+/// Returns `None` if access was denied, or the dialog couldn't be shown.
+pub(crate) async fn prompt(
+    tx: &Sender<subscription::Event>,
+    handle: zvariant::ObjectPath<'static>,
+    app_id: &str,
+    mut title: String,
+    subtitle: String,
+    mut body: String,
+    mut options: AccessDialogOptions,
+) -> Option<AccessDialogResult> {
+    apply_template(app_id, &mut title, &mut body, &mut options).await;
+    mark_unavailable(&mut options).await;
+    let (response_tx, mut rx) = Responder::channel();
+    if let Err(err) = tx
+        .send(subscription::Event::Access(AccessDialogArgs {
+            handle,
+            app_id: app_id.to_string(),
+            parent_window: String::new(),
+            title,
+            subtitle,
+            body,
+            body_from_app: false,
+            options,
+            raw_options: Default::default(),
+            tx: response_tx,
+            modal: false,
+            outputs: Vec::new(),
+            relocations: 0,
+            shown: false,
+            instant: false,
+            requested: Some(Instant::now()),
+            deadline: None,
+            attached: Vec::new(),
+        }))
+        .await
+    {
+        log::error!("Failed to send access dialog event, {err}");
+        return None;
+    }
+    match rx.recv().await {
+        Some(PortalResponse::Success(res)) => Some(res),
+        _ => None,
+    }
+}
+
+/// Signals for the shell to coordinate with access prompts on screen, and a way to
+/// dismiss them without keyboard focus
+pub struct Prompts {
+    tx: Sender<subscription::Event>,
+}
+
+impl Prompts {
+    pub fn new(tx: Sender<subscription::Event>) -> Self {
+        Self { tx }
+    }
+}
+
+#[zbus::interface(name = "com.system76.CosmicPortal.Prompts")]
+impl Prompts {
+    /// Cancel the access prompt on screen, as if its cancel button was pressed.
+    ///
+    /// For binding to a global shortcut, so a prompt that can't take keyboard focus can
+    /// still be dismissed.
+    async fn cancel_shown(&self) {
+        if let Err(err) = self.tx.send(subscription::Event::CancelShownAccess).await {
+            log::error!("Failed to send access dialog cancel event, {err}");
+        }
+    }
+
+    /// How a request from `app_id` for `permission` would be answered, without making one.
+    ///
+    /// `permission` is a kind of access like `camera`, as passed in
+    /// `com.system76.cosmic.permission`, or `open-uri:` and a scheme for opening URIs.
+    /// Returns `prompt` if the user would be asked, or else the answer given without
+    /// asking: `allow`, `deny`, or `reject` for an error.
+    async fn query_permission(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        app_id: &str,
+        permission: &str,
+    ) -> String {
+        if let Some(scheme) = permission.strip_prefix("open-uri:") {
+            return crate::app_chooser::query_scheme(connection, app_id, scheme)
+                .await
+                .to_string();
+        }
+        // As `update_args` decides
+        let access = SETTINGS.read().unwrap();
+        let answer = if app_id.is_empty() && access.unidentified_app == UnidentifiedApp::Reject {
+            "reject"
+        } else {
+            match access.unattended.permissions.get(permission) {
+                Some(Answer::Allow) => "allow",
+                Some(Answer::Deny) => "deny",
+                None => "prompt",
+            }
+        };
+        answer.to_string()
+    }
+
+    /// An access prompt for `handle` was shown
+    #[zbus(signal)]
+    pub async fn prompt_shown(
+        ctxt: &zbus::SignalContext<'_>,
+        handle: zvariant::ObjectPath<'_>,
+        app_id: &str,
+    ) -> zbus::Result<()>;
+
+    /// The access prompt for `handle` was closed
+    #[zbus(signal)]
+    pub async fn prompt_hidden(
+        ctxt: &zbus::SignalContext<'_>,
+        handle: zvariant::ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Msg {
     Allow,
     Cancel,
     Choice(usize, usize),
+    Toggle(usize, bool),
+    ToggleDetails,
+    ToggleReason,
+    ApplyToSimilar(bool),
+    /// The grace period before creating the surface of the dialog with this serial passed
+    GraceElapsed(u64),
+    /// The unattended timeout of the dialog shown with this serial passed
+    TimedOut(u64),
+    /// A second of the unattended countdown passed, to show the seconds left
+    Tick,
 }
 
 #[derive(Clone)]
@@ -107,13 +894,188 @@ pub(crate) struct AccessDialogArgs {
     pub title: String,
     pub subtitle: String,
     pub body: String,
+    /// Whether `body` is the requesting app's reason rather than text of our own, shown
+    /// apart from it
+    pub body_from_app: bool,
     pub options: AccessDialogOptions,
-    pub tx: Sender<PortalResponse<AccessDialogResult>>,
+    /// All options as received, including keys `options` doesn't know about
+    pub raw_options: Arc<HashMap<String, zvariant::OwnedValue>>,
+    pub tx: Responder<PortalResponse<AccessDialogResult>>,
+    /// Whether the dialog is a modal window rather than a layer surface, decided when shown
+    pub modal: bool,
+    /// Surface for each output when mirrored on all of them, the first with `ACCESS_ID`.
+    /// Empty for a single surface on the active output.
+    pub outputs: Vec<(window::Id, WlOutput)>,
+    /// Times the dialog was shown again after the compositor closed it
+    pub relocations: u32,
+    /// Whether the dialog's surface was created, which waits for the grace period
+    pub shown: bool,
+    /// Whether the dialog is shown in instant mode, decided when shown
+    pub instant: bool,
+    /// When the request arrived, until the dialog first takes keyboard focus
+    pub requested: Option<Instant>,
+    /// When the dialog is answered for the user if `unattended.timeout_secs` is configured,
+    /// counted from when it was shown
+    pub deadline: Option<Instant>,
+    /// Identical requests answered by the same dialog
+    pub attached: Vec<(
+        zvariant::ObjectPath<'static>,
+        Responder<PortalResponse<AccessDialogResult>>,
+    )>,
 }
 
 impl AccessDialogArgs {
+    /// Whether `other` asks the same thing of the user, so one decision can answer both
+    fn is_duplicate(&self, other: &AccessDialogArgs) -> bool {
+        self.app_id == other.app_id
+            && self.title == other.title
+            && self.subtitle == other.subtitle
+            && self.body == other.body
+            && self.body_from_app == other.body_from_app
+            && self.options == other.options
+    }
+
+    /// The dialog's choices, with the initial selections asked for. Those left for the portal
+    /// to choose get the most restrictive option if `config` says to.
+    fn choices(&self, config: &config::access::Access) -> Vec<Choice> {
+        let choices = self.options.choices.clone().unwrap_or_default();
+        choices
+            .into_iter()
+            .map(|choice| {
+                let (id, _, options, initial) = &choice;
+                let fallback = if config.restrictive_defaults && initial.is_empty() {
+                    let named = self
+                        .options
+                        .restrictive_choices
+                        .as_ref()
+                        .and_then(|x| x.get(id))
+                        .and_then(|named| options.iter().position(|(x, _)| x == named));
+                    let ids: Vec<_> = options.iter().map(|(x, _)| x.as_str()).collect();
+                    let fallback = named.or_else(|| config.most_restrictive(&ids));
+                    if fallback.is_none() && !options.is_empty() {
+                        log::warn!("No option of choice {id:?} is known to be most restrictive");
+                    }
+                    fallback
+                } else {
+                    None
+                };
+                let unavailable = self.options.unavailable.as_ref().and_then(|x| x.get(id));
+                Choice::new(choice, fallback, unavailable)
+            })
+            .collect()
+    }
+
+    /// Classification of the access asked for, which more cautious dialogs key off
+    pub(crate) fn prompt_style(&self, config: &config::access::Access) -> PromptStyle {
+        config.prompt_style(self.options.permission.as_deref())
+    }
+
+    /// Whether `other` asks the same app for the same kind of permission, with the same
+    /// choices, so the user may choose to answer both at once with the same result
+    fn is_similar(&self, other: &AccessDialogArgs) -> bool {
+        self.app_id == other.app_id
+            && self.title == other.title
+            && self.options.permission == other.options.permission
+            && self.options.choice_types == other.options.choice_types
+            && choice_ids(&self.options) == choice_ids(&other.options)
+    }
+
+    /// Remove the attached request `handle` from those this dialog answers
+    fn detach(
+        &mut self,
+        handle: &zvariant::ObjectPath<'static>,
+    ) -> Option<Responder<PortalResponse<AccessDialogResult>>> {
+        let i = self.attached.iter().position(|(h, _)| h == handle)?;
+        Some(self.attached.remove(i).1)
+    }
+
+    /// Send `response` to the request and every attached request, and to observers of
+    /// resolutions. Requests already answered, and so their observers, get nothing.
+    fn send_response(self, response: PortalResponse<AccessDialogResult>) {
+        let requests = std::iter::once((self.handle, self.tx)).chain(self.attached);
+        for (handle, tx) in requests {
+            // Answered now, so a `Request.Close` while it's delayed is dropped
+            if !tx.send_after(response.clone(), crate::response_delay()) {
+                continue;
+            }
+            // Fails only without observers
+            let _ = RESOLUTIONS.send(Resolution {
+                handle,
+                app_id: self.app_id.clone(),
+                title: self.title.clone(),
+                permission: self.options.permission.clone(),
+                response: response.clone(),
+            });
+        }
+    }
+
+    /// Whether to show a modal window, which disrupts a focused fullscreen app less than a
+    /// layer surface above it. The caller's `modal` option takes precedence.
+    fn use_modal(&self, config: &config::access::Access, wayland_helper: &WaylandHelper) -> bool {
+        if let Some(modal) = self.options.modal {
+            return modal;
+        }
+        match config.dialog_surface {
+            DialogSurface::Automatic => {
+                !self.app_id.is_empty()
+                    && wayland_helper.focused_fullscreen_app_id().as_deref()
+                        == Some(self.app_id.as_str())
+            }
+            DialogSurface::Layer => false,
+            DialogSurface::Modal => true,
+        }
+    }
+
+    /// Surfaces to mirror the dialog on every output, starting with the active one
+    fn mirror_outputs(
+        &self,
+        config: &config::access::Access,
+        outputs: &[crate::app::OutputState],
+        active_output: Option<&WlOutput>,
+    ) -> Vec<(window::Id, WlOutput)> {
+        if self.modal || !self.options.all_outputs.unwrap_or(config.all_outputs) {
+            return Vec::new();
+        }
+        let mut outputs: Vec<_> = outputs.iter().map(|o| o.output.clone()).collect();
+        if let Some(i) = outputs.iter().position(|o| Some(o) == active_output) {
+            outputs.swap(0, i);
+        }
+        outputs
+            .into_iter()
+            .enumerate()
+            .map(|(i, output)| {
+                let id = if i == 0 {
+                    *ACCESS_ID
+                } else {
+                    window::Id::unique()
+                };
+                (id, output)
+            })
+            .collect()
+    }
+
+    fn layer_surface(&self, id: window::Id) -> SctkLayerSurfaceSettings {
+        let settings = match self.options.placement_anchor() {
+            Some(anchor) => crate::surfaces::edge_dialog(id, anchor, "access"),
+            None => crate::surfaces::centered_dialog(id, "access"),
+        };
+        if self.instant {
+            crate::surfaces::focused(settings)
+        } else {
+            settings
+        }
+    }
+
     pub(crate) fn get_surface(&self) -> cosmic::Command<Msg> {
-        if self.options.modal.unwrap_or_default() {
+        if !self.modal && !self.outputs.is_empty() {
+            return cosmic::iced::Command::batch(self.outputs.iter().map(|(id, output)| {
+                get_layer_surface(SctkLayerSurfaceSettings {
+                    output: IcedOutput::Output(output.clone()),
+                    ..self.layer_surface(*id)
+                })
+            }));
+        }
+        if self.modal {
             // create a modal surface
             get_window(SctkWindowSettings {
                 window_id: *ACCESS_ID,
@@ -126,23 +1088,21 @@ impl AccessDialogArgs {
             })
         } else {
             // create a layer surface
-            get_layer_surface(SctkLayerSurfaceSettings {
-                id: *ACCESS_ID,
-                layer: cosmic_client_toolkit::sctk::shell::wlr_layer::Layer::Top,
-                keyboard_interactivity:
-                    cosmic_client_toolkit::sctk::shell::wlr_layer::KeyboardInteractivity::OnDemand,
-                pointer_interactivity: true,
-                anchor: cosmic_client_toolkit::sctk::shell::wlr_layer::Anchor::empty(),
-                output: cosmic::iced::wayland::actions::layer_surface::IcedOutput::Active,
-                namespace: "access portal".to_string(),
-                ..Default::default()
-            })
+            get_layer_surface(self.layer_surface(*ACCESS_ID))
         }
     }
 
     pub(crate) fn destroy_surface(&self) -> cosmic::Command<Msg> {
-        if self.options.modal.unwrap_or_default() {
+        if !self.shown {
+            cosmic::iced::Command::none()
+        } else if self.modal {
             close_window(*ACCESS_ID)
+        } else if !self.outputs.is_empty() {
+            cosmic::iced::Command::batch(
+                self.outputs
+                    .iter()
+                    .map(|(id, _)| destroy_layer_surface(*id)),
+            )
         } else {
             destroy_layer_surface(*ACCESS_ID)
         }
@@ -156,89 +1116,571 @@ pub(crate) fn view(portal: &CosmicPortal) -> cosmic::Element<Msg> {
     };
 
     let choices = &portal.access_choices;
-    let mut options = Vec::with_capacity(choices.len() + 3);
-    for (i, choice) in choices.iter().enumerate() {
-        options.push(dropdown(choice.1.as_slice(), choice.0, move |j| Msg::Choice(i, j)).into());
-    }
-    options.push(horizontal_space(Length::Fill).into());
-    options.push(
-        button::text(
-            args.options
-                .deny_label
-                .clone()
-                .unwrap_or_else(|| fl!("cancel")),
-        )
-        .on_press(Msg::Cancel)
-        .into(),
+    let inline = args.options.inline_choices.unwrap_or_default();
+    let layout = Layout::new(dialog_output_size(portal), spacing);
+    let mut buttons = Vec::with_capacity(choices.len() + 3);
+    if inline {
+        buttons.extend(choices.iter().enumerate().map(|(i, choice)| choice.view(i)));
+    }
+    if !layout.stack_buttons {
+        buttons.push(horizontal_space(Length::Fill).into());
+    }
+    let button_width = if layout.stack_buttons {
+        Length::Fill
+    } else {
+        Length::Shrink
+    };
+    let (labels, mnemonics) = button_labels(args);
+    let [deny_label, grant_label] = labels;
+    let style = args.prompt_style(&portal.config.access);
+    let (deny_style, grant_style) = match style {
+        PromptStyle::Standard => (
+            cosmic::theme::Button::Standard,
+            cosmic::theme::Button::Suggested,
+        ),
+        PromptStyle::Destructive => (
+            cosmic::theme::Button::Suggested,
+            cosmic::theme::Button::Destructive,
+        ),
+    };
+    buttons.push(
+        button::text(deny_label)
+            .on_press(Msg::Cancel)
+            .style(deny_style)
+            .width(button_width)
+            .into(),
+    );
+    buttons.push(
+        button::text(grant_label)
+            .on_press(Msg::Allow)
+            .style(grant_style)
+            .width(button_width)
+            .into(),
     );
-    options.push(
-        button::text(
-            args.options
-                .grant_label
-                .clone()
-                .unwrap_or_else(|| fl!("allow")),
+    let mut stacked_choices = stacked_view(
+        args.options.details.as_deref(),
+        portal.access_details_shown,
+        (!inline).then_some(choices.as_slice()),
+        spacing,
+    );
+    let similar = similar_queued(portal);
+    if similar > 0 {
+        stacked_choices.push(
+            checkbox(
+                fl!("apply-to-similar", count = similar),
+                portal.access_apply_to_similar,
+            )
+            .on_toggle(Msg::ApplyToSimilar)
+            .into(),
+        );
+    }
+    if let Some(deadline) = args.deadline {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let secs = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+        let note = match portal.config.access.unattended.timeout_answer {
+            Answer::Deny => fl!("unattended-deny", seconds = secs),
+            Answer::Allow => fl!("unattended-allow", seconds = secs),
+        };
+        stacked_choices.push(text(note).into());
+    }
+
+    let mut header = Vec::with_capacity(5);
+    header.push(text(args.title.as_str()).into());
+    if args.app_id.is_empty() && portal.config.access.unidentified_app == UnidentifiedApp::Caution {
+        header.push(unidentified_app_warning(spacing));
+    }
+    if !args.subtitle.is_empty() {
+        header.push(text(args.subtitle.as_str()).into());
+    }
+    if !args.body.is_empty() {
+        header.push(if args.body_from_app {
+            reason_view(args, portal.access_reason_expanded, spacing)
+        } else {
+            text(args.body.as_str()).into()
+        });
+    }
+    if !portal.config.access.hide_privacy_notes {
+        if let Some(note) = args.options.permission.as_deref().and_then(privacy_note) {
+            header.push(privacy_note_view(note, spacing));
+        }
+    }
+
+    let header = row![
+        icon::Icon::from(icon::from_name(args.options.dialog_icon()).size(layout.icon_size))
+            .width(Length::Fixed(layout.icon_size.into()))
+            .height(Length::Fixed(layout.icon_size.into())), // TODO icon for the dialog
+        Column::with_children(header).spacing(spacing.space_xxs as f32),
+    ]
+    .spacing(spacing.space_s as f32);
+    let stacked_choices = Column::with_children(stacked_choices).spacing(spacing.space_xxs as f32);
+    let content: cosmic::Element<_> = if layout.scroll_header {
+        container(scrollable(
+            column![header, stacked_choices].spacing(layout.spacing as f32),
+        ))
+        .max_height(layout.max_choices_height)
+        .into()
+    } else {
+        column![
+            header,
+            container(scrollable(stacked_choices)).max_height(layout.max_choices_height),
+        ]
+        .spacing(layout.spacing as f32)
+        .into()
+    };
+    let buttons: cosmic::Element<_> = if layout.stack_buttons {
+        Column::with_children(buttons)
+            .spacing(spacing.space_xxs as f32)
+            .into()
+    } else {
+        Row::with_children(buttons)
+            .spacing(spacing.space_xxs as f32) // space_l
+            .align_items(Alignment::Center)
+            .into()
+    };
+
+    let (footer, footer_spacing) = footer_view(choices, buttons, layout.spacing);
+    let mut dialog = container(
+        column![
+            content,
+            Column::with_children(footer).spacing(footer_spacing as f32)
+        ]
+        .spacing(layout.spacing as f32), // space_l
+    )
+    .max_width(layout.max_width)
+    .max_height(layout.max_height);
+    if let Some(width) = layout.width(args.options.width) {
+        dialog = dialog.width(Length::Fixed(width));
+    }
+    KeyboardWrapper::new(dialog, move |key, modifiers| {
+        key_msg(
+            &portal.config.shortcuts.bindings,
+            mnemonics,
+            style,
+            key,
+            modifiers,
+        )
+    })
+    .into()
+}
+
+// Widgets keep their state, like whether a dropdown is open, by their position in the tree,
+// so what appears and disappears is kept in slots of its own. Otherwise choices after it
+// would take another's state, and an open dropdown would snap shut.
+
+// What's stacked under the header before any notes: the details, in one slot whether
+// they're shown or not, then `choices` unless they're inline
+fn stacked_view<'a>(
+    details: Option<&'a str>,
+    details_shown: bool,
+    choices: Option<&'a [Choice]>,
+    spacing: cosmic::cosmic_theme::Spacing,
+) -> Vec<cosmic::Element<'a, Msg>> {
+    let choices = choices.unwrap_or_default();
+    let mut stacked = Vec::with_capacity(choices.len() + 3);
+    if let Some(details) = details {
+        let label = if details_shown {
+            fl!("hide-details")
+        } else {
+            fl!("show-details")
+        };
+        let mut details_view = vec![button::text(label).on_press(Msg::ToggleDetails).into()];
+        if details_shown {
+            details_view.push(text(details).into());
+        }
+        stacked.push(
+            Column::with_children(details_view)
+                .spacing(spacing.space_xxs as f32)
+                .into(),
+        );
+    }
+    stacked.extend(choices.iter().enumerate().map(|(i, choice)| choice.view(i)));
+    stacked
+}
+
+// The summary of `choices` above `buttons`, with the spacing between. The summary is an
+// empty slot without spacing when there's none.
+fn footer_view<'a>(
+    choices: &[Choice],
+    buttons: cosmic::Element<'a, Msg>,
+    spacing: u16,
+) -> (Vec<cosmic::Element<'a, Msg>>, u16) {
+    match choices_summary(choices) {
+        Some(summary) => (vec![summary, buttons], spacing),
+        None => (vec![horizontal_space(Length::Fixed(0.)).into(), buttons], 0),
+    }
+}
+
+// One line restating what the choices grant, so their combination is read before it's
+// allowed. `None` without choices, or if they grant nothing, so simple dialogs don't grow.
+fn choices_summary<'a>(choices: &[Choice]) -> Option<cosmic::Element<'a, Msg>> {
+    let items: Vec<_> = choices.iter().filter_map(Choice::summary).collect();
+    if items.is_empty() {
+        return None;
+    }
+    let summary = fl!("choices-summary", choices = crate::localize::list(items));
+    if summary.chars().count() <= MAX_SUMMARY_CHARS {
+        return Some(text(summary).size(12).into());
+    }
+    let mut short: String = summary.chars().take(MAX_SUMMARY_CHARS).collect();
+    short.push('…');
+    Some(
+        cosmic::widget::tooltip(
+            text(short).size(12),
+            text(summary),
+            cosmic::widget::tooltip::Position::Bottom,
         )
-        .on_press(Msg::Allow)
-        .style(cosmic::theme::Button::Suggested)
         .into(),
-    );
+    )
+}
+
+// Flag prompts from callers that didn't say which app they are, as anything could be asking
+fn unidentified_app_warning<'a>(
+    spacing: cosmic::cosmic_theme::Spacing,
+) -> cosmic::Element<'a, Msg> {
+    let warning =
+        text(fl!("unidentified-app")).style(cosmic::theme::style::Text::Custom(|theme| {
+            cosmic::iced_core::widget::text::Appearance {
+                color: Some(theme.cosmic().warning.base.into()),
+            }
+        }));
+    row![
+        icon::Icon::from(icon::from_name("dialog-warning-symbolic").size(16)),
+        warning,
+    ]
+    .spacing(spacing.space_xxs as f32)
+    .align_items(Alignment::Center)
+    .into()
+}
+
+/// What an app granted `permission` can do, for kinds of access where it isn't obvious from
+/// the dialog alone. Written by us, so callers can't play it down.
+fn privacy_note(permission: &str) -> Option<String> {
+    Some(match permission {
+        "camera" => fl!("privacy-note", "camera"),
+        "microphone" => fl!("privacy-note", "microphone"),
+        "screen" | "screencast" => fl!("privacy-note", "screen"),
+        "location" => fl!("privacy-note", "location"),
+        _ => return None,
+    })
+}
+
+fn privacy_note_view<'a>(
+    note: String,
+    spacing: cosmic::cosmic_theme::Spacing,
+) -> cosmic::Element<'a, Msg> {
+    row![
+        icon::Icon::from(icon::from_name("security-medium-symbolic").size(16)),
+        text(note).size(12),
+    ]
+    .spacing(spacing.space_xxs as f32)
+    .align_items(Alignment::Center)
+    .into()
+}
+
+/// Alt+letter accelerators of the dialog's buttons
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Mnemonics {
+    pub deny: Option<char>,
+    pub grant: Option<char>,
+}
+
+/// Labels of the deny and grant buttons, showing their accelerators
+fn button_labels(args: &AccessDialogArgs) -> ([String; 2], Mnemonics) {
+    let (deny, deny_mnemonic) = match args.options.deny_label.as_deref() {
+        Some(label) => (label.to_string(), None),
+        None => (fl!("cancel"), fl!("cancel", "mnemonic").chars().next()),
+    };
+    let (grant, grant_mnemonic) = match args.options.grant_label.as_deref() {
+        Some(label) => (label.to_string(), None),
+        None => (fl!("allow"), fl!("allow", "mnemonic").chars().next()),
+    };
+    // The grant button picks first, so its letter is the one users learn
+    let mut labels = shortcuts::mnemonics(&[(&grant, grant_mnemonic), (&deny, deny_mnemonic)]);
+    let (deny_label, deny) = labels.pop().unwrap();
+    let (grant_label, grant) = labels.pop().unwrap();
+    let shown = |label: String, letter: Option<char>| match letter {
+        Some(letter) => fl!(
+            "button-mnemonic",
+            label = label,
+            key = letter.to_uppercase().to_string()
+        ),
+        None => label,
+    };
+    (
+        [shown(deny_label, deny), shown(grant_label, grant)],
+        Mnemonics { deny, grant },
+    )
+}
 
+/// Message for a key pressed in the dialog
+///
+/// Bare Enter is left to the focused widget, so a stray Enter can't grant access. In
+/// destructive dialogs it denies instead, and Ctrl+Enter doesn't grant.
+pub(crate) fn key_msg(
+    bindings: &[config::shortcuts::Shortcut],
+    mnemonics: Mnemonics,
+    style: PromptStyle,
+    key: &keyboard::Key,
+    modifiers: keyboard::Modifiers,
+) -> Option<Msg> {
+    let quick = || match style {
+        PromptStyle::Standard => shortcuts::quick_grant(key, modifiers),
+        PromptStyle::Destructive => shortcuts::quick_deny(key, modifiers),
+    };
+    let action = shortcuts::action(bindings, false, false, key, modifiers).or_else(quick);
+    match action {
+        Some(Action::Accept) => return Some(Msg::Allow),
+        Some(Action::Cancel) => return Some(Msg::Cancel),
+        None => {}
+    }
+    let matches = |letter: Option<char>| {
+        letter.is_some_and(|letter| shortcuts::mnemonic_matches(letter, key, modifiers))
+    };
+    if matches(mnemonics.grant) {
+        Some(Msg::Allow)
+    } else if matches(mnemonics.deny) {
+        Some(Msg::Cancel)
+    } else {
+        None
+    }
+}
+
+/// The requesting app's reason, quoted beside its icon so it can't be mistaken for ours.
+/// Long reasons are cut short until expanded.
+fn reason_view<'a>(
+    args: &'a AccessDialogArgs,
+    expanded: bool,
+    spacing: cosmic::cosmic_theme::Spacing,
+) -> cosmic::Element<'a, Msg> {
+    let long = args.body.chars().count() > MAX_REASON_CHARS;
+    let reason = if long && !expanded {
+        let mut reason: String = args.body.chars().take(MAX_REASON_CHARS).collect();
+        reason.push('…');
+        text(reason)
+    } else {
+        text(args.body.as_str())
+    };
+    let mut column = Vec::with_capacity(2);
+    column.push(reason.into());
+    if long {
+        let label = if expanded {
+            fl!("show-less")
+        } else {
+            fl!("show-more")
+        };
+        column.push(button::text(label).on_press(Msg::ToggleReason).into());
+    }
+    let app_icon = if args.app_id.is_empty() {
+        "application-x-executable"
+    } else {
+        args.app_id.as_str()
+    };
     container(
-        column![
-            row![
-                icon::Icon::from(
-                    icon::from_name(
-                        args.options
-                            .icon
-                            .as_ref()
-                            .map_or("image-missing", |name| name.as_str())
-                    )
-                    .size(64)
-                )
-                .width(Length::Fixed(64.0))
-                .height(Length::Fixed(64.0)), // TODO icon for the dialog
-                text(args.title.as_str()),
-                text(args.subtitle.as_str()),
-                text(args.body.as_str()),
-            ],
-            Row::with_children(options)
-                .spacing(spacing.space_xxs as f32) // space_l
-                .align_items(Alignment::Center),
+        row![
+            icon::Icon::from(icon::from_name(app_icon).size(16)),
+            Column::with_children(column).spacing(spacing.space_xxs as f32),
         ]
-        .spacing(spacing.space_l as f32), // space_l
+        .spacing(spacing.space_xs as f32),
     )
+    .padding(spacing.space_xs)
+    .width(Length::Fill)
+    .style(cosmic::theme::Container::Card)
     .into()
 }
 
+/// Sizes for the dialog, scaled down on small outputs so the buttons stay on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Layout {
+    icon_size: u16,
+    spacing: u16,
+    /// Buttons are stacked rather than in a row, on narrow outputs
+    stack_buttons: bool,
+    /// The header scrolls along with the choices, on short outputs
+    scroll_header: bool,
+    /// Choices scroll beyond this height
+    max_choices_height: f32,
+    max_width: f32,
+    max_height: f32,
+}
+
+impl Layout {
+    // Outputs shorter than this get the smaller icon and spacing
+    const COMPACT_OUTPUT_HEIGHT: u32 = 800;
+    // Outputs narrower than this get stacked buttons
+    const COMPACT_OUTPUT_WIDTH: u32 = 640;
+    // Fraction of the output's size the dialog may cover
+    const MAX_HEIGHT_FRACTION: f32 = 0.8;
+    const MAX_WIDTH_FRACTION: f32 = 0.9;
+    // Height of a row of buttons
+    const BUTTON_HEIGHT: f32 = 32.;
+    // Narrowest width an app may ask for, which still fits the buttons in a row
+    const MIN_WIDTH: f32 = 360.;
+
+    fn new(output_size: Option<(u32, u32)>, spacing: cosmic::cosmic_theme::Spacing) -> Self {
+        let Some((output_width, output_height)) = output_size else {
+            return Self {
+                icon_size: 64,
+                spacing: spacing.space_l,
+                stack_buttons: false,
+                scroll_header: false,
+                max_choices_height: f32::INFINITY,
+                max_width: f32::INFINITY,
+                max_height: f32::INFINITY,
+            };
+        };
+        let scroll_header = output_height < Self::COMPACT_OUTPUT_HEIGHT;
+        let (icon_size, spacing) = if scroll_header {
+            (32, spacing.space_s)
+        } else {
+            (64, spacing.space_l)
+        };
+        let stack_buttons = output_width < Self::COMPACT_OUTPUT_WIDTH;
+        let max_height = output_height as f32 * Self::MAX_HEIGHT_FRACTION;
+        // Buttons always keep their space, so what's above them gives way
+        let buttons_height = if stack_buttons {
+            2. * Self::BUTTON_HEIGHT + f32::from(spacing)
+        } else {
+            Self::BUTTON_HEIGHT
+        };
+        let reserved = if scroll_header {
+            buttons_height + f32::from(spacing)
+        } else {
+            f32::from(icon_size) + buttons_height + 2. * f32::from(spacing)
+        };
+        Self {
+            icon_size,
+            spacing,
+            stack_buttons,
+            scroll_header,
+            max_choices_height: (max_height - reserved).max(0.),
+            max_width: output_width as f32 * Self::MAX_WIDTH_FRACTION,
+            max_height,
+        }
+    }
+
+    /// Width of the dialog for the `preferred` one, or `None` to fit its content
+    fn width(&self, preferred: Option<u32>) -> Option<f32> {
+        let max = self.max_width.max(Self::MIN_WIDTH);
+        preferred.map(|x| (x as f32).clamp(Self::MIN_WIDTH, max))
+    }
+}
+
+// Logical size of the output the dialog is most likely shown on
+fn dialog_output_size(portal: &CosmicPortal) -> Option<(u32, u32)> {
+    portal
+        .outputs
+        .iter()
+        .find(|o| Some(&o.output) == portal.active_output.as_ref())
+        .or_else(|| portal.outputs.iter().find(|o| o.has_pointer))
+        .or_else(|| portal.outputs.iter().min_by_key(|o| o.logical_size.1))
+        .map(|o| o.logical_size)
+}
+
+// Let the subscription know, so it can signal when prompts are on screen
+fn send_event(portal: &CosmicPortal, event: subscription::Event) {
+    if let Some(tx) = portal.tx.clone() {
+        tokio::spawn(async move {
+            _ = tx.send(event).await;
+        });
+    }
+}
+
 pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate::app::Msg> {
     match msg {
         Msg::Allow => {
-            let args = portal.access_args.take().unwrap();
-            let tx = args.tx.clone();
-            tokio::spawn(async move {
-                tx.send(PortalResponse::Success(AccessDialogResult {
-                    choices: vec![],
-                }))
-                .await
-            });
-
-            args.destroy_surface()
+            // Already answered, if another button or `Request.Close` got there first
+            let Some(args) = portal.access_args.take() else {
+                log::debug!("Access dialog already answered, ignoring Allow");
+                return cosmic::iced::Command::none();
+            };
+            let cmd = args.destroy_surface();
+            let choices = portal.access_choices.iter().map(Choice::result).collect();
+            let result = AccessDialogResult::new(choices, args.options.choice_types.as_ref());
+            let apply_to_similar = portal.access_apply_to_similar;
+            let next = answer(
+                portal,
+                args,
+                PortalResponse::Success(result),
+                apply_to_similar,
+            );
+            cosmic::iced::Command::batch([cmd, next])
         }
         Msg::Cancel => {
-            let args = portal.access_args.take().unwrap();
-            let tx = args.tx.clone();
-            tokio::spawn(async move {
-                tx.send(PortalResponse::Cancelled::<AccessDialogResult>)
-                    .await
-            });
-
-            args.destroy_surface()
+            let Some(args) = portal.access_args.take() else {
+                log::debug!("Access dialog already answered, ignoring Cancel");
+                return cosmic::iced::Command::none();
+            };
+            let cmd = args.destroy_surface();
+            let apply_to_similar = portal.access_apply_to_similar;
+            let next = answer(
+                portal,
+                args,
+                PortalResponse::Cancelled::<AccessDialogResult>,
+                apply_to_similar,
+            );
+            cosmic::iced::Command::batch([cmd, next])
         }
         Msg::Choice(i, j) => {
-            let args = portal.access_args.as_mut().unwrap();
-            portal.access_choices[i].0 = Some(j);
+            // Unavailable options are still in the dropdown, for the keyboard as well
+            if portal
+                .access_choices
+                .get(i)
+                .is_some_and(|x| x.is_unavailable(j))
+            {
+                log::debug!("Option {j} of access dialog choice {i} is unavailable");
+            } else if let Some(Choice::Options { selected, .. }) = portal.access_choices.get_mut(i)
+            {
+                *selected = j;
+            }
+            cosmic::iced::Command::none()
+        }
+        Msg::Toggle(i, value) => {
+            if let Some(Choice::Boolean {
+                checked,
+                unavailable: None,
+                ..
+            }) = portal.access_choices.get_mut(i)
+            {
+                *checked = value;
+            }
+            cosmic::iced::Command::none()
+        }
+        Msg::ToggleDetails => {
+            portal.access_details_shown = !portal.access_details_shown;
+            cosmic::iced::Command::none()
+        }
+        Msg::ToggleReason => {
+            portal.access_reason_expanded = !portal.access_reason_expanded;
+            cosmic::iced::Command::none()
+        }
+        Msg::ApplyToSimilar(value) => {
+            portal.access_apply_to_similar = value;
             cosmic::iced::Command::none()
         }
+        Msg::GraceElapsed(serial) => {
+            let pending = portal.access_args.as_ref().is_some_and(|args| !args.shown);
+            if serial != portal.access_serial || !pending {
+                return cosmic::iced::Command::none();
+            }
+            show_surface(portal)
+        }
+        Msg::TimedOut(serial) => {
+            if serial != portal.access_serial || portal.access_args.is_none() {
+                return cosmic::iced::Command::none();
+            }
+            let args = portal.access_args.take().unwrap();
+            let timeout_answer = portal.config.access.unattended.timeout_answer;
+            log::warn!(
+                "Access dialog {} for {:?} timed out, answering {timeout_answer:?}",
+                args.handle,
+                args.app_id
+            );
+            let cmd = args.destroy_surface();
+            let response = automatic_response(&args, &portal.access_choices, timeout_answer);
+            let next = answer(portal, args, response, false);
+            cosmic::iced::Command::batch([cmd, next])
+        }
+        Msg::Tick => cosmic::iced::Command::none(),
     }
     .map(crate::app::Msg::Access)
 }
@@ -246,20 +1688,748 @@ pub fn update_args(
     portal: &mut CosmicPortal,
     msg: AccessDialogArgs,
 ) -> cosmic::Command<crate::app::Msg> {
-    let mut cmds = Vec::with_capacity(2);
-    if let Some(args) = portal.access_args.take() {
-        // destroy surface and recreate
-        cmds.push(args.destroy_surface());
-        // send cancelled response
-        tokio::spawn(async move {
-            let _ = args
-                .tx
-                .send(PortalResponse::Cancelled::<AccessDialogResult>)
-                .await;
-        });
+    if msg.app_id.is_empty() && portal.config.access.unidentified_app == UnidentifiedApp::Reject {
+        log::warn!("Rejected access request {} without an app ID", msg.handle);
+        msg.send_response(PortalResponse::Error(
+            "requests without an app ID are rejected".to_string(),
+        ));
+        return cosmic::iced::Command::none();
+    }
+    let unattended = &portal.config.access.unattended;
+    if let Some((permission, answer)) = msg
+        .options
+        .permission
+        .as_ref()
+        .and_then(|x| unattended.permissions.get_key_value(x))
+    {
+        log::info!(
+            "Access request {} for {:?} answered {answer:?}, as configured for {permission}",
+            msg.handle,
+            msg.app_id
+        );
+        let choices = msg.choices(&portal.config.access);
+        let response = automatic_response(&msg, &choices, *answer);
+        let notice = unattended
+            .notify_answered
+            .then(|| answered_notice(msg.app_id.clone(), msg.title.clone(), *answer));
+        msg.send_response(response);
+        return notice.unwrap_or_else(cosmic::iced::Command::none);
     }
+    if let Some(args) = portal.access_args.as_mut() {
+        // Answer identical requests with the same dialog
+        let duplicate = std::iter::once(args)
+            .chain(portal.access_queue.iter_mut())
+            .find(|args| args.is_duplicate(&msg));
+        if let Some(args) = duplicate {
+            args.attached.push((msg.handle, msg.tx));
+        } else {
+            // Shown once the dialogs before it are answered
+            portal.access_queue.push_back(msg);
+        }
+        return cosmic::iced::Command::none();
+    }
+    show(portal, msg).map(crate::app::Msg::Access)
+}
 
-    cmds.push(msg.get_surface());
+fn show(portal: &mut CosmicPortal, mut msg: AccessDialogArgs) -> cosmic::Command<Msg> {
+    msg.modal = msg.use_modal(&portal.config.access, &portal.wayland_helper);
+    msg.instant = portal.config.instant_dialogs;
+    msg.outputs = msg.mirror_outputs(
+        &portal.config.access,
+        &portal.outputs,
+        portal.active_output.as_ref(),
+    );
+    portal.access_choices = msg.choices(&portal.config.access);
+    portal.access_details_shown = false;
+    portal.access_reason_expanded = false;
+    portal.access_apply_to_similar = false;
+    let timeout_secs = portal.config.access.unattended.timeout_secs;
+    msg.deadline = timeout_secs.map(|secs| Instant::now() + Duration::from_secs(secs.into()));
     portal.access_args = Some(msg);
-    cosmic::iced::Command::batch(cmds).map(crate::app::Msg::Access)
+    portal.access_serial += 1;
+    let serial = portal.access_serial;
+    // A request cancelled within the grace period, as is common while apps start, never
+    // flashes a dialog
+    let grace_period_ms = if portal.config.instant_dialogs {
+        0
+    } else {
+        portal.config.access.grace_period_ms
+    };
+    let cmd = match grace_period_ms {
+        0 => show_surface(portal),
+        ms => cosmic::iced::Command::perform(
+            tokio::time::sleep(Duration::from_millis(ms.into())),
+            move |_| Msg::GraceElapsed(serial),
+        ),
+    };
+    match timeout_secs {
+        Some(secs) => {
+            let timeout = cosmic::iced::Command::perform(
+                tokio::time::sleep(Duration::from_secs(secs.into())),
+                move |_| Msg::TimedOut(serial),
+            );
+            cosmic::iced::Command::batch([cmd, timeout])
+        }
+        None => cmd,
+    }
+}
+
+// Create the surface of the dialog in `access_args`
+fn show_surface(portal: &mut CosmicPortal) -> cosmic::Command<Msg> {
+    let Some(args) = portal.access_args.as_mut() else {
+        return cosmic::iced::Command::none();
+    };
+    args.shown = true;
+    let cmd = args.get_surface();
+    let event = subscription::Event::PromptShown(args.handle.clone(), args.app_id.clone());
+    send_event(portal, event);
+    cmd
+}
+
+// Toast telling that a request of `app_id` titled `title` was answered `answer` without a
+// dialog
+fn answered_notice(
+    app_id: String,
+    title: String,
+    answer: Answer,
+) -> cosmic::Command<crate::app::Msg> {
+    cosmic::iced::Command::perform(
+        async move {
+            let app_name = if app_id.is_empty() {
+                fl!("unknown-application")
+            } else {
+                crate::screencast_dialog::app_name(&app_id)
+                    .await
+                    .unwrap_or(app_id)
+            };
+            match answer {
+                Answer::Allow => (
+                    "emblem-ok-symbolic",
+                    fl!(
+                        "access-answered",
+                        "allowed",
+                        app_name = app_name,
+                        title = title
+                    ),
+                ),
+                Answer::Deny => (
+                    "action-unavailable-symbolic",
+                    fl!(
+                        "access-answered",
+                        "denied",
+                        app_name = app_name,
+                        title = title
+                    ),
+                ),
+            }
+        },
+        |(icon, text)| {
+            crate::app::Msg::Toast(crate::toast::Msg::Show {
+                icon: icon.to_string(),
+                text,
+            })
+        },
+    )
+}
+
+// Response the user would have given by answering `answer` with `choices` as selected
+fn automatic_response(
+    args: &AccessDialogArgs,
+    choices: &[Choice],
+    answer: Answer,
+) -> PortalResponse<AccessDialogResult> {
+    match answer {
+        Answer::Deny => PortalResponse::Cancelled,
+        Answer::Allow => PortalResponse::Success(AccessDialogResult::new(
+            choices.iter().map(Choice::result).collect(),
+            args.options.choice_types.as_ref(),
+        )),
+    }
+}
+
+// IDs of the choices in `options`, each with the IDs of its options
+fn choice_ids(options: &AccessDialogOptions) -> Vec<(&str, Vec<&str>)> {
+    options
+        .choices
+        .iter()
+        .flatten()
+        .map(|(id, _, options, _)| {
+            (
+                id.as_str(),
+                options.iter().map(|(x, _)| x.as_str()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Send `response` for the dialog that was on screen, and to similar queued requests if
+/// `apply_to_similar`, then show the next queued request
+fn answer(
+    portal: &mut CosmicPortal,
+    args: AccessDialogArgs,
+    response: PortalResponse<AccessDialogResult>,
+    apply_to_similar: bool,
+) -> cosmic::Command<Msg> {
+    if args.shown {
+        send_event(
+            portal,
+            subscription::Event::PromptHidden(args.handle.clone()),
+        );
+    }
+    if apply_to_similar {
+        let (similar, queue) = mem::take(&mut portal.access_queue)
+            .into_iter()
+            .partition::<Vec<_>, _>(|queued| args.is_similar(queued));
+        portal.access_queue = queue.into();
+        for queued in similar {
+            queued.send_response(response.clone());
+        }
+    }
+    args.send_response(response);
+    match portal.access_queue.pop_front() {
+        Some(next) => show(portal, next),
+        None => cosmic::iced::Command::none(),
+    }
+}
+
+// Queued requests the user may answer together with the one on screen
+fn similar_queued(portal: &CosmicPortal) -> usize {
+    let Some(args) = portal.access_args.as_ref() else {
+        return 0;
+    };
+    portal
+        .access_queue
+        .iter()
+        .filter(|queued| args.is_similar(queued))
+        .count()
+}
+
+/// Whether `id` is one of the dialog's surfaces
+pub fn is_surface(portal: &CosmicPortal, id: window::Id) -> bool {
+    id == *ACCESS_ID
+        || portal
+            .access_args
+            .as_ref()
+            .is_some_and(|args| args.outputs.iter().any(|(x, _)| *x == id))
+}
+
+/// Log how long the dialog took to take keyboard focus after it was requested, the first
+/// time one of its surfaces is focused
+pub fn surface_focused(portal: &mut CosmicPortal, id: window::Id) {
+    if !is_surface(portal, id) {
+        return;
+    }
+    let Some(args) = portal.access_args.as_mut() else {
+        return;
+    };
+    if let Some(requested) = args.requested.take() {
+        log::info!(
+            "Access dialog for {:?} focused {} ms after the request{}",
+            args.app_id,
+            requested.elapsed().as_millis(),
+            if args.instant {
+                ", in instant mode"
+            } else {
+                ""
+            }
+        );
+    }
+}
+
+/// Handle the compositor closing one of the dialog's surfaces, like when its output was
+/// removed
+///
+/// A mirror closing leaves the others up. Otherwise the dialog moves to the outputs left,
+/// or the request is cancelled if there are none.
+pub fn surface_closed(
+    portal: &mut CosmicPortal,
+    id: window::Id,
+    reason: String,
+) -> cosmic::Command<crate::app::Msg> {
+    let Some(mut args) = portal.access_args.take() else {
+        return cosmic::iced::Command::none();
+    };
+    let surfaces: Vec<_> = args.outputs.iter().map(|(x, _)| *x).collect();
+    match Closed::of(
+        &surfaces,
+        id,
+        args.modal,
+        !portal.outputs.is_empty(),
+        args.relocations,
+    ) {
+        Closed::Mirror => {
+            args.outputs.retain(|(x, _)| *x != id);
+            portal.access_args = Some(args);
+            cosmic::iced::Command::none()
+        }
+        Closed::Relocate => {
+            log::warn!(
+                "Access dialog for {:?}: {reason}, showing it again",
+                args.app_id
+            );
+            args.relocations += 1;
+            args.outputs = args.mirror_outputs(
+                &portal.config.access,
+                &portal.outputs,
+                portal.active_output.as_ref(),
+            );
+            let cmd = args.get_surface();
+            portal.access_args = Some(args);
+            cmd.map(crate::app::Msg::Access)
+        }
+        Closed::Cancel => {
+            log::error!("Access dialog for {:?}: {reason}", args.app_id);
+            answer(portal, args, PortalResponse::Cancelled, false).map(crate::app::Msg::Access)
+        }
+    }
+}
+
+/// What to do when the compositor closes one of the dialog's surfaces
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Closed {
+    /// Leave the other mirrors up
+    Mirror,
+    /// Show the dialog again on the outputs left
+    Relocate,
+    /// Cancel the request
+    Cancel,
+}
+
+impl Closed {
+    /// For surface `id` of a dialog with `surfaces`, shown again `relocations` times, when
+    /// `have_outputs` tells if any outputs are left
+    fn of(
+        surfaces: &[window::Id],
+        id: window::Id,
+        modal: bool,
+        have_outputs: bool,
+        relocations: u32,
+    ) -> Self {
+        if surfaces.len() > 1 && surfaces.contains(&id) {
+            Self::Mirror
+        } else if !modal && have_outputs && relocations < MAX_RELOCATIONS {
+            Self::Relocate
+        } else {
+            Self::Cancel
+        }
+    }
+}
+
+/// Cancel the dialog on screen, and show the next queued request
+pub fn cancel_shown(portal: &mut CosmicPortal) -> cosmic::Command<crate::app::Msg> {
+    if !portal.access_args.as_ref().is_some_and(|args| args.shown) {
+        return cosmic::iced::Command::none();
+    }
+    let args = portal.access_args.take().unwrap();
+    log::info!(
+        "Access dialog {} for {:?} cancelled by shortcut",
+        args.handle,
+        args.app_id
+    );
+    let cmd = args.destroy_surface();
+    let next = answer(portal, args, PortalResponse::Cancelled, false);
+    cosmic::iced::Command::batch([cmd, next]).map(crate::app::Msg::Access)
+}
+
+/// Handle `Request.Close` for `handle`. Closing the request a dialog was made for closes
+/// the dialog, cancelling the identical requests attached to it too, while closing an
+/// attached request only stops the dialog answering it.
+///
+/// A request still queued is withdrawn from the queue, leaving the dialog on screen alone.
+pub fn cancel(
+    portal: &mut CosmicPortal,
+    handle: zvariant::ObjectPath<'static>,
+) -> cosmic::Command<crate::app::Msg> {
+    if portal
+        .access_args
+        .as_ref()
+        .is_some_and(|args| args.handle == handle)
+    {
+        let args = portal.access_args.take().unwrap();
+        let cmd = args.destroy_surface();
+        let next = answer(portal, args, PortalResponse::Cancelled, false);
+        return cosmic::iced::Command::batch([cmd, next]).map(crate::app::Msg::Access);
+    }
+    if withdraw(&mut portal.access_queue, &handle) {
+        return cosmic::iced::Command::none();
+    }
+    let requests = portal
+        .access_args
+        .iter_mut()
+        .chain(portal.access_queue.iter_mut());
+    for args in requests {
+        if let Some(tx) = args.detach(&handle) {
+            tx.send(PortalResponse::Cancelled);
+            break;
+        }
+    }
+    cosmic::iced::Command::none()
+}
+
+// Withdraw the request for `handle` from `queue` as cancelled, if it's queued
+fn withdraw(
+    queue: &mut VecDeque<AccessDialogArgs>,
+    handle: &zvariant::ObjectPath<'static>,
+) -> bool {
+    let Some(i) = queue.iter().position(|queued| queued.handle == *handle) else {
+        return false;
+    };
+    let queued = queue.remove(i).unwrap();
+    log::info!(
+        "Queued access dialog {} for {:?} withdrawn",
+        queued.handle,
+        queued.app_id
+    );
+    queued.send_response(PortalResponse::Cancelled);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface_is_exported_as_access_1() {
+        use zbus::object_server::Interface;
+        assert_eq!(
+            Access::name().as_str(),
+            "org.freedesktop.impl.portal.Access"
+        );
+        assert_eq!(VERSION, 1);
+    }
+
+    #[test]
+    fn result_round_trips() {
+        let choice_types = HashMap::from([
+            ("notify".to_string(), "boolean".to_string()),
+            ("count".to_string(), "integer".to_string()),
+            ("ratio".to_string(), "double".to_string()),
+        ]);
+        let choices = [
+            ("notify", "true"),
+            ("count", "3"),
+            ("ratio", "0.5"),
+            ("mode", "once"),
+        ]
+        .map(|(id, value)| (id.to_string(), value.to_string()))
+        .to_vec();
+        crate::assert_round_trips(AccessDialogResult::new(choices, Some(&choice_types)));
+        crate::assert_round_trips(AccessDialogResult::new(Vec::new(), None));
+    }
+
+    fn shortcut(keys: &str, action: Action) -> config::shortcuts::Shortcut {
+        config::shortcuts::Shortcut {
+            keys: keys.to_string(),
+            action,
+        }
+    }
+
+    #[test]
+    fn keys_map_to_messages() {
+        use keyboard::{key::Named, Key, Modifiers};
+        let key_msg =
+            |style, key: Key, modifiers| key_msg(&[], Mnemonics::default(), style, &key, modifiers);
+        let enter = || Key::Named(Named::Enter);
+        let escape = || Key::Named(Named::Escape);
+
+        let standard = PromptStyle::Standard;
+        assert_eq!(
+            key_msg(standard, enter(), Modifiers::CTRL),
+            Some(Msg::Allow)
+        );
+        assert_eq!(
+            key_msg(standard, escape(), Modifiers::empty()),
+            Some(Msg::Cancel)
+        );
+        // Left to the focused widget
+        assert_eq!(key_msg(standard, enter(), Modifiers::empty()), None);
+        assert_eq!(
+            key_msg(standard, Key::Character("a".into()), Modifiers::empty()),
+            None
+        );
+
+        let destructive = PromptStyle::Destructive;
+        assert_eq!(
+            key_msg(destructive, enter(), Modifiers::empty()),
+            Some(Msg::Cancel)
+        );
+        assert_eq!(
+            key_msg(destructive, escape(), Modifiers::empty()),
+            Some(Msg::Cancel)
+        );
+        assert_eq!(key_msg(destructive, enter(), Modifiers::CTRL), None);
+    }
+
+    #[test]
+    fn configured_shortcuts_come_first() {
+        use keyboard::{key::Named, Key, Modifiers};
+        let bindings = [
+            shortcut("D", Action::Cancel),
+            shortcut("Ctrl+Enter", Action::Cancel),
+        ];
+        let key_msg = |key: Key, modifiers| {
+            key_msg(
+                &bindings,
+                Mnemonics::default(),
+                PromptStyle::Standard,
+                &key,
+                modifiers,
+            )
+        };
+        assert_eq!(
+            key_msg(Key::Character("d".into()), Modifiers::empty()),
+            Some(Msg::Cancel)
+        );
+        assert_eq!(key_msg(Key::Character("D".into()), Modifiers::SHIFT), None);
+        assert_eq!(
+            key_msg(Key::Named(Named::Enter), Modifiers::CTRL),
+            Some(Msg::Cancel)
+        );
+    }
+
+    #[test]
+    fn layout_keeps_buttons_on_small_outputs() {
+        let spacing = cosmic::cosmic_theme::Spacing::default();
+        let layout = Layout::new(None, spacing);
+        assert!(!layout.stack_buttons && !layout.scroll_header);
+        assert_eq!(layout.max_height, f32::INFINITY);
+
+        let layout = Layout::new(Some((1920, 1080)), spacing);
+        assert!(!layout.stack_buttons && !layout.scroll_header);
+        assert_eq!(layout.icon_size, 64);
+
+        let layout = Layout::new(Some((1024, 600)), spacing);
+        assert!(!layout.stack_buttons && layout.scroll_header);
+        assert_eq!(layout.icon_size, 32);
+
+        for size in [(320, 240), (480, 320), (240, 120)] {
+            let layout = Layout::new(Some(size), spacing);
+            assert!(layout.stack_buttons && layout.scroll_header, "{size:?}");
+            // Whatever the choices get, the buttons keep their space
+            let buttons = 2. * Layout::BUTTON_HEIGHT + f32::from(layout.spacing);
+            assert!(
+                layout.max_choices_height <= layout.max_height - buttons,
+                "{size:?}"
+            );
+            assert!(layout.max_width <= size.0 as f32, "{size:?}");
+        }
+    }
+
+    #[test]
+    fn layout_width_is_clamped() {
+        let spacing = cosmic::cosmic_theme::Spacing::default();
+        let layout = Layout::new(Some((1920, 1080)), spacing);
+        assert_eq!(layout.width(None), None);
+        assert_eq!(layout.width(Some(500)), Some(500.));
+        assert_eq!(layout.width(Some(100)), Some(Layout::MIN_WIDTH));
+        assert_eq!(layout.width(Some(5000)), Some(layout.max_width));
+        // Narrow outputs still fit the buttons in a row
+        let layout = Layout::new(Some((320, 240)), spacing);
+        assert_eq!(layout.width(Some(1000)), Some(Layout::MIN_WIDTH));
+    }
+
+    #[test]
+    fn icons_are_sanitized() {
+        assert_eq!(
+            sanitize_icon("camera").as_deref(),
+            Some("camera-web-symbolic")
+        );
+        for name in ["org.gnome.Maps", "audio-input_microphone-symbolic"] {
+            assert_eq!(sanitize_icon(name).as_deref(), Some(name));
+        }
+        for name in [
+            "",
+            "/usr/share/icons/hicolor/48x48/apps/firefox.png",
+            "../../../etc/passwd",
+            ".hidden",
+            "firefox\0",
+            "fire fox",
+            "fire\nfox",
+            "firefox\u{202e}",
+            "ícono",
+        ] {
+            assert_eq!(sanitize_icon(name), None, "{name:?}");
+        }
+        assert!(sanitize_icon(&"a".repeat(MAX_ICON_NAME_LEN)).is_some());
+        assert!(sanitize_icon(&"a".repeat(MAX_ICON_NAME_LEN + 1)).is_none());
+    }
+
+    #[test]
+    fn placements_parse_to_anchors() {
+        let anchor = |placement: Option<&str>| {
+            AccessDialogOptions {
+                placement: placement.map(str::to_string),
+                ..Default::default()
+            }
+            .placement_anchor()
+        };
+        assert_eq!(anchor(None), None);
+        assert_eq!(anchor(Some("top")), Some(Anchor::TOP));
+        assert_eq!(
+            anchor(Some("bottom-right")),
+            Some(Anchor::BOTTOM | Anchor::RIGHT)
+        );
+        assert_eq!(anchor(Some("middle")), None);
+        assert_eq!(anchor(Some("top-middle")), None);
+    }
+
+    // A request for `handle` and the receiver its response arrives on
+    fn boolean(checked: bool) -> Choice {
+        Choice::Boolean {
+            id: "remember".to_string(),
+            label: "Remember".to_string(),
+            checked,
+            unavailable: None,
+        }
+    }
+
+    #[test]
+    fn details_keep_the_choices_in_place() {
+        let spacing = cosmic::cosmic_theme::Spacing::default();
+        let choices = [boolean(false), boolean(true)];
+        let count = |details, shown, choices| stacked_view(details, shown, choices, spacing).len();
+        // One slot before the choices, whether the details are shown or not
+        assert_eq!(count(Some("details"), false, Some(&choices[..])), 3);
+        assert_eq!(count(Some("details"), true, Some(&choices[..])), 3);
+        assert_eq!(count(None, false, Some(&choices[..])), 2);
+        assert_eq!(count(Some("details"), true, None), 1);
+    }
+
+    #[test]
+    fn summary_keeps_its_slot() {
+        let buttons = || -> cosmic::Element<Msg> { horizontal_space(Length::Fill).into() };
+        let (footer, spacing) = footer_view(&[boolean(true)], buttons(), 24);
+        assert_eq!((footer.len(), spacing), (2, 24));
+        // Nothing granted, so no summary
+        let (footer, spacing) = footer_view(&[boolean(false)], buttons(), 24);
+        assert_eq!((footer.len(), spacing), (2, 0));
+        let (footer, spacing) = footer_view(&[], buttons(), 24);
+        assert_eq!((footer.len(), spacing), (2, 0));
+    }
+
+    #[test]
+    fn closed_surfaces_relocate_or_cancel() {
+        let (a, b) = (window::Id::unique(), window::Id::unique());
+        // Mirrors close alone
+        assert_eq!(Closed::of(&[a, b], b, false, true, 0), Closed::Mirror);
+        assert_eq!(
+            Closed::of(&[a, b], b, true, false, MAX_RELOCATIONS),
+            Closed::Mirror
+        );
+        // The last surface moves to the outputs left, if there are any
+        assert_eq!(Closed::of(&[a], a, false, true, 0), Closed::Relocate);
+        assert_eq!(Closed::of(&[a], a, false, false, 0), Closed::Cancel);
+        assert_eq!(Closed::of(&[a], a, true, true, 0), Closed::Cancel);
+        // Only so many times
+        let last = MAX_RELOCATIONS - 1;
+        assert_eq!(Closed::of(&[a], a, false, true, last), Closed::Relocate);
+        assert_eq!(
+            Closed::of(&[a], a, false, true, MAX_RELOCATIONS),
+            Closed::Cancel
+        );
+    }
+
+    fn request(
+        handle: &'static str,
+    ) -> (
+        AccessDialogArgs,
+        tokio::sync::mpsc::Receiver<PortalResponse<AccessDialogResult>>,
+    ) {
+        let (tx, rx) = Responder::channel();
+        let args = AccessDialogArgs {
+            handle: zvariant::ObjectPath::try_from(handle).unwrap(),
+            app_id: "org.example.App".to_string(),
+            parent_window: String::new(),
+            title: "Use the camera?".to_string(),
+            subtitle: String::new(),
+            body: String::new(),
+            body_from_app: false,
+            options: AccessDialogOptions::default(),
+            raw_options: Default::default(),
+            tx,
+            modal: false,
+            outputs: Vec::new(),
+            relocations: 0,
+            shown: false,
+            instant: false,
+            requested: None,
+            deadline: None,
+            attached: Vec::new(),
+        };
+        (args, rx)
+    }
+
+    #[tokio::test]
+    async fn withdrawn_requests_leave_the_queue() {
+        let (a, mut a_rx) = request("/request/a");
+        let (b, mut b_rx) = request("/request/b");
+        let (c, mut c_rx) = request("/request/c");
+        let b_handle = b.handle.clone();
+        let mut queue = VecDeque::from([a, b, c]);
+        assert!(withdraw(&mut queue, &b_handle));
+        assert_eq!(b_rx.recv().await, Some(PortalResponse::Cancelled));
+        assert!(!withdraw(&mut queue, &b_handle));
+
+        let handles: Vec<_> = queue.iter().map(|x| x.handle.as_str()).collect();
+        assert_eq!(handles, ["/request/a", "/request/c"]);
+        for args in queue {
+            args.send_response(PortalResponse::Other);
+        }
+        assert_eq!(a_rx.recv().await, Some(PortalResponse::Other));
+        assert_eq!(c_rx.recv().await, Some(PortalResponse::Other));
+    }
+
+    #[tokio::test]
+    async fn attached_requests_share_the_answer() {
+        let (mut args, mut rx) = request("/request/a");
+        let (attached, mut attached_rx) = request("/request/b");
+        assert!(args.is_duplicate(&attached));
+        args.attached.push((attached.handle, attached.tx));
+        args.send_response(PortalResponse::Cancelled);
+        assert_eq!(rx.recv().await, Some(PortalResponse::Cancelled));
+        assert_eq!(attached_rx.recv().await, Some(PortalResponse::Cancelled));
+    }
+
+    #[test]
+    fn only_attached_requests_detach() {
+        let (mut args, _rx) = request("/request/a");
+        let (attached, _attached_rx) = request("/request/b");
+        let (primary, handle) = (args.handle.clone(), attached.handle.clone());
+        args.attached.push((attached.handle, attached.tx));
+        assert!(args.detach(&primary).is_none());
+        assert!(args.detach(&handle).is_some());
+        assert!(args.attached.is_empty());
+        assert!(args.detach(&handle).is_none());
+    }
+
+    #[test]
+    fn other_text_is_not_a_duplicate() {
+        let (args, _rx) = request("/request/a");
+        let (mut other, _other_rx) = request("/request/b");
+        other.title = "Use the microphone?".to_string();
+        assert!(!args.is_duplicate(&other));
+        assert!(args.is_similar(&request("/request/c").0));
+    }
+
+    #[test]
+    fn other_choices_are_not_similar() {
+        let choice = |options: &[&str]| {
+            let options = options.iter().map(|x| (x.to_string(), x.to_string()));
+            Some(vec![(
+                "camera".to_string(),
+                "Camera".to_string(),
+                options.collect(),
+                String::new(),
+            )])
+        };
+        let (mut args, _rx) = request("/request/a");
+        args.options.choices = choice(&["front", "back"]);
+        let (mut same, _same_rx) = request("/request/b");
+        same.options.choices = choice(&["front", "back"]);
+        let (mut other, _other_rx) = request("/request/c");
+        other.options.choices = choice(&["front", "external"]);
+        assert!(args.is_similar(&same));
+        assert!(!args.is_similar(&other));
+        assert!(!args.is_similar(&request("/request/d").0));
+    }
 }
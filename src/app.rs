@@ -1,5 +1,5 @@
-use crate::{access, config, file_chooser, fl, screencast_dialog, screenshot, subscription};
-use cosmic::iced_core::event::wayland::OutputEvent;
+use crate::{access, config, file_chooser, fl, screencast_dialog, screenshot, subscription, toast};
+use cosmic::iced_core::event::wayland::{LayerEvent, OutputEvent};
 use cosmic::widget::{self, dropdown};
 use cosmic::Command;
 use cosmic::{
@@ -7,17 +7,22 @@ use cosmic::{
     iced::window,
     iced_futures::{event::listen_with, Subscription},
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use wayland_client::protocol::wl_output::WlOutput;
 
-pub(crate) fn run() -> cosmic::iced::Result {
+pub(crate) fn run(preview: Option<crate::preview::Preview>) -> cosmic::iced::Result {
     let settings = cosmic::app::Settings::default()
         .no_main_window(true)
         .exit_on_close(false);
     let (config, config_handler) = config::Config::load();
+    crate::request_limit::configure(&config);
+    crate::metrics::configure(&config);
+    crate::access::configure(&config);
+    crate::app_chooser::configure(&config);
     let flags = Flags {
         config,
         config_handler,
+        preview,
     };
     cosmic::app::run::<CosmicPortal>(settings, flags)
 }
@@ -31,9 +36,19 @@ pub struct CosmicPortal {
     pub config: config::Config,
 
     pub access_args: Option<access::AccessDialogArgs>,
-    pub access_choices: Vec<(Option<usize>, Vec<String>)>,
+    pub access_choices: Vec<access::Choice>,
+    pub access_details_shown: bool,
+    pub access_reason_expanded: bool,
+    /// Requests waiting for the dialog on screen to be answered
+    pub access_queue: VecDeque<access::AccessDialogArgs>,
+    pub access_apply_to_similar: bool,
+    /// Increased each time a dialog is shown, to tell whether a timeout is for the one on screen
+    pub access_serial: u64,
 
     pub file_choosers: HashMap<window::Id, (file_chooser::Args, file_chooser::Dialog)>,
+    pub archive_browsers: HashMap<window::Id, file_chooser::ArchiveBrowser>,
+    pub write_warnings: HashMap<window::Id, file_chooser::WriteWarning>,
+    pub file_copies: HashMap<window::Id, file_chooser::FileCopy>,
 
     pub screenshot_args: Option<screenshot::Args>,
     pub screencast_args: Option<screencast_dialog::Args>,
@@ -41,10 +56,19 @@ pub struct CosmicPortal {
         widget::segmented_button::Model<widget::segmented_button::SingleSelect>,
     pub location_options: Vec<String>,
     pub prev_rectangle: Option<screenshot::Rect>,
+    /// Last capture made in comparison mode
+    pub previous_capture: Option<(screenshot::Region, std::sync::Arc<image::RgbaImage>)>,
     pub wayland_helper: crate::wayland::WaylandHelper,
 
     pub outputs: Vec<OutputState>,
     pub active_output: Option<WlOutput>,
+    /// Scale last reported to the Settings portal
+    pub scale_factor: f64,
+    pub toast: Option<toast::Toast>,
+    /// Increased each time a toast is shown, to tell whether a dismissal is for the one shown
+    pub toast_serial: u64,
+    /// Whether a sample dialog is shown instead of serving the portal
+    pub preview: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +80,7 @@ pub struct OutputState {
     pub logical_pos: (i32, i32),
     pub has_pointer: bool,
     pub bg_source: Option<cosmic_bg_config::Source>,
+    pub scale_factor: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -64,8 +89,12 @@ pub enum Msg {
     FileChooser(window::Id, file_chooser::Msg),
     Screenshot(screenshot::Msg),
     Screencast(screencast_dialog::Msg),
+    Toast(toast::Msg),
     Portal(subscription::Event),
     Output(OutputEvent, WlOutput),
+    /// The compositor closed a layer surface
+    SurfaceClosed(window::Id),
+    SurfaceFocused(window::Id),
     ConfigSetScreenshot(config::screenshot::Screenshot),
     /// Update config from external changes
     ConfigSubUpdate(config::Config),
@@ -75,6 +104,7 @@ pub enum Msg {
 pub struct Flags {
     pub config_handler: Option<cosmic_config::Config>,
     pub config: config::Config,
+    pub preview: Option<crate::preview::Preview>,
 }
 
 impl cosmic::Application for CosmicPortal {
@@ -99,6 +129,7 @@ impl cosmic::Application for CosmicPortal {
         Flags {
             config_handler,
             config,
+            preview,
         }: Self::Flags,
     ) -> (Self, cosmic::iced::Command<app::Message<Self::Message>>) {
         let mut model = cosmic::widget::dropdown::multi::model();
@@ -122,26 +153,42 @@ impl cosmic::Application for CosmicPortal {
         model.selected = Some(config.screenshot.save_location);
         let wayland_conn = crate::wayland::connect_to_wayland();
         let wayland_helper = crate::wayland::WaylandHelper::new(wayland_conn);
-        (
-            Self {
-                core,
-                config_handler,
-                config,
-                access_args: Default::default(),
-                access_choices: Default::default(),
-                file_choosers: Default::default(),
-                screenshot_args: Default::default(),
-                screencast_args: Default::default(),
-                screencast_tab_model: Default::default(),
-                location_options: Vec::new(),
-                prev_rectangle: Default::default(),
-                outputs: Default::default(),
-                active_output: Default::default(),
-                wayland_helper,
-                tx: None,
-            },
-            cosmic::iced::Command::none(),
-        )
+        let scale_factor = wayland_helper.scale_factor();
+        let mut portal = Self {
+            core,
+            config_handler,
+            config,
+            access_args: Default::default(),
+            access_choices: Default::default(),
+            access_details_shown: false,
+            access_reason_expanded: false,
+            access_queue: VecDeque::new(),
+            access_apply_to_similar: false,
+            access_serial: 0,
+            file_choosers: Default::default(),
+            archive_browsers: Default::default(),
+            write_warnings: Default::default(),
+            file_copies: Default::default(),
+            screenshot_args: Default::default(),
+            screencast_args: Default::default(),
+            screencast_tab_model: Default::default(),
+            location_options: Vec::new(),
+            prev_rectangle: Default::default(),
+            previous_capture: Default::default(),
+            outputs: Default::default(),
+            active_output: Default::default(),
+            scale_factor,
+            wayland_helper,
+            tx: None,
+            toast: None,
+            toast_serial: 0,
+            preview: preview.is_some(),
+        };
+        let cmd = match preview {
+            Some(preview) => preview.show(&mut portal).map(cosmic::app::Message::App),
+            None => cosmic::iced::Command::none(),
+        };
+        (portal, cmd)
     }
 
     fn view(&self) -> cosmic::prelude::Element<Self::Message> {
@@ -149,10 +196,12 @@ impl cosmic::Application for CosmicPortal {
     }
 
     fn view_window(&self, id: window::Id) -> cosmic::prelude::Element<Self::Message> {
-        if id == *access::ACCESS_ID {
+        if access::is_surface(self, id) {
             access::view(self).map(Msg::Access)
         } else if id == *screencast_dialog::SCREENCAST_ID {
             screencast_dialog::view(self).map(Msg::Screencast)
+        } else if id == *toast::TOAST_ID {
+            toast::view(self).map(Msg::Toast)
         } else if self.outputs.iter().any(|o| o.id == id) {
             screenshot::view(self, id).map(Msg::Screenshot)
         } else {
@@ -171,6 +220,12 @@ impl cosmic::Application for CosmicPortal {
                 subscription::Event::Access(args) => {
                     access::update_args(self, args).map(cosmic::app::Message::App)
                 }
+                subscription::Event::CancelAccess(handle) => {
+                    access::cancel(self, handle).map(cosmic::app::Message::App)
+                }
+                subscription::Event::CancelShownAccess => {
+                    access::cancel_shown(self).map(cosmic::app::Message::App)
+                }
                 subscription::Event::FileChooser(args) => file_chooser::update_args(self, args),
                 subscription::Event::Screenshot(args) => {
                     screenshot::update_args(self, args).map(cosmic::app::Message::App)
@@ -184,7 +239,10 @@ impl cosmic::Application for CosmicPortal {
                 subscription::Event::Config(config) => self.update(Msg::ConfigSubUpdate(config)),
                 subscription::Event::Accent(_)
                 | subscription::Event::IsDark(_)
-                | subscription::Event::HighContrast(_) => cosmic::iced::Command::none(),
+                | subscription::Event::HighContrast(_)
+                | subscription::Event::ScaleFactor(_)
+                | subscription::Event::PromptShown(..)
+                | subscription::Event::PromptHidden(_) => cosmic::iced::Command::none(),
                 subscription::Event::Init(tx) => {
                     self.tx = Some(tx);
                     Command::none()
@@ -194,6 +252,7 @@ impl cosmic::Application for CosmicPortal {
             Msg::Screencast(m) => {
                 screencast_dialog::update_msg(self, m).map(cosmic::app::Message::App)
             }
+            Msg::Toast(m) => toast::update_msg(self, m).map(cosmic::app::Message::App),
             Msg::Output(o_event, wl_output) => {
                 match o_event {
                     OutputEvent::Created(Some(info))
@@ -201,6 +260,7 @@ impl cosmic::Application for CosmicPortal {
                             && info.logical_size.is_some()
                             && info.logical_position.is_some() =>
                     {
+                        let scale_factor = crate::wayland::output_scale_factor(&info);
                         self.outputs.push(OutputState {
                             output: wl_output,
                             id: window::Id::unique(),
@@ -212,6 +272,7 @@ impl cosmic::Application for CosmicPortal {
                             logical_pos: info.logical_position.unwrap(),
                             has_pointer: false,
                             bg_source: None,
+                            scale_factor,
                         })
                     }
                     OutputEvent::Removed => self.outputs.retain(|o| o.output != wl_output),
@@ -220,6 +281,7 @@ impl cosmic::Application for CosmicPortal {
                             && info.logical_size.is_some()
                             && info.logical_position.is_some() =>
                     {
+                        let scale_factor = crate::wayland::output_scale_factor(&info);
                         if let Some(state) = self.outputs.iter_mut().find(|o| o.output == wl_output)
                         {
                             state.name = info.name.unwrap();
@@ -228,6 +290,7 @@ impl cosmic::Application for CosmicPortal {
                                 .map(|(w, h)| (w as u32, h as u32))
                                 .unwrap();
                             state.logical_pos = info.logical_position.unwrap();
+                            state.scale_factor = scale_factor;
                         } else {
                             log::warn!("Updated output {:?} not found", wl_output);
                             self.outputs.push(OutputState {
@@ -241,6 +304,7 @@ impl cosmic::Application for CosmicPortal {
                                 logical_pos: info.logical_position.unwrap(),
                                 has_pointer: false,
                                 bg_source: None,
+                                scale_factor,
                             });
                         }
                     }
@@ -264,8 +328,48 @@ impl cosmic::Application for CosmicPortal {
                     self.prev_rectangle = Some(rect);
                 }
 
+                let scale_factor = crate::wayland::primary_scale_factor(
+                    self.outputs.iter().map(|o| (o.logical_pos, o.scale_factor)),
+                );
+                if scale_factor != self.scale_factor {
+                    self.scale_factor = scale_factor;
+                    if let Some(tx) = self.tx.clone() {
+                        tokio::spawn(async move {
+                            _ = tx
+                                .send(subscription::Event::ScaleFactor(scale_factor))
+                                .await;
+                        });
+                    }
+                }
+
                 cosmic::iced::Command::none()
             }
+            Msg::SurfaceClosed(id) => {
+                let reason = "compositor closed the surface".to_string();
+                if access::is_surface(self, id) {
+                    access::surface_closed(self, id, reason).map(cosmic::app::Message::App)
+                } else if id == *screencast_dialog::SCREENCAST_ID {
+                    match self.screencast_args.as_ref() {
+                        Some(args) => {
+                            log::error!("Screencast dialog: {reason}");
+                            let handle = args.session_handle.clone();
+                            screencast_dialog::cancel(self, handle).map(cosmic::app::Message::App)
+                        }
+                        None => Command::none(),
+                    }
+                } else if id == *toast::TOAST_ID {
+                    toast::surface_closed(self);
+                    Command::none()
+                } else if self.outputs.iter().any(|o| o.id == id) {
+                    screenshot::surface_closed(self, reason).map(cosmic::app::Message::App)
+                } else {
+                    file_chooser::surface_closed(self, id)
+                }
+            }
+            Msg::SurfaceFocused(id) => {
+                access::surface_focused(self, id);
+                Command::none()
+            }
             Msg::ConfigSetScreenshot(screenshot) => {
                 match &mut self.config_handler {
                     Some(handler) => {
@@ -279,6 +383,10 @@ impl cosmic::Application for CosmicPortal {
                 cosmic::iced::Command::none()
             }
             Msg::ConfigSubUpdate(config) => {
+                crate::request_limit::configure(&config);
+                crate::metrics::configure(&config);
+                crate::access::configure(&config);
+                crate::app_chooser::configure(&config);
                 self.config = config;
                 cosmic::iced::Command::none()
             }
@@ -287,8 +395,14 @@ impl cosmic::Application for CosmicPortal {
 
     #[allow(clippy::collapsible_match)]
     fn subscription(&self) -> cosmic::iced_futures::Subscription<Self::Message> {
+        // Previews don't serve the portal on D-Bus
+        let portal = if self.preview {
+            subscription::config_subscription()
+        } else {
+            subscription::portal_subscription(self.wayland_helper.clone())
+        };
         let mut subscriptions = vec![
-            subscription::portal_subscription(self.wayland_helper.clone()).map(Msg::Portal),
+            portal.map(Msg::Portal),
             listen_with(|e, _| match e {
                 cosmic::iced_core::Event::PlatformSpecific(
                     cosmic::iced_core::event::PlatformSpecific::Wayland(w_e),
@@ -296,11 +410,48 @@ impl cosmic::Application for CosmicPortal {
                     cosmic::iced_core::event::wayland::Event::Output(o_event, wl_output) => {
                         Some(Msg::Output(o_event, wl_output))
                     }
+                    cosmic::iced_core::event::wayland::Event::Layer(
+                        LayerEvent::Done,
+                        _surface,
+                        id,
+                    ) => Some(Msg::SurfaceClosed(id)),
+                    cosmic::iced_core::event::wayland::Event::Layer(
+                        LayerEvent::Focused,
+                        _surface,
+                        id,
+                    ) => Some(Msg::SurfaceFocused(id)),
                     _ => None,
                 },
                 _ => None,
             }),
         ];
+        // Until the pointer moves over the screenshot overlay, which it can't while another
+        // app has it locked. The compositor lifts the lock while the overlay has focus, and
+        // restores it when the app is focused again.
+        if self
+            .screenshot_args
+            .as_ref()
+            .is_some_and(|args| !args.pointer_seen)
+        {
+            subscriptions.push(listen_with(|e, _| match e {
+                cosmic::iced_core::Event::Mouse(
+                    cosmic::iced_core::mouse::Event::CursorEntered
+                    | cosmic::iced_core::mouse::Event::CursorMoved { .. },
+                ) => Some(Msg::Screenshot(screenshot::Msg::PointerSeen)),
+                _ => None,
+            }));
+        }
+        // Redraws the seconds left before an unattended dialog is answered
+        if self
+            .access_args
+            .as_ref()
+            .is_some_and(|args| args.shown && args.deadline.is_some())
+        {
+            subscriptions.push(
+                cosmic::iced::time::every(std::time::Duration::from_secs(1))
+                    .map(|_| Msg::Access(access::Msg::Tick)),
+            );
+        }
         for (id, (_args, dialog)) in self.file_choosers.iter() {
             let id = id.clone();
             subscriptions.push(dialog.subscription().map(move |x| Msg::FileChooser(id, x)));
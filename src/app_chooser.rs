@@ -0,0 +1,363 @@
+// Choosing the app to open a URI or file with, confirming schemes that can start
+// another app with arguments, like `ssh:`. The frontend only calls `ChooseApplication`
+// when there's more than one handler and no default, so URIs it opens directly are
+// neither confirmed nor blocked here.
+
+use futures::future::abortable;
+use once_cell::sync::Lazy;
+use std::sync::RwLock;
+use tokio::sync::mpsc::Sender;
+use zbus::zvariant;
+
+use crate::permission_store::PermissionStoreProxy;
+use crate::request_limit::RequestLimit;
+use crate::{access, config, fl, screencast_dialog, subscription, PortalResponse, Request};
+
+// Permission store table for apps always allowed to open a scheme, by scheme
+const PERMISSION_TABLE: &str = "com.system76.cosmic.open-uri";
+// IDs of the dialog's choices
+const APP_CHOICE: &str = "app";
+const ALWAYS_CHOICE: &str = "always";
+// Longest URI shown in full in the dialog's body
+const MAX_URI_CHARS: usize = 80;
+// Schemes opened without confirmation
+const FAST_PATH_SCHEMES: &[&str] = &["file", "http", "https"];
+
+// The `open_uri` config, kept current as the config changes so requests don't read it
+// from disk
+static SETTINGS: Lazy<RwLock<config::open_uri::OpenUri>> = Lazy::new(Default::default);
+
+/// Apply `config` to requests from now on
+pub fn configure(config: &config::Config) {
+    *SETTINGS.write().unwrap() = config.open_uri.clone();
+}
+
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug)]
+#[zvariant(signature = "a{sv}")]
+pub struct ChooseApplicationOptions {
+    last_choice: Option<String>,
+    modal: Option<bool>,
+    content_type: Option<String>,
+    uri: Option<String>,
+    filename: Option<String>,
+}
+
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
+#[zvariant(signature = "a{sv}")]
+pub struct ChooseApplicationResult {
+    choice: String,
+}
+
+pub struct AppChooser {
+    tx: Sender<subscription::Event>,
+    limit: RequestLimit,
+}
+
+impl AppChooser {
+    pub fn new(tx: Sender<subscription::Event>) -> Self {
+        Self {
+            tx,
+            limit: RequestLimit::new("AppChooser"),
+        }
+    }
+}
+
+impl AppChooser {
+    async fn choose_application_inner(
+        &self,
+        connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        app_id: &str,
+        parent_window: &str,
+        choices: Vec<String>,
+        options: ChooseApplicationOptions,
+    ) -> PortalResponse<ChooseApplicationResult> {
+        log::debug!("choose application {handle} {app_id} {parent_window} {choices:?} {options:?}");
+        let settings = SETTINGS.read().unwrap().clone();
+        let scheme = options.uri.as_deref().and_then(uri_scheme);
+        if let Some(scheme) = scheme.as_deref() {
+            if is_blocked(&settings, scheme) {
+                log::warn!("{app_id:?} tried to open a blocked {scheme}: URI");
+                return PortalResponse::Error(format!("{scheme}: URIs are blocked"));
+            }
+        }
+        let Some(target) = options
+            .last_choice
+            .as_ref()
+            .filter(|x| choices.contains(x))
+            .or_else(|| choices.first())
+            .cloned()
+        else {
+            return PortalResponse::Cancelled;
+        };
+
+        let confirm_scheme = scheme.filter(|scheme| needs_confirmation(&settings, scheme));
+        let confirm = match confirm_scheme.as_deref() {
+            Some(scheme) => !always_allowed(connection, app_id, scheme).await,
+            None => false,
+        };
+        if !confirm && choices.len() == 1 {
+            return PortalResponse::Success(ChooseApplicationResult { choice: target });
+        }
+
+        let app_name = if app_id.is_empty() {
+            fl!("unknown-application")
+        } else {
+            screencast_dialog::app_name(app_id)
+                .await
+                .unwrap_or_else(|| fl!("unknown-application"))
+        };
+        let mut app_options = Vec::with_capacity(choices.len());
+        for choice in &choices {
+            let name = screencast_dialog::app_name(choice)
+                .await
+                .unwrap_or_else(|| choice.clone());
+            app_options.push((choice.clone(), name));
+        }
+        let mut dialog_choices = Vec::with_capacity(2);
+        if choices.len() > 1 {
+            dialog_choices.push((
+                APP_CHOICE.to_string(),
+                fl!("open-with"),
+                app_options.clone(),
+                target.clone(),
+            ));
+        }
+        if confirm && !app_id.is_empty() {
+            dialog_choices.push((
+                ALWAYS_CHOICE.to_string(),
+                fl!("open-uri-always"),
+                Vec::new(),
+                "false".to_string(),
+            ));
+        }
+        let target_name = app_options
+            .iter()
+            .find(|(id, _)| *id == target)
+            .map_or_else(|| target.clone(), |(_, name)| name.clone());
+        let (title, body, details) = match (options.uri.as_deref(), confirm) {
+            (Some(uri), true) => {
+                let shown = ellipsize_middle(uri, MAX_URI_CHARS);
+                let details = (shown != uri).then(|| uri.to_string());
+                (
+                    fl!("open-uri"),
+                    fl!(
+                        "open-uri",
+                        "description",
+                        app_name = app_name,
+                        target_name = target_name,
+                        uri = shown
+                    ),
+                    details,
+                )
+            }
+            _ => {
+                let subject = options
+                    .filename
+                    .clone()
+                    .or_else(|| {
+                        options
+                            .uri
+                            .as_deref()
+                            .map(|x| ellipsize_middle(x, MAX_URI_CHARS))
+                    })
+                    .or_else(|| options.content_type.clone())
+                    .unwrap_or_default();
+                (
+                    fl!("choose-application"),
+                    fl!(
+                        "choose-application",
+                        "description",
+                        app_name = app_name,
+                        subject = subject
+                    ),
+                    None,
+                )
+            }
+        };
+        let dialog_options = access::AccessDialogOptions {
+            modal: options.modal,
+            grant_label: Some(fl!("open")),
+            icon: Some("document-open-symbolic".to_string()),
+            choices: Some(dialog_choices),
+            details,
+            ..Default::default()
+        };
+
+        let (abortable, abort_handle) = abortable(async {
+            let _slot = self.limit.acquire(&handle, &self.tx).await;
+            access::prompt(
+                &self.tx,
+                handle.to_owned(),
+                app_id,
+                title,
+                String::new(),
+                body,
+                dialog_options,
+            )
+            .await
+        });
+        let _ = connection
+            .object_server()
+            .at(&handle, Request(abort_handle))
+            .await;
+        let resp = abortable.await;
+        let _ = connection
+            .object_server()
+            .remove::<Request, _>(&handle)
+            .await;
+        let res = match resp {
+            Ok(Some(res)) => res,
+            Ok(None) => return PortalResponse::Cancelled,
+            Err(_) => {
+                // Closed with `Request.Close`
+                if let Err(err) = self
+                    .tx
+                    .send(subscription::Event::CancelAccess(handle.to_owned()))
+                    .await
+                {
+                    log::error!("Failed to send access dialog cancel event, {err}");
+                }
+                return PortalResponse::Cancelled;
+            }
+        };
+
+        if res.choice(ALWAYS_CHOICE) == Some("true") {
+            if let Some(scheme) = confirm_scheme.as_deref() {
+                allow_always(connection, app_id, scheme).await;
+            }
+        }
+        let choice = res.choice(APP_CHOICE).map_or(target, |x| x.to_string());
+        PortalResponse::Success(ChooseApplicationResult { choice })
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.AppChooser")]
+impl AppChooser {
+    async fn choose_application(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        app_id: &str,
+        parent_window: &str,
+        choices: Vec<String>,
+        options: ChooseApplicationOptions,
+    ) -> PortalResponse<ChooseApplicationResult> {
+        let response = self
+            .choose_application_inner(
+                connection,
+                handle.clone(),
+                app_id,
+                parent_window,
+                choices,
+                options,
+            )
+            .await;
+        crate::emit_response(connection, &handle, &response).await;
+        response
+    }
+
+    async fn update_choices(&self, handle: zvariant::ObjectPath<'_>, choices: Vec<String>) {
+        // The dialog keeps the choices it was shown with
+        log::warn!("updating the choices of {handle} is unsupported, ignoring {choices:?}");
+    }
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        2
+    }
+}
+
+/// How opening a URI with `scheme` for `app_id` would be answered, for
+/// `access::query_permission`: `reject` if the scheme is blocked, `prompt` if it needs
+/// confirming, or else `allow`. Choosing among several apps still shows a dialog.
+pub async fn query_scheme(
+    connection: &zbus::Connection,
+    app_id: &str,
+    scheme: &str,
+) -> &'static str {
+    let settings = SETTINGS.read().unwrap().clone();
+    let scheme = scheme.to_ascii_lowercase();
+    if is_blocked(&settings, &scheme) {
+        "reject"
+    } else if needs_confirmation(&settings, &scheme)
+        && !always_allowed(connection, app_id, &scheme).await
+    {
+        "prompt"
+    } else {
+        "allow"
+    }
+}
+
+fn is_blocked(settings: &config::open_uri::OpenUri, scheme: &str) -> bool {
+    settings
+        .blocked_schemes
+        .iter()
+        .any(|x| x.eq_ignore_ascii_case(scheme))
+}
+
+/// Whether opening a URI with the lowercase `scheme` is confirmed, unless always allowed
+fn needs_confirmation(settings: &config::open_uri::OpenUri, scheme: &str) -> bool {
+    !FAST_PATH_SCHEMES.contains(&scheme)
+        && settings
+            .confirm_schemes
+            .iter()
+            .any(|x| x.eq_ignore_ascii_case(scheme))
+}
+
+/// Lowercase scheme of `uri`
+fn uri_scheme(uri: &str) -> Option<String> {
+    let (scheme, _) = uri.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// `text` shortened to `max_chars`, keeping its start and end
+fn ellipsize_middle(text: &str, max_chars: usize) -> String {
+    let len = text.chars().count();
+    if len <= max_chars {
+        return text.to_string();
+    }
+    let keep = max_chars.saturating_sub(1);
+    let start: String = text.chars().take(keep - keep / 2).collect();
+    let end: String = text.chars().skip(len - keep / 2).collect();
+    format!("{start}…{end}")
+}
+
+async fn always_allowed(connection: &zbus::Connection, app_id: &str, scheme: &str) -> bool {
+    if app_id.is_empty() {
+        return false;
+    }
+    let permission = match PermissionStoreProxy::new(connection).await {
+        Ok(store) => store.get_permission(PERMISSION_TABLE, scheme, app_id).await,
+        Err(err) => Err(err),
+    };
+    // Fails when the table or scheme has no entries yet
+    permission.is_ok_and(|x| x.iter().any(|x| x == "yes"))
+}
+
+async fn allow_always(connection: &zbus::Connection, app_id: &str, scheme: &str) {
+    let result = async {
+        PermissionStoreProxy::new(connection)
+            .await?
+            .set_permission(PERMISSION_TABLE, true, scheme, app_id, &["yes"])
+            .await
+    };
+    if let Err(err) = result.await {
+        log::error!("failed to always allow {app_id} to open {scheme}: URIs: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_round_trips() {
+        crate::assert_round_trips(ChooseApplicationResult {
+            choice: "org.gnome.TextEditor".to_string(),
+        });
+    }
+}
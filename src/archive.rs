@@ -0,0 +1,143 @@
+// Picking files inside zip archives in the file chooser.
+//
+// Entries are extracted to a private directory under the runtime directory, and
+// that copy is returned to the caller. Without the `archives` feature, no file
+// is treated as an archive.
+
+use std::path::{Path, PathBuf};
+
+// Largest entry that will be extracted
+#[cfg(feature = "archives")]
+const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+// Entries compressed more than this are treated as zip bombs
+#[cfg(feature = "archives")]
+const MAX_COMPRESSION_RATIO: u64 = 100;
+
+/// File inside an archive
+#[derive(Clone, Debug)]
+pub struct Entry {
+    #[cfg_attr(not(feature = "archives"), allow(dead_code))]
+    index: usize,
+    /// Path within the archive
+    pub name: String,
+}
+
+/// Whether `path` is an archive that can be browsed into
+#[cfg(feature = "archives")]
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+        && path.is_file()
+}
+
+#[cfg(not(feature = "archives"))]
+pub fn is_archive(_path: &Path) -> bool {
+    false
+}
+
+/// Files in the archive at `path`, skipping directories and entries with unsafe names
+#[cfg(feature = "archives")]
+pub fn entries(path: &Path) -> anyhow::Result<Vec<Entry>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut entries = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        if file.is_dir() || file.enclosed_name().is_none() {
+            continue;
+        }
+        entries.push(Entry {
+            index,
+            name: file.name().to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(not(feature = "archives"))]
+pub fn entries(_path: &Path) -> anyhow::Result<Vec<Entry>> {
+    Err(anyhow::anyhow!("archive support not enabled"))
+}
+
+/// Extract `entry` of the archive at `path`, returning the path of the copy
+#[cfg(feature = "archives")]
+pub fn extract(path: &Path, entry: &Entry) -> anyhow::Result<PathBuf> {
+    use std::io::Read;
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut file = archive.by_index(entry.index)?;
+    let Some(file_name) = file
+        .enclosed_name()
+        .and_then(|x| x.file_name().map(PathBuf::from))
+    else {
+        return Err(anyhow::anyhow!("unsafe entry name {:?}", file.name()));
+    };
+    if file.size() > MAX_ENTRY_SIZE {
+        return Err(anyhow::anyhow!(
+            "{:?} is {} bytes, over the {} byte limit",
+            file.name(),
+            file.size(),
+            MAX_ENTRY_SIZE
+        ));
+    }
+    if file.size() > file.compressed_size().max(1) * MAX_COMPRESSION_RATIO {
+        return Err(anyhow::anyhow!(
+            "{:?} has a compression ratio over {}",
+            file.name(),
+            MAX_COMPRESSION_RATIO
+        ));
+    }
+
+    let base = dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("xdg-desktop-portal-cosmic")
+        .join("archives");
+    std::fs::create_dir_all(&base)?;
+    // Created with mode 0700
+    let dir = tempfile::Builder::new()
+        .prefix("extract-")
+        .tempdir_in(&base)?
+        .into_path();
+    let dest = dir.join(file_name);
+
+    // The recorded size may be wrong, so the limit is also enforced while reading
+    let result = crate::fs_util::write_atomic(&dest, 0o600, |out| {
+        let written = std::io::copy(&mut (&mut file).take(MAX_ENTRY_SIZE + 1), out)?;
+        if written > MAX_ENTRY_SIZE {
+            return Err(anyhow::anyhow!(
+                "{:?} is over the {} byte limit",
+                entry.name,
+                MAX_ENTRY_SIZE
+            ));
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        let _ = std::fs::remove_dir_all(&dir);
+        return Err(err);
+    }
+    Ok(dest)
+}
+
+#[cfg(not(feature = "archives"))]
+pub fn extract(_path: &Path, _entry: &Entry) -> anyhow::Result<PathBuf> {
+    Err(anyhow::anyhow!("archive support not enabled"))
+}
+
+/// Whether a file chooser filter accepts `name`, from its glob patterns.
+///
+/// Only `*.ext` globs are understood; a filter with none accepts everything.
+pub fn filter_accepts(patterns: &[(u32, String)], name: &str) -> bool {
+    let mut globs = patterns
+        .iter()
+        .filter(|(kind, _)| *kind == 0)
+        .map(|(_, glob)| glob.as_str())
+        .peekable();
+    if globs.peek().is_none() {
+        return true;
+    }
+    let name = name.to_lowercase();
+    globs.any(|glob| match glob.strip_prefix('*') {
+        Some(suffix) => name.ends_with(&suffix.to_lowercase()),
+        None => name == glob.to_lowercase(),
+    })
+}
@@ -0,0 +1,241 @@
+// Periodic review of apps allowed to run in the background or at login.
+//
+// Grants are read from the `background` table of the permission store, and from
+// autostart files written for sandboxed apps. The pending review is persisted, so
+// its actions still work after the portal restarts.
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::notification::{self, NotificationsProxy};
+use crate::permission_store::PermissionStoreProxy;
+use crate::{config, fl, screencast_dialog, state};
+
+// Permission store table and ID used by the Background portal
+const BACKGROUND_TABLE: &str = "background";
+const BACKGROUND_ID: &str = "background";
+// Key in autostart files naming the sandboxed app they start
+const AUTOSTART_APP_KEY: &str = "X-Flatpak=";
+// State directory key for the last and pending review
+const STATE_KEY: &str = "background_review";
+// How often to check whether a review is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct ReviewState {
+    // Seconds since the Unix epoch
+    last_review: u64,
+    pending: Option<PendingReview>,
+}
+
+/// Review notification still on screen, with the apps not yet kept or revoked
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct PendingReview {
+    notification_id: u32,
+    app_ids: Vec<String>,
+}
+
+impl ReviewState {
+    fn load() -> Self {
+        state::load(STATE_KEY)
+    }
+
+    fn save(&self) {
+        state::save(STATE_KEY, self);
+    }
+}
+
+/// Post a review whenever one is due, and handle its actions. Never returns.
+pub async fn run() {
+    let connection = match zbus::Connection::session().await {
+        Ok(connection) => connection,
+        Err(err) => {
+            log::error!("background review unavailable: {}", err);
+            return;
+        }
+    };
+    let actions_connection = connection.clone();
+    tokio::spawn(async move {
+        if let Err(err) = handle_actions(&actions_connection).await {
+            log::error!("failed to handle background review actions: {}", err);
+        }
+    });
+    loop {
+        // Loaded each time, so changes apply without a restart
+        let settings = config::Config::load().0.background;
+        if settings.review {
+            if let Err(err) = review_if_due(&connection, &settings).await {
+                log::error!("background review failed: {}", err);
+            }
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+async fn review_if_due(
+    connection: &zbus::Connection,
+    settings: &config::background::Background,
+) -> anyhow::Result<()> {
+    let mut state = ReviewState::load();
+    let interval = u64::from(settings.review_interval_days.max(1)) * 24 * 60 * 60;
+    let now = now();
+    if now < state.last_review.saturating_add(interval) {
+        return Ok(());
+    }
+    // Recorded first, so a failure below doesn't cause repeated reviews this period
+    state.last_review = now;
+    state.save();
+
+    let app_ids = granted_apps(connection).await?;
+    if app_ids.is_empty() {
+        state.pending = None;
+        state.save();
+        return Ok(());
+    }
+    let mut names = Vec::with_capacity(app_ids.len());
+    let mut actions = Vec::with_capacity(app_ids.len() * 2);
+    for app_id in &app_ids {
+        let name = screencast_dialog::app_name(app_id)
+            .await
+            .unwrap_or_else(|| app_id.clone());
+        actions.push((
+            format!("keep:{app_id}"),
+            fl!("background-keep", app_name = name.as_str()),
+        ));
+        actions.push((
+            format!("revoke:{app_id}"),
+            fl!("background-revoke", app_name = name.as_str()),
+        ));
+        names.push(name);
+    }
+    let notification_id = notification::notify_with_actions(
+        connection,
+        &fl!("background-review"),
+        &fl!(
+            "background-review",
+            "description",
+            app_names = names.join(", ")
+        ),
+        &actions,
+    )
+    .await?;
+    state.pending = Some(PendingReview {
+        notification_id,
+        app_ids,
+    });
+    state.save();
+    Ok(())
+}
+
+/// Apps with a background grant or an autostart file, sorted
+async fn granted_apps(connection: &zbus::Connection) -> anyhow::Result<Vec<String>> {
+    let mut app_ids: Vec<String> = autostart_files()
+        .into_iter()
+        .map(|(app_id, _)| app_id)
+        .collect();
+    let store = PermissionStoreProxy::new(connection).await?;
+    match store.lookup(BACKGROUND_TABLE, BACKGROUND_ID).await {
+        Ok((permissions, _)) => app_ids.extend(
+            permissions
+                .into_iter()
+                .filter(|(_, permission)| permission.iter().any(|x| x == "yes"))
+                .map(|(app_id, _)| app_id),
+        ),
+        // The table doesn't exist until an app asks to run in the background
+        Err(err) => log::info!("no background permissions: {}", err),
+    }
+    app_ids.sort();
+    app_ids.dedup();
+    Ok(app_ids)
+}
+
+/// Autostart files of sandboxed apps, with the app they start
+fn autostart_files() -> Vec<(String, PathBuf)> {
+    let Some(dir) = dirs::config_dir().map(|x| x.join("autostart")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "desktop" {
+                return None;
+            }
+            let contents = fs::read_to_string(&path).ok()?;
+            let app_id = contents
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(AUTOSTART_APP_KEY))?;
+            Some((app_id.to_string(), path))
+        })
+        .collect()
+}
+
+async fn handle_actions(connection: &zbus::Connection) -> zbus::Result<()> {
+    let proxy = NotificationsProxy::new(connection).await?;
+    let mut actions = proxy.receive_action_invoked().await?;
+    while let Some(signal) = actions.next().await {
+        let Ok(args) = signal.args() else {
+            continue;
+        };
+        let mut state = ReviewState::load();
+        let Some(pending) = state
+            .pending
+            .as_mut()
+            .filter(|x| x.notification_id == *args.id())
+        else {
+            continue;
+        };
+        let Some((action, app_id)) = args.action_key().split_once(':') else {
+            continue;
+        };
+        // Only act on apps this review listed
+        if !pending.app_ids.iter().any(|x| x == app_id) {
+            continue;
+        }
+        match action {
+            "revoke" => {
+                if let Err(err) = revoke(connection, app_id).await {
+                    log::error!("failed to revoke background permission of {app_id}: {err}");
+                    continue;
+                }
+                log::info!("revoked background permission of {app_id}");
+            }
+            "keep" => {}
+            _ => continue,
+        }
+        pending.app_ids.retain(|x| x != app_id);
+        if pending.app_ids.is_empty() {
+            state.pending = None;
+        }
+        state.save();
+    }
+    Ok(())
+}
+
+async fn revoke(connection: &zbus::Connection, app_id: &str) -> anyhow::Result<()> {
+    for (_, path) in autostart_files().into_iter().filter(|(x, _)| x == app_id) {
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    PermissionStoreProxy::new(connection)
+        .await?
+        .set_permission(BACKGROUND_TABLE, true, BACKGROUND_ID, app_id, &["no"])
+        .await?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
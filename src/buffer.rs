@@ -65,3 +65,83 @@ pub fn create_dmabuf<T: AsFd>(
             .collect(),
     }
 }
+
+/// Resample `src` to fill `dst`, a `width` by `height` RGBA buffer with no row padding.
+///
+/// For sources rendered at a different scale than a stream was negotiated at. The frame is
+/// filtered as a whole, so content rendered at different scales doesn't get seams between.
+pub fn resample_into(src: &image::RgbaImage, dst: &mut [u8], width: u32, height: u32) {
+    let resized =
+        image::imageops::resize(src, width, height, image::imageops::FilterType::Triangle);
+    dst[..resized.len()].copy_from_slice(&resized);
+}
+
+/// Heatmap of the per-pixel difference between two images of the same size
+///
+/// Unchanged pixels are a dimmed grayscale copy of `after`, and changed pixels range from
+/// yellow for small differences to red for large ones.
+pub fn difference_heatmap(before: &image::RgbaImage, after: &image::RgbaImage) -> image::RgbaImage {
+    image::RgbaImage::from_fn(after.width(), after.height(), |x, y| {
+        let [r, g, b, _] = after.get_pixel(x, y).0;
+        let delta = before
+            .get_pixel(x, y)
+            .0
+            .iter()
+            .zip([r, g, b])
+            .map(|(before, after)| before.abs_diff(after))
+            .max()
+            .unwrap_or(0);
+        if delta == 0 {
+            let gray = ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 3000) as u8;
+            image::Rgba([gray, gray, gray, 255])
+        } else {
+            image::Rgba([255, 255 - delta, 0, 255])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: image::Rgba<u8> = image::Rgba([255, 0, 0, 255]);
+    const BLUE: image::Rgba<u8> = image::Rgba([0, 0, 255, 255]);
+
+    // Red on the left half, blue on the right
+    fn halves(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, _| if x < width / 2 { RED } else { BLUE })
+    }
+
+    fn resampled(src: &image::RgbaImage, width: u32, height: u32) -> image::RgbaImage {
+        let mut dst = vec![0; (width * height * 4) as usize];
+        resample_into(src, &mut dst, width, height);
+        image::RgbaImage::from_raw(width, height, dst).unwrap()
+    }
+
+    #[test]
+    fn resampling_to_the_same_size_copies() {
+        let src = halves(8, 4);
+        assert_eq!(resampled(&src, 8, 4), src);
+    }
+
+    #[test]
+    fn resampling_keeps_edge_pixels() {
+        // From 2x to 1x, and back
+        for (src, width, height) in [(halves(16, 8), 8, 4), (halves(8, 4), 16, 8)] {
+            let dst = resampled(&src, width, height);
+            assert_eq!(dst.dimensions(), (width, height));
+            for y in [0, height - 1] {
+                assert_eq!(*dst.get_pixel(0, y), RED);
+                assert_eq!(*dst.get_pixel(width - 1, y), BLUE);
+            }
+        }
+    }
+
+    #[test]
+    fn resampling_fills_only_the_frame() {
+        let mut dst = vec![7; 8 * 4 * 4 + 4];
+        resample_into(&halves(16, 8), &mut dst, 8, 4);
+        assert_eq!(dst[..4], RED.0);
+        assert_eq!(dst[8 * 4 * 4..], [7; 4]);
+    }
+}
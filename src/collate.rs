@@ -0,0 +1,107 @@
+// Ordering and matching of file names as people read them, so `Apple` sorts beside
+// `apple`, and `cafe` finds `café` in languages that don't treat accented letters as
+// letters of their own.
+//
+// Keys fold case with Unicode lowercasing, and strip diacritics by decomposing and
+// dropping combining marks. Names with equal keys are ordered by their bytes, so the
+// order is total and the same on every sort.
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::config::file_chooser::Collation;
+
+// Languages that sort accented letters, like `å` or `ö`, as letters of their own
+const DISTINCT_DIACRITIC_LANGUAGES: &[&str] = &[
+    "cs", "da", "et", "fi", "hu", "is", "lt", "lv", "nb", "nn", "no", "pl", "sk", "sv", "tr",
+];
+
+pub struct Collator {
+    case_sensitive: bool,
+    ignore_diacritics: bool,
+}
+
+impl Collator {
+    pub fn new(collation: &Collation) -> Self {
+        let ignore_diacritics = collation.ignore_diacritics.unwrap_or_else(|| {
+            let language = crate::localize::LANGUAGE_LOADER.current_language();
+            !DISTINCT_DIACRITIC_LANGUAGES.contains(&language.language.as_str())
+        });
+        Self {
+            case_sensitive: collation.case_sensitive,
+            ignore_diacritics,
+        }
+    }
+
+    /// Form of `name` compared when sorting and searching
+    pub fn key(&self, name: &str) -> String {
+        let folded = if self.case_sensitive {
+            name.to_string()
+        } else {
+            name.to_lowercase()
+        };
+        if self.ignore_diacritics {
+            folded.nfd().filter(|c| !is_combining_mark(*c)).collect()
+        } else {
+            folded
+        }
+    }
+
+    /// Sort `items` by the names `name` gives them, computing each key once
+    pub fn sort<T>(&self, items: &mut [T], name: impl Fn(&T) -> &str) {
+        items.sort_by_cached_key(|item| {
+            let name = name(item);
+            (self.key(name), name.to_string())
+        });
+    }
+
+    /// Whether `name` contains `query`
+    pub fn matches(&self, query: &str, name: &str) -> bool {
+        self.key(name).contains(&self.key(query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collator(case_sensitive: bool, ignore_diacritics: bool) -> Collator {
+        Collator::new(&Collation {
+            case_sensitive,
+            ignore_diacritics: Some(ignore_diacritics),
+        })
+    }
+
+    #[test]
+    fn sorts_as_people_read() {
+        let mut names = ["banana", "Apple", "café", "apple", "cafe", "Cafe"];
+        collator(false, true).sort(&mut names, |x| x);
+        assert_eq!(names, ["Apple", "apple", "banana", "Cafe", "cafe", "café"]);
+
+        let mut names = ["banana", "Apple", "apple"];
+        collator(true, true).sort(&mut names, |x| x);
+        assert_eq!(names, ["Apple", "apple", "banana"]);
+    }
+
+    #[test]
+    fn sort_is_stable_across_runs() {
+        let collator = collator(false, true);
+        let mut a = ["e", "E", "é", "É"];
+        let mut b = ["É", "é", "E", "e"];
+        collator.sort(&mut a, |x| x);
+        collator.sort(&mut b, |x| x);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn matches_ignore_case_and_diacritics() {
+        let folding = collator(false, true);
+        assert!(folding.matches("cafe", "Café Menu.pdf"));
+        assert!(folding.matches("MENU", "Café Menu.pdf"));
+        assert!(!folding.matches("tea", "Café Menu.pdf"));
+
+        let exact = collator(true, false);
+        assert!(!exact.matches("cafe", "Café Menu.pdf"));
+        assert!(!exact.matches("menu", "Café Menu.pdf"));
+        assert!(exact.matches("Café", "Café Menu.pdf"));
+    }
+}
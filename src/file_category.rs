@@ -0,0 +1,205 @@
+// Broad kinds of files the file chooser can narrow its listing to, on top of the app's
+// filters. Patterns are those of file chooser filters, `(0, glob)` or `(1, MIME type)`.
+
+use crate::fl;
+
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "avif", "bmp", "gif", "heic", "heif", "ico", "jpeg", "jpg", "jxl", "png", "svg", "tif", "tiff",
+    "webp",
+];
+const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "csv", "doc", "docx", "epub", "md", "odp", "ods", "odt", "pdf", "ppt", "pptx", "rtf", "txt",
+    "xls", "xlsx",
+];
+const VIDEO_EXTENSIONS: &[&str] = &[
+    "avi", "flv", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "ogv", "webm", "wmv",
+];
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "aac", "aiff", "flac", "m4a", "mp3", "oga", "ogg", "opus", "wav", "wma",
+];
+// Document MIME types outside `text/`
+const DOCUMENT_MIME_TYPES: &[&str] = &[
+    "application/epub+zip",
+    "application/msword",
+    "application/pdf",
+    "application/rtf",
+    "application/vnd.ms-excel",
+    "application/vnd.ms-powerpoint",
+    "application/vnd.oasis.opendocument.presentation",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    Images,
+    Documents,
+    Video,
+    Audio,
+}
+
+impl Category {
+    pub const ALL: [Self; 4] = [Self::Images, Self::Documents, Self::Video, Self::Audio];
+
+    pub fn label(self) -> String {
+        match self {
+            Self::Images => fl!("file-category", "images"),
+            Self::Documents => fl!("file-category", "documents"),
+            Self::Video => fl!("file-category", "video"),
+            Self::Audio => fl!("file-category", "audio"),
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Images => IMAGE_EXTENSIONS,
+            Self::Documents => DOCUMENT_EXTENSIONS,
+            Self::Video => VIDEO_EXTENSIONS,
+            Self::Audio => AUDIO_EXTENSIONS,
+        }
+    }
+
+    /// Whether `mime_type`, which may be a wildcard like `image/*`, is of this category
+    fn has_mime_type(self, mime_type: &str) -> bool {
+        let mime_type = mime_type.to_ascii_lowercase();
+        match self {
+            Self::Images => mime_type.starts_with("image/"),
+            Self::Documents => {
+                mime_type.starts_with("text/") || DOCUMENT_MIME_TYPES.contains(&mime_type.as_str())
+            }
+            Self::Video => mime_type.starts_with("video/"),
+            Self::Audio => mime_type.starts_with("audio/"),
+        }
+    }
+
+    /// Category of files a pattern matches, if they're all of one
+    fn of_pattern((kind, value): &(u32, String)) -> Option<Self> {
+        match kind {
+            0 => {
+                let extension = value.strip_prefix("*.")?.to_ascii_lowercase();
+                Self::ALL
+                    .into_iter()
+                    .find(|x| x.extensions().contains(&extension.as_str()))
+            }
+            1 => Self::ALL.into_iter().find(|x| x.has_mime_type(value)),
+            _ => None,
+        }
+    }
+
+    /// Globs matching files of this category, by extension
+    pub fn patterns(self) -> Vec<(u32, String)> {
+        self.extensions()
+            .iter()
+            .map(|x| (0, format!("*.{x}")))
+            .collect()
+    }
+
+    /// `patterns` of a filter narrowed to this category, empty if they match none of it.
+    ///
+    /// A filter matching everything is narrowed to the category's own patterns.
+    pub fn intersect(self, patterns: &[(u32, String)]) -> Vec<(u32, String)> {
+        if patterns.is_empty() || patterns.iter().any(matches_all) {
+            return self.patterns();
+        }
+        patterns
+            .iter()
+            .filter(|x| Self::of_pattern(x) == Some(self))
+            .cloned()
+            .collect()
+    }
+
+    /// The one category all patterns of `filters` match, if there is one
+    pub fn of_filters<'a>(mut filters: impl Iterator<Item = &'a [(u32, String)]>) -> Option<Self> {
+        let mut found = None;
+        filters.try_for_each(|patterns| {
+            for pattern in patterns {
+                let category = Self::of_pattern(pattern)?;
+                if found.is_some_and(|x| x != category) {
+                    return None;
+                }
+                found = Some(category);
+            }
+            Some(())
+        })?;
+        found
+    }
+}
+
+fn matches_all((kind, value): &(u32, String)) -> bool {
+    match kind {
+        0 => value == "*",
+        1 => value == "*/*" || value == "application/octet-stream",
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(glob: &str) -> (u32, String) {
+        (0, glob.to_string())
+    }
+
+    fn mime(mime_type: &str) -> (u32, String) {
+        (1, mime_type.to_string())
+    }
+
+    #[test]
+    fn patterns_have_categories() {
+        assert_eq!(Category::of_pattern(&glob("*.PNG")), Some(Category::Images));
+        assert_eq!(
+            Category::of_pattern(&glob("*.pdf")),
+            Some(Category::Documents)
+        );
+        assert_eq!(
+            Category::of_pattern(&mime("video/*")),
+            Some(Category::Video)
+        );
+        assert_eq!(
+            Category::of_pattern(&mime("text/plain")),
+            Some(Category::Documents)
+        );
+        assert_eq!(
+            Category::of_pattern(&mime("application/pdf")),
+            Some(Category::Documents)
+        );
+        assert_eq!(Category::of_pattern(&glob("*.tar.gz")), None);
+        assert_eq!(Category::of_pattern(&glob("photo-*")), None);
+        assert_eq!(Category::of_pattern(&mime("application/zip")), None);
+        assert_eq!(Category::of_pattern(&(2, "*.png".to_string())), None);
+    }
+
+    #[test]
+    fn filters_are_narrowed() {
+        let filter = [glob("*.png"), glob("*.pdf"), mime("image/jpeg")];
+        assert_eq!(
+            Category::Images.intersect(&filter),
+            [glob("*.png"), mime("image/jpeg")]
+        );
+        assert!(Category::Audio.intersect(&filter).is_empty());
+        // Filters matching everything get the category's own patterns
+        assert_eq!(
+            Category::Audio.intersect(&[glob("*")]),
+            Category::Audio.patterns()
+        );
+        assert_eq!(Category::Video.intersect(&[]), Category::Video.patterns());
+    }
+
+    #[test]
+    fn filters_of_one_category() {
+        let images: &[_] = &[glob("*.png"), mime("image/*")];
+        let gifs: &[_] = &[glob("*.gif")];
+        let mixed: &[_] = &[glob("*.png"), glob("*.mp3")];
+        let unknown: &[_] = &[glob("*.bin")];
+        let of = |filters: &[&[(u32, String)]]| Category::of_filters(filters.iter().copied());
+        assert_eq!(of(&[images]), Some(Category::Images));
+        assert_eq!(of(&[images, gifs]), Some(Category::Images));
+        assert_eq!(of(&[mixed]), None);
+        assert_eq!(of(&[images, unknown]), None);
+        assert_eq!(of(&[]), None);
+    }
+}
@@ -1,15 +1,32 @@
-use cosmic::{app, iced::window, widget};
+use cosmic::{
+    app,
+    iced::{widget::column, window, Length},
+    iced_sctk::commands::layer_surface::{destroy_layer_surface, get_layer_surface},
+    widget::{self, button, horizontal_space, icon, scrollable, text, Column, Row},
+};
 use cosmic_files::dialog::{
     DialogChoice, DialogChoiceOption, DialogFilter, DialogFilterPattern, DialogKind, DialogMessage,
     DialogResult,
 };
-use std::{ffi::OsString, os::unix::ffi::OsStringExt, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    os::unix::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 use tokio::sync::mpsc::Sender;
 use zbus::zvariant;
 
 use crate::{
     app::{CosmicPortal, Msg as AppMsg},
-    subscription, PortalResponse,
+    archive,
+    collate::Collator,
+    file_category::Category,
+    file_copy, fl, partial_file,
+    responder::Responder,
+    state, subscription, PortalResponse,
 };
 
 pub(crate) type Dialog = cosmic_files::dialog::Dialog<Msg>;
@@ -18,6 +35,17 @@ type Choices = Vec<(String, String, Vec<(String, String)>, String)>;
 type Filter = (String, Vec<(u32, String)>);
 type Filters = Vec<Filter>;
 
+// State directory key for the state of open dialogs
+const SAVED_STATE_KEY: &str = "file_chooser";
+// How long saved state may be picked up by the same app re-issuing a request
+const RESTORE_WINDOW: Duration = Duration::from_secs(120);
+// State directory key for folders files were recently chosen in, most recent first
+const RECENT_LOCATIONS_KEY: &str = "recent_locations";
+const MAX_RECENT_LOCATIONS: usize = 15;
+// Time between redraws of the progress of opening copies, and before it's first shown, so
+// copies of small files don't flash a dialog by
+const COPY_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(zvariant::DeserializeDict, zvariant::Type, Clone, Debug)]
 #[zvariant(signature = "a{sv}")]
 pub struct OpenFileOptions {
@@ -110,6 +138,15 @@ impl FileChooserOptions {
         .unwrap_or(true)
     }
 
+    fn mode(&self) -> &'static str {
+        match self {
+            Self::OpenFile(x) if x.directory.unwrap_or(false) => "open-folder",
+            Self::OpenFile(_) => "open-file",
+            Self::SaveFile(_) => "save-file",
+            Self::SaveFiles(_) => "save-files",
+        }
+    }
+
     fn current_folder(&self) -> Option<PathBuf> {
         match self {
             Self::OpenFile(x) => x.current_folder.clone(),
@@ -126,7 +163,7 @@ impl FileChooserOptions {
     }
 }
 
-#[derive(zvariant::SerializeDict, zvariant::Type)]
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
 #[zvariant(signature = "a{sv}")]
 pub struct FileChooserResult {
     uris: Vec<String>,
@@ -134,6 +171,141 @@ pub struct FileChooserResult {
     current_filter: Option<Filter>,
 }
 
+/// Minimal state of an open dialog, persisted so it survives the portal being restarted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SavedState {
+    handle: String,
+    app_id: String,
+    mode: String,
+    // TODO: Also save the navigated folder and typed name, once cosmic-files exposes them
+    current_filter: Option<usize>,
+    // Seconds since the Unix epoch
+    saved_at: u64,
+}
+
+impl SavedState {
+    fn new(args: &Args, dialog: &Dialog) -> Self {
+        Self {
+            handle: args.handle.to_string(),
+            app_id: args.app_id.clone(),
+            mode: args.options.mode().to_string(),
+            current_filter: dialog.filters().1,
+            saved_at: 0,
+        }
+    }
+
+    /// Take saved state for a request re-delivered with the same handle, or re-issued
+    /// by the same app shortly after. States of dialogs open in `live_handles` aren't taken.
+    fn take(args: &Args, live_handles: &[String]) -> Option<Self> {
+        let now = now();
+        let handle = args.handle.to_string();
+        let mut taken = None;
+        state::update(SAVED_STATE_KEY, |states: &mut Vec<Self>| {
+            let i = Self::position(
+                states,
+                &handle,
+                &args.app_id,
+                args.options.mode(),
+                live_handles,
+                now,
+            );
+            taken = i.map(|i| states.remove(i));
+            taken.is_some()
+        });
+        taken
+    }
+
+    /// Index of the state in `states` that `take` takes at `now`
+    fn position(
+        states: &[Self],
+        handle: &str,
+        app_id: &str,
+        mode: &str,
+        live_handles: &[String],
+        now: u64,
+    ) -> Option<usize> {
+        states.iter().position(|x| x.handle == handle).or_else(|| {
+            states.iter().position(|x| {
+                !live_handles.contains(&x.handle)
+                    && x.app_id == app_id
+                    && x.mode == mode
+                    && now.saturating_sub(x.saved_at) <= RESTORE_WINDOW.as_secs()
+            })
+        })
+    }
+
+    fn save(&mut self) {
+        self.saved_at = now();
+        state::update(SAVED_STATE_KEY, |states: &mut Vec<Self>| {
+            states.retain(|x| x.handle != self.handle);
+            states.push(self.clone());
+            true
+        });
+    }
+
+    fn clear(handle: &str) {
+        state::update(SAVED_STATE_KEY, |states: &mut Vec<Self>| {
+            let len = states.len();
+            states.retain(|x| x.handle != handle);
+            states.len() != len
+        });
+    }
+}
+
+/// Remember the folders of chosen `paths`, or the paths themselves if they are folders
+fn add_recent_locations(paths: &[PathBuf]) {
+    let mut folders: Vec<PathBuf> = Vec::with_capacity(paths.len());
+    for path in paths {
+        let folder = if path.is_dir() {
+            Some(path.as_path())
+        } else {
+            path.parent()
+        };
+        if let Some(folder) = folder.filter(|x| !is_private_location(x)) {
+            if !folders.iter().any(|x| x == folder) {
+                folders.push(folder.to_path_buf());
+            }
+        }
+    }
+    if folders.is_empty() {
+        return;
+    }
+    state::update(RECENT_LOCATIONS_KEY, |recent: &mut Vec<PathBuf>| {
+        recent.retain(|x| !folders.contains(x));
+        recent.splice(0..0, folders);
+        recent.truncate(MAX_RECENT_LOCATIONS);
+        true
+    });
+}
+
+/// Most recent location that still exists, forgetting those that don't
+fn recent_location() -> Option<PathBuf> {
+    let mut location = None;
+    state::update(RECENT_LOCATIONS_KEY, |recent: &mut Vec<PathBuf>| {
+        let len = recent.len();
+        recent.retain(|x| x.is_dir());
+        location = recent.first().cloned();
+        recent.len() != len
+    });
+    location
+}
+
+// Locations not worth remembering, or that shouldn't be
+fn is_private_location(path: &Path) -> bool {
+    let in_trash = dirs::data_dir().is_some_and(|x| path.starts_with(x.join("Trash")))
+        || path.components().any(|x| {
+            let name = x.as_os_str().to_string_lossy();
+            name == ".Trash" || name.starts_with(".Trash-")
+        });
+    path.starts_with("/run") || path.starts_with("/proc") || in_trash
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
 pub struct FileChooser {
     tx: Sender<subscription::Event>,
 }
@@ -153,7 +325,7 @@ impl FileChooser {
     ) -> PortalResponse<FileChooserResult> {
         log::debug!("file chooser {handle}, {app_id}, {parent_window}, {title}, {options:?}");
 
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, mut rx) = Responder::channel();
         if let Err(err) = self
             .tx
             .send(subscription::Event::FileChooser(Args {
@@ -163,6 +335,12 @@ impl FileChooser {
                 title: title.to_string(),
                 options,
                 tx,
+                saved: None,
+                filters: Vec::new(),
+                show_categories: false,
+                category: None,
+                offer_copy: false,
+                open_copy: false,
             }))
             .await
         {
@@ -238,6 +416,56 @@ impl FileChooser {
 pub enum Msg {
     DialogMessage(DialogMessage),
     DialogResult(DialogResult),
+    /// Extract an entry of the archive being browsed
+    ArchiveEntry(usize),
+    ArchiveSearch(String),
+    ArchiveCancel,
+    /// Files chosen that seem to be still being written, found after the dialog closed
+    BeingWritten(Vec<PathBuf>),
+    /// Return the files chosen, despite a warning that some are still being written
+    WarningOpen,
+    WarningCancel,
+    /// Open copies of the files chosen, instead of the files
+    OpenCopy(bool),
+    /// Redraw the progress of opening copies, showing it if it isn't yet
+    CopyTick,
+    CopyCancel,
+    /// Copies to open, made after the dialog closed
+    Copied(Result<Vec<PathBuf>, String>),
+    /// Narrow the listing to a category, or stop if it already is
+    Category(Category),
+    // TODO: Open a selected file in its default app, leaving the dialog open, from an
+    // "Open with default application" item of the context menu of selected files. The
+    // dialog of cosmic-files has no way to add one yet.
+}
+
+/// Archive picked in a dialog, whose entries are being chosen from
+pub(crate) struct ArchiveBrowser {
+    args: Args,
+    archive: PathBuf,
+    entries: Vec<archive::Entry>,
+    // Text entries are filtered by
+    query: String,
+    // Result of the dialog, other than the URIs
+    result: FileChooserResult,
+}
+
+/// Result of a dialog held back while files chosen are checked, and then while the user is
+/// warned of those still being written
+pub(crate) struct WriteWarning {
+    args: Args,
+    result: FileChooserResult,
+    // Files found to be still being written, empty while checking
+    files: Vec<PathBuf>,
+}
+
+/// Result of a dialog held back while copies of the files chosen are made to open
+pub(crate) struct FileCopy {
+    args: Args,
+    result: FileChooserResult,
+    progress: Arc<file_copy::Progress>,
+    // Whether the progress is shown, which it is only once the copy takes a moment
+    shown: bool,
 }
 
 #[derive(Clone)]
@@ -247,7 +475,19 @@ pub(crate) struct Args {
     pub parent_window: String,
     pub title: String,
     pub options: FileChooserOptions,
-    pub tx: Sender<PortalResponse<FileChooserResult>>,
+    pub tx: Responder<PortalResponse<FileChooserResult>>,
+    /// State last persisted for this dialog
+    pub saved: Option<SavedState>,
+    /// Filters offered, including pinned ones, before narrowing them to `category`
+    pub filters: Filters,
+    /// Whether categories are offered, which they aren't if the app's filters are all of one
+    pub show_categories: bool,
+    /// Category the listing is narrowed to, kept for this dialog only
+    pub category: Option<Category>,
+    /// Whether opening a copy is offered, which it is to sandboxed apps opening files
+    pub offer_copy: bool,
+    /// Whether to open copies of the files chosen
+    pub open_copy: bool,
 }
 
 fn map_msg(id: window::Id, message: app::Message<Msg>) -> app::Message<AppMsg> {
@@ -259,12 +499,382 @@ fn map_msg(id: window::Id, message: app::Message<Msg>) -> app::Message<AppMsg> {
 }
 
 pub(crate) fn view(portal: &CosmicPortal, id: window::Id) -> cosmic::Element<AppMsg> {
+    if let Some(browser) = portal.archive_browsers.get(&id) {
+        return archive_view(portal, browser).map(move |msg| AppMsg::FileChooser(id, msg));
+    }
+    if let Some(warning) = portal.write_warnings.get(&id) {
+        return write_warning_view(portal, warning).map(move |msg| AppMsg::FileChooser(id, msg));
+    }
+    if let Some(copy) = portal.file_copies.get(&id) {
+        return copy_view(portal, copy).map(move |msg| AppMsg::FileChooser(id, msg));
+    }
     match portal.file_choosers.get(&id) {
+        Some((args, dialog)) if args.show_categories || args.offer_copy => {
+            let mut content = Vec::with_capacity(3);
+            if args.show_categories {
+                content.push(category_chips(portal, args, dialog));
+            }
+            if args.offer_copy {
+                content.push(copy_toggle(portal, args));
+            }
+            content.push(dialog.view(id));
+            cosmic::Element::from(Column::with_children(content))
+                .map(move |msg| AppMsg::FileChooser(id, msg))
+        }
         Some((_args, dialog)) => dialog.view(id).map(move |msg| AppMsg::FileChooser(id, msg)),
         None => widget::text(format!("no file chooser dialog with ID {id:?}")).into(),
     }
 }
 
+// Buttons narrowing the listing to a category. Those matching nothing the selected filter
+// does are disabled.
+fn category_chips<'a>(
+    portal: &CosmicPortal,
+    args: &Args,
+    dialog: &Dialog,
+) -> cosmic::Element<'a, Msg> {
+    let spacing = portal.core.system_theme().cosmic().spacing;
+    let selected = dialog.filters().1.and_then(|i| args.filters.get(i));
+    let chips = Category::ALL.into_iter().map(|category| {
+        let active = args.category == Some(category);
+        let usable = active
+            || selected.map_or(true, |(_, patterns)| {
+                !category.intersect(patterns).is_empty()
+            });
+        button::text(category.label())
+            .style(if active {
+                cosmic::theme::Button::Suggested
+            } else {
+                cosmic::theme::Button::Standard
+            })
+            .on_press_maybe(usable.then_some(Msg::Category(category)))
+            .into()
+    });
+    let mut content = vec![Row::with_children(chips.collect())
+        .spacing(spacing.space_xs as f32)
+        .into()];
+    // A folder may look empty because of it
+    if let Some(category) = args.category {
+        content.push(
+            text(fl!("file-category-active", category = category.label()))
+                .size(12)
+                .into(),
+        );
+    }
+    widget::container(Column::with_children(content).spacing(spacing.space_xxs as f32))
+        .padding([spacing.space_xs, spacing.space_m])
+        .into()
+}
+
+// Toggle opening copies of the files chosen, so the app can't change the originals
+fn copy_toggle<'a>(portal: &CosmicPortal, args: &Args) -> cosmic::Element<'a, Msg> {
+    let spacing = portal.core.system_theme().cosmic().spacing;
+    widget::container(
+        column![
+            widget::checkbox(fl!("open-copy"), args.open_copy).on_toggle(Msg::OpenCopy),
+            text(fl!("open-copy", "description")).size(12),
+        ]
+        .spacing(spacing.space_xxs as f32),
+    )
+    .padding([spacing.space_xs, spacing.space_m])
+    .into()
+}
+
+fn archive_view<'a>(
+    portal: &CosmicPortal,
+    browser: &'a ArchiveBrowser,
+) -> cosmic::Element<'a, Msg> {
+    let spacing = portal.core.system_theme().cosmic().spacing;
+    let archive_name = browser
+        .archive
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let collator = Collator::new(&portal.config.file_chooser.collation);
+    let entries = browser.entries.iter().enumerate();
+    // Rows are marked as archive contents, since choosing one extracts a copy
+    let rows = entries
+        .filter(|(_, entry)| {
+            browser.query.is_empty() || collator.matches(&browser.query, &entry.name)
+        })
+        .map(|(i, entry)| {
+            button::custom(
+                Row::with_children(vec![
+                    icon::from_name("package-x-generic-symbolic")
+                        .size(16)
+                        .into(),
+                    text(entry.name.as_str()).into(),
+                    horizontal_space(Length::Fill).into(),
+                    text(fl!("archive-extract")).size(12).into(),
+                ])
+                .spacing(spacing.space_xs as f32),
+            )
+            .width(Length::Fill)
+            .style(cosmic::theme::Button::MenuItem)
+            .on_press(Msg::ArchiveEntry(i))
+            .into()
+        });
+    widget::container(
+        column![
+            text(fl!("archive-contents", archive = archive_name)),
+            text(fl!("archive-contents", "description")).size(12),
+            widget::search_input(fl!("archive-search"), browser.query.as_str())
+                .on_input(Msg::ArchiveSearch)
+                .on_clear(Msg::ArchiveSearch(String::new())),
+            widget::container(scrollable(
+                Column::with_children(rows).spacing(spacing.space_xxxs as f32)
+            ))
+            .max_height(400.),
+            Row::with_children(vec![
+                horizontal_space(Length::Fill).into(),
+                button::text(fl!("cancel"))
+                    .on_press(Msg::ArchiveCancel)
+                    .into(),
+            ]),
+        ]
+        .spacing(spacing.space_s as f32),
+    )
+    .width(Length::Fixed(480.))
+    .padding(spacing.space_m)
+    .into()
+}
+
+fn write_warning_view<'a>(
+    portal: &CosmicPortal,
+    warning: &'a WriteWarning,
+) -> cosmic::Element<'a, Msg> {
+    let spacing = portal.core.system_theme().cosmic().spacing;
+    let files = warning.files.iter().map(|path| {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        Row::with_children(vec![
+            icon::from_name("dialog-warning-symbolic").size(16).into(),
+            text(name.to_string_lossy().into_owned()).into(),
+        ])
+        .spacing(spacing.space_xs as f32)
+        .into()
+    });
+    widget::container(
+        column![
+            text(fl!("being-written", count = warning.files.len())),
+            text(fl!("being-written", "description")).size(12),
+            widget::container(scrollable(
+                Column::with_children(files).spacing(spacing.space_xxxs as f32)
+            ))
+            .max_height(200.),
+            Row::with_children(vec![
+                horizontal_space(Length::Fill).into(),
+                button::text(fl!("cancel"))
+                    .on_press(Msg::WarningCancel)
+                    .into(),
+                button::text(fl!("being-written", "open"))
+                    .style(cosmic::theme::Button::Suggested)
+                    .on_press(Msg::WarningOpen)
+                    .into(),
+            ])
+            .spacing(spacing.space_xs as f32),
+        ]
+        .spacing(spacing.space_s as f32),
+    )
+    .width(Length::Fixed(480.))
+    .padding(spacing.space_m)
+    .into()
+}
+
+fn copy_view<'a>(portal: &CosmicPortal, copy: &'a FileCopy) -> cosmic::Element<'a, Msg> {
+    let spacing = portal.core.system_theme().cosmic().spacing;
+    let cancelled = copy.progress.is_cancelled();
+    widget::container(
+        column![
+            text(fl!("open-copy", "copying", count = copy.result.uris.len())),
+            cosmic::iced::widget::progress_bar(0.0..=1.0, copy.progress.fraction()),
+            Row::with_children(vec![
+                horizontal_space(Length::Fill).into(),
+                button::text(fl!("cancel"))
+                    .on_press_maybe((!cancelled).then_some(Msg::CopyCancel))
+                    .into(),
+            ]),
+        ]
+        .spacing(spacing.space_s as f32),
+    )
+    .width(Length::Fixed(480.))
+    .padding(spacing.space_m)
+    .into()
+}
+
+/// Browse into `archive` instead of returning it, if it isn't what the current filter wants.
+///
+/// Only single file selection in open dialogs descends into archives.
+fn browse_archive(
+    portal: &mut CosmicPortal,
+    args: Args,
+    paths: &[PathBuf],
+    result: FileChooserResult,
+) -> Result<cosmic::Command<app::Message<AppMsg>>, (Args, FileChooserResult)> {
+    let single_file = match &args.options {
+        FileChooserOptions::OpenFile(options) => {
+            !options.directory.unwrap_or(false) && !options.multiple.unwrap_or(false)
+        }
+        _ => false,
+    };
+    let [archive] = paths else {
+        return Err((args, result));
+    };
+    let Some((_, patterns)) = result.current_filter.as_ref() else {
+        return Err((args, result));
+    };
+    let archive_name = archive.to_string_lossy();
+    if !single_file
+        || !archive::is_archive(archive)
+        || archive::filter_accepts(patterns, &archive_name)
+        || patterns
+            .iter()
+            .any(|x| *x == (1, "application/zip".to_string()))
+    {
+        return Err((args, result));
+    }
+    let mut entries: Vec<_> = match archive::entries(archive) {
+        Ok(entries) => entries
+            .into_iter()
+            .filter(|x| archive::filter_accepts(patterns, &x.name))
+            .collect(),
+        Err(err) => {
+            log::error!("failed to read archive {:?}: {}", archive, err);
+            return Err((args, result));
+        }
+    };
+    Collator::new(&portal.config.file_chooser.collation).sort(&mut entries, |x| &x.name);
+
+    let id = window::Id::unique();
+    portal.archive_browsers.insert(
+        id,
+        ArchiveBrowser {
+            args,
+            archive: archive.clone(),
+            entries,
+            query: String::new(),
+            result,
+        },
+    );
+    Ok(get_layer_surface(crate::surfaces::centered_dialog(
+        id,
+        "file-chooser-archive",
+    )))
+}
+
+/// Check whether files opened are still being written before returning them, to warn if
+/// they are. Checked only once chosen, so listing folders never waits on it.
+fn check_being_written(
+    portal: &mut CosmicPortal,
+    args: Args,
+    paths: Vec<PathBuf>,
+    result: FileChooserResult,
+) -> cosmic::Command<app::Message<AppMsg>> {
+    // Files saved to are about to be written by the app itself
+    if !matches!(args.options, FileChooserOptions::OpenFile(_)) {
+        return send_response(args, PortalResponse::Success(result));
+    }
+    let id = window::Id::unique();
+    portal.write_warnings.insert(
+        id,
+        WriteWarning {
+            args,
+            result,
+            files: Vec::new(),
+        },
+    );
+    cosmic::Command::perform(
+        async move {
+            tokio::task::spawn_blocking(move || partial_file::being_written(&paths))
+                .await
+                .unwrap_or_default()
+        },
+        move |files| app::Message::App(AppMsg::FileChooser(id, Msg::BeingWritten(files))),
+    )
+}
+
+/// Return the files opened, or copies of them if the user chose to open copies
+fn respond_opened(
+    portal: &mut CosmicPortal,
+    args: Args,
+    result: FileChooserResult,
+) -> cosmic::Command<app::Message<AppMsg>> {
+    if !args.open_copy {
+        return send_response(args, PortalResponse::Success(result));
+    }
+    let Some(dir) = file_copy::copies_dir(&args.app_id) else {
+        log::error!("no directory for copies opened by {:?}", args.app_id);
+        return send_response(args, PortalResponse::Other);
+    };
+    let paths: Vec<PathBuf> = result
+        .uris
+        .iter()
+        .filter_map(|x| url::Url::parse(x).ok()?.to_file_path().ok())
+        .collect();
+    let progress = Arc::new(file_copy::Progress::default());
+    let id = window::Id::unique();
+    portal.file_copies.insert(
+        id,
+        FileCopy {
+            args,
+            result,
+            progress: progress.clone(),
+            shown: false,
+        },
+    );
+    cosmic::Command::batch([
+        cosmic::Command::perform(
+            async move {
+                let copied = tokio::task::spawn_blocking(move || {
+                    file_copy::copy_into(&paths, &dir, &progress)
+                })
+                .await;
+                match copied {
+                    Ok(Ok(copies)) => Ok(copies),
+                    Ok(Err(err)) => Err(err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                }
+            },
+            move |copied| app::Message::App(AppMsg::FileChooser(id, Msg::Copied(copied))),
+        ),
+        copy_tick(id),
+    ])
+}
+
+fn copy_tick(id: window::Id) -> cosmic::Command<app::Message<AppMsg>> {
+    cosmic::Command::perform(tokio::time::sleep(COPY_PROGRESS_INTERVAL), move |()| {
+        app::Message::App(AppMsg::FileChooser(id, Msg::CopyTick))
+    })
+}
+
+fn send_response(
+    args: Args,
+    response: PortalResponse<FileChooserResult>,
+) -> cosmic::Command<app::Message<AppMsg>> {
+    args.tx.send(response);
+    cosmic::Command::none()
+}
+
+/// Cancel browsing an archive, a warning of files being written or opening copies, if the
+/// compositor closed its surface
+pub fn surface_closed(
+    portal: &mut CosmicPortal,
+    id: window::Id,
+) -> cosmic::Command<app::Message<AppMsg>> {
+    // Answered once the copy stops
+    if let Some(copy) = portal.file_copies.get_mut(&id) {
+        copy.progress.cancel();
+        copy.shown = false;
+        return cosmic::Command::none();
+    }
+    match portal.archive_browsers.remove(&id) {
+        Some(browser) => send_response(browser.args, PortalResponse::Cancelled),
+        None => match portal.write_warnings.remove(&id) {
+            Some(warning) => send_response(warning.args, PortalResponse::Cancelled),
+            None => cosmic::Command::none(),
+        },
+    }
+}
+
 pub fn update_msg(
     portal: &mut CosmicPortal,
     id: window::Id,
@@ -272,21 +882,223 @@ pub fn update_msg(
 ) -> cosmic::Command<app::Message<AppMsg>> {
     match msg {
         Msg::DialogMessage(dialog_msg) => match portal.file_choosers.get_mut(&id) {
-            Some((_args, dialog)) => dialog.update(dialog_msg).map(move |msg| map_msg(id, msg)),
+            Some((args, dialog)) => {
+                let mut cmd = dialog.update(dialog_msg).map(move |msg| map_msg(id, msg));
+                let current_filter = dialog.filters().1;
+                // Another filter was selected, which nothing of the category matches
+                let unmatched = args
+                    .category
+                    .zip(current_filter)
+                    .is_some_and(|(category, i)| {
+                        args.filters
+                            .get(i)
+                            .is_some_and(|(_, patterns)| category.intersect(patterns).is_empty())
+                    });
+                if unmatched {
+                    args.category = None;
+                    let filters = dialog_filters(&args.filters, None);
+                    let set_filters = dialog.set_filters(filters, current_filter);
+                    cmd =
+                        cosmic::Command::batch([cmd, set_filters.map(move |msg| map_msg(id, msg))]);
+                }
+                if let Some(saved) = args
+                    .saved
+                    .as_mut()
+                    .filter(|x| x.current_filter != current_filter)
+                {
+                    saved.current_filter = current_filter;
+                    saved.save();
+                }
+                cmd
+            }
             None => {
                 log::warn!("no file chooser dialog with ID {id:?}");
                 cosmic::Command::none()
             }
         },
+        Msg::ArchiveEntry(i) => match portal.archive_browsers.remove(&id) {
+            Some(browser) => {
+                let ArchiveBrowser {
+                    args,
+                    archive,
+                    entries,
+                    result,
+                    ..
+                } = browser;
+                let Some(entry) = entries.get(i).cloned() else {
+                    log::warn!("no archive entry {i}");
+                    return destroy_layer_surface(id);
+                };
+                cosmic::Command::batch([
+                    destroy_layer_surface(id),
+                    cosmic::Command::perform(
+                        async move {
+                            let extracted = tokio::task::spawn_blocking(move || {
+                                archive::extract(&archive, &entry)
+                            })
+                            .await;
+                            let response = match extracted {
+                                Ok(Ok(path)) => match url::Url::from_file_path(&path) {
+                                    Ok(url) => PortalResponse::Success(FileChooserResult {
+                                        uris: vec![url.to_string()],
+                                        ..result
+                                    }),
+                                    Err(()) => {
+                                        log::error!("failed to convert to URL: {:?}", path);
+                                        PortalResponse::Other
+                                    }
+                                },
+                                Ok(Err(err)) => {
+                                    log::error!("failed to extract from archive: {}", err);
+                                    PortalResponse::Error(err.to_string())
+                                }
+                                Err(err) => {
+                                    log::error!("failed to extract from archive: {}", err);
+                                    PortalResponse::Other
+                                }
+                            };
+                            args.tx.send(response);
+                            cosmic::app::message::none()
+                        },
+                        |x| x,
+                    ),
+                ])
+            }
+            None => {
+                log::warn!("no archive browser with ID {id:?}");
+                cosmic::Command::none()
+            }
+        },
+        Msg::Category(category) => {
+            let Some((args, dialog)) = portal.file_choosers.get_mut(&id) else {
+                log::warn!("no file chooser dialog with ID {id:?}");
+                return cosmic::Command::none();
+            };
+            args.category = (args.category != Some(category)).then_some(category);
+            // Narrowing no filters at all adds one of the category's
+            let selected = if args.filters.is_empty() {
+                args.category.map(|_| 0)
+            } else {
+                dialog.filters().1
+            };
+            let filters = dialog_filters(&args.filters, args.category);
+            dialog
+                .set_filters(filters, selected)
+                .map(move |msg| map_msg(id, msg))
+        }
+        Msg::ArchiveSearch(query) => {
+            if let Some(browser) = portal.archive_browsers.get_mut(&id) {
+                browser.query = query;
+            }
+            cosmic::Command::none()
+        }
+        Msg::ArchiveCancel => match portal.archive_browsers.remove(&id) {
+            Some(browser) => cosmic::Command::batch([
+                destroy_layer_surface(id),
+                send_response(browser.args, PortalResponse::Cancelled),
+            ]),
+            None => cosmic::Command::none(),
+        },
+        Msg::BeingWritten(files) => {
+            let Some(mut warning) = portal.write_warnings.remove(&id) else {
+                log::warn!("no file chooser warning with ID {id:?}");
+                return cosmic::Command::none();
+            };
+            if files.is_empty() {
+                return respond_opened(portal, warning.args, warning.result);
+            }
+            log::info!("files chosen may be still being written: {:?}", files);
+            warning.files = files;
+            portal.write_warnings.insert(id, warning);
+            get_layer_surface(crate::surfaces::centered_dialog(
+                id,
+                "file-chooser-being-written",
+            ))
+        }
+        Msg::WarningOpen => match portal.write_warnings.remove(&id) {
+            Some(warning) => cosmic::Command::batch([
+                destroy_layer_surface(id),
+                respond_opened(portal, warning.args, warning.result),
+            ]),
+            None => cosmic::Command::none(),
+        },
+        Msg::WarningCancel => match portal.write_warnings.remove(&id) {
+            Some(warning) => cosmic::Command::batch([
+                destroy_layer_surface(id),
+                send_response(warning.args, PortalResponse::Cancelled),
+            ]),
+            None => cosmic::Command::none(),
+        },
+        Msg::OpenCopy(open_copy) => {
+            if let Some((args, _)) = portal.file_choosers.get_mut(&id) {
+                args.open_copy = open_copy;
+            }
+            cosmic::Command::none()
+        }
+        Msg::CopyTick => match portal.file_copies.get_mut(&id) {
+            Some(copy) if !copy.progress.is_finished() => {
+                if copy.shown {
+                    return copy_tick(id);
+                }
+                copy.shown = true;
+                cosmic::Command::batch([
+                    get_layer_surface(crate::surfaces::centered_dialog(id, "file-chooser-copy")),
+                    copy_tick(id),
+                ])
+            }
+            _ => cosmic::Command::none(),
+        },
+        Msg::CopyCancel => {
+            if let Some(copy) = portal.file_copies.get(&id) {
+                copy.progress.cancel();
+            }
+            cosmic::Command::none()
+        }
+        Msg::Copied(copied) => {
+            let Some(copy) = portal.file_copies.remove(&id) else {
+                log::warn!("no file copy with ID {id:?}");
+                return cosmic::Command::none();
+            };
+            let response = match copied {
+                Ok(copies) => {
+                    let uris: Option<Vec<_>> = copies
+                        .iter()
+                        .map(|x| url::Url::from_file_path(x).ok().map(|x| x.to_string()))
+                        .collect();
+                    match uris {
+                        Some(uris) => PortalResponse::Success(FileChooserResult {
+                            uris,
+                            ..copy.result
+                        }),
+                        None => {
+                            log::error!("failed to convert to URL: {:?}", copies);
+                            PortalResponse::Other
+                        }
+                    }
+                }
+                Err(_) if copy.progress.is_cancelled() => PortalResponse::Cancelled,
+                Err(err) => {
+                    log::error!("failed to copy files to open: {}", err);
+                    PortalResponse::Error(err)
+                }
+            };
+            let response = send_response(copy.args, response);
+            if copy.shown {
+                cosmic::Command::batch([destroy_layer_surface(id), response])
+            } else {
+                response
+            }
+        }
         Msg::DialogResult(dialog_res) => match portal.file_choosers.remove(&id) {
             Some((args, dialog)) => {
                 log::debug!("file chooser result {:?}", dialog_res);
+                SavedState::clear(args.handle.as_str());
                 let response = match dialog_res {
                     DialogResult::Cancel => PortalResponse::Cancelled,
                     DialogResult::Open(paths) => {
                         let mut uris = Vec::with_capacity(paths.len());
-                        for path in paths {
-                            match url::Url::from_file_path(&path) {
+                        for path in &paths {
+                            match url::Url::from_file_path(path) {
                                 Ok(url) => uris.push(url.to_string()),
                                 Err(()) => {
                                     log::error!("failed to convert to URL: {:?}", path);
@@ -294,6 +1106,7 @@ pub fn update_msg(
                             }
                         }
 
+                        add_recent_locations(&paths);
                         if uris.is_empty() {
                             // Return error if URIs is empty, likely as a result of failing to convert paths
                             PortalResponse::Other
@@ -323,36 +1136,28 @@ pub fn update_msg(
                                 }
                             }
 
-                            let (filters, filter_selected) = dialog.filters();
-                            let mut current_filter = None;
-                            if let Some(filter_i) = filter_selected {
-                                if let Some(filter) = filters.get(filter_i) {
-                                    let mut patterns = Vec::with_capacity(filter.patterns.len());
-                                    for pattern in filter.patterns.iter() {
-                                        patterns.push(match pattern {
-                                            DialogFilterPattern::Glob(glob) => (0u32, glob.clone()),
-                                            DialogFilterPattern::Mime(mime) => (1u32, mime.clone()),
-                                        });
-                                    }
-                                    current_filter = Some((filter.label.clone(), patterns));
-                                }
-                            }
+                            // The filter as offered, not as narrowed to a category
+                            let current_filter = dialog
+                                .filters()
+                                .1
+                                .and_then(|filter_i| args.filters.get(filter_i))
+                                .cloned();
 
-                            PortalResponse::Success(FileChooserResult {
+                            let result = FileChooserResult {
                                 uris,
                                 choices,
                                 current_filter,
-                            })
+                            };
+                            match browse_archive(portal, args, &paths, result) {
+                                Ok(cmd) => return cmd,
+                                Err((args, result)) => {
+                                    return check_being_written(portal, args, paths, result)
+                                }
+                            }
                         }
                     }
                 };
-                cosmic::Command::perform(
-                    async move {
-                        let _ = args.tx.send(response).await;
-                        cosmic::app::message::none()
-                    },
-                    |x| x,
-                )
+                send_response(args, response)
             }
             None => {
                 log::warn!("no file chooser dialog with ID {id:?}");
@@ -362,9 +1167,22 @@ pub fn update_msg(
     }
 }
 
-pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<app::Message<AppMsg>> {
+pub fn update_args(
+    portal: &mut CosmicPortal,
+    mut args: Args,
+) -> cosmic::Command<app::Message<AppMsg>> {
     let mut cmds = Vec::with_capacity(2);
 
+    let live_handles: Vec<String> = portal
+        .file_choosers
+        .values()
+        .map(|(x, _)| x.handle.to_string())
+        .collect();
+    let restored = SavedState::take(&args, &live_handles);
+    if let Some(restored) = &restored {
+        log::info!("restoring file chooser state {:?}", restored);
+    }
+
     let kind = match &args.options {
         FileChooserOptions::OpenFile(options) => {
             if options.directory.unwrap_or(false) {
@@ -389,7 +1207,15 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<app
             DialogKind::OpenFolder
         }
     };
-    let path_opt = args.options.current_folder();
+    // Apps not sandboxed can open the originals anyway. Those are told by having no app
+    // ID, though the frontend may give an ID to some apps running on the host.
+    args.offer_copy = matches!(kind, DialogKind::OpenFile | DialogKind::OpenMultipleFiles)
+        && file_copy::copies_dir(&args.app_id).is_some();
+    // TODO: Offer recent locations in the breadcrumb bar, once cosmic-files has a slot
+    // for it there and a way to navigate an open dialog
+    // TODO: Sizes, times and date groups in the dialog are formatted by cosmic-files,
+    // which has no way to be told the unit system, clock or first day of the week
+    let path_opt = args.options.current_folder().or_else(recent_location);
 
     let (mut dialog, command) = Dialog::new(
         kind,
@@ -429,7 +1255,7 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<app
     }
     {
         let mut xdg_filters = args.options.filters().unwrap_or_default();
-        let filter_selected = match args.options.current_filter() {
+        let mut filter_selected = match args.options.current_filter() {
             Some(current_filter) => match xdg_filters.iter().position(|x| *x == current_filter) {
                 Some(filter_i) => Some(filter_i),
                 None => {
@@ -446,24 +1272,129 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<app
                 }
             }
         };
-        let mut filters = Vec::with_capacity(xdg_filters.len());
-        for (label, xdg_patterns) in xdg_filters {
-            let mut patterns = Vec::with_capacity(xdg_patterns.len());
-            for (kind, value) in xdg_patterns {
-                patterns.push(match kind {
-                    0 => DialogFilterPattern::Glob(value),
-                    1 => DialogFilterPattern::Mime(value),
-                    _ => {
-                        log::warn!("unsupported filter pattern {:?}", (kind, value));
-                        continue;
-                    }
-                });
+        if let Some(filter_i) = restored.as_ref().and_then(|x| x.current_filter) {
+            if filter_i < xdg_filters.len() {
+                filter_selected = Some(filter_i);
             }
-            filters.push(DialogFilter { label, patterns });
         }
-        cmds.push(dialog.set_filters(filters, filter_selected));
+        args.show_categories =
+            Category::of_filters(xdg_filters.iter().map(|(_, x)| x.as_slice())).is_none();
+        // After the app's filters, so indices of those are unchanged
+        for pinned in &portal.config.file_chooser.pinned_filters {
+            if let Err(reason) = pinned.validate() {
+                log::warn!("skipping pinned filter {:?}: {reason}", pinned.label);
+                continue;
+            }
+            let patterns: Vec<_> = pinned.patterns.iter().map(|x| (0, x.clone())).collect();
+            if !xdg_filters.iter().any(|(_, x)| *x == patterns) {
+                xdg_filters.push((pinned.label.clone(), patterns));
+            }
+        }
+        cmds.push(dialog.set_filters(dialog_filters(&xdg_filters, None), filter_selected));
+        args.filters = xdg_filters;
     }
+    let mut state = SavedState::new(&args, &dialog);
+    state.save();
+    args.saved = Some(state);
+
     let id = dialog.window_id();
     portal.file_choosers.insert(id, (args, dialog));
     cosmic::iced::Command::batch(cmds).map(move |msg| map_msg(id, msg))
 }
+
+// Filters for the dialog from those of the portal, narrowed to `category`. Those matching
+// nothing of it are left as they are.
+fn dialog_filters(xdg_filters: &[Filter], category: Option<Category>) -> Vec<DialogFilter> {
+    let narrowed: Filters = match category {
+        Some(category) if xdg_filters.is_empty() => vec![(category.label(), category.patterns())],
+        Some(category) => xdg_filters
+            .iter()
+            .map(|(label, patterns)| {
+                let narrowed = category.intersect(patterns);
+                if narrowed.is_empty() {
+                    (label.clone(), patterns.clone())
+                } else {
+                    (label.clone(), narrowed)
+                }
+            })
+            .collect(),
+        None => xdg_filters.to_vec(),
+    };
+    let mut filters = Vec::with_capacity(narrowed.len());
+    for (label, xdg_patterns) in narrowed {
+        let mut patterns = Vec::with_capacity(xdg_patterns.len());
+        for (kind, value) in xdg_patterns {
+            patterns.push(match kind {
+                0 => DialogFilterPattern::Glob(value),
+                1 => DialogFilterPattern::Mime(value),
+                _ => {
+                    log::warn!("unsupported filter pattern {:?}", (kind, value));
+                    continue;
+                }
+            });
+        }
+        filters.push(DialogFilter { label, patterns });
+    }
+    filters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_round_trips() {
+        crate::assert_round_trips(FileChooserResult {
+            uris: vec!["file:///tmp/a.txt".to_string()],
+            choices: vec![("encoding".to_string(), "utf8".to_string())],
+            current_filter: Some(("Text".to_string(), vec![(1, "text/plain".to_string())])),
+        });
+    }
+
+    fn saved(handle: &str, app_id: &str, saved_at: u64) -> SavedState {
+        SavedState {
+            handle: handle.to_string(),
+            app_id: app_id.to_string(),
+            mode: "open-file".to_string(),
+            current_filter: Some(1),
+            saved_at,
+        }
+    }
+
+    #[test]
+    fn saved_state_is_taken_by_handle_first() {
+        let states = [saved("/a", "org.app", 1000), saved("/b", "org.app", 1000)];
+        // Re-delivered with the same handle, rather than another state of the same app
+        let position =
+            |handle| SavedState::position(&states, handle, "org.app", "open-file", &[], 1000);
+        assert_eq!(position("/b"), Some(1));
+        assert_eq!(position("/c"), Some(0));
+        // Whatever the app, mode or age
+        assert_eq!(
+            SavedState::position(&states, "/b", "", "", &[], u64::MAX),
+            Some(1)
+        );
+        assert_eq!(SavedState::position(&states, "/c", "", "", &[], 1000), None);
+    }
+
+    #[test]
+    fn saved_state_is_taken_by_app_within_the_window() {
+        let states = [saved("/a", "org.app", 1000)];
+        let window = RESTORE_WINDOW.as_secs();
+        let position = |app_id, mode, live: &[String], now| {
+            SavedState::position(&states, "/new", app_id, mode, live, now)
+        };
+        assert_eq!(
+            position("org.app", "open-file", &[], 1000 + window),
+            Some(0)
+        );
+        assert_eq!(position("org.app", "open-file", &[], 1001 + window), None);
+        assert_eq!(position("org.other", "open-file", &[], 1000), None);
+        assert_eq!(position("org.app", "save-file", &[], 1000), None);
+        // Not while the dialog it was saved for is still open
+        assert_eq!(
+            position("org.app", "open-file", &["/a".to_string()], 1000),
+            None
+        );
+    }
+}
@@ -0,0 +1,169 @@
+// Opening copies of chosen files instead of the originals, for sandboxed apps the user
+// doesn't want changing the originals. Copies go in a directory per app under the data
+// directory, and the frontend exports them to the app like any other file chosen.
+//
+// Copies are never removed by the portal, since the app may keep using them.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+// Size of chunks copied between checks for cancellation
+const CHUNK_SIZE: usize = 256 * 1024;
+// Highest number suffixed to a name taken already, like "notes (2).txt"
+const MAX_SUFFIX: u32 = 1000;
+
+/// Progress of copying files, shared with the dialog showing it
+#[derive(Debug, Default)]
+pub struct Progress {
+    // Bytes of all files copied, known once copying starts
+    total: AtomicU64,
+    copied: AtomicU64,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+}
+
+impl Progress {
+    /// Share copied so far, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.;
+        }
+        (self.copied.load(Ordering::Relaxed) as f64 / total as f64).min(1.) as f32
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+/// Directory copies opened by `app_id` go in, if it can name one
+pub fn copies_dir(app_id: &str) -> Option<PathBuf> {
+    if app_id.is_empty() || app_id.starts_with('.') || app_id.contains('/') {
+        return None;
+    }
+    Some(
+        dirs::data_dir()?
+            .join("xdg-desktop-portal-cosmic")
+            .join("copies")
+            .join(app_id),
+    )
+}
+
+/// Copy `paths` into `dir`, numbering names already taken there, and return the copies.
+///
+/// Fails with `ErrorKind::Interrupted` if cancelled. On failure no copies are left behind.
+pub fn copy_into(paths: &[PathBuf], dir: &Path, progress: &Progress) -> io::Result<Vec<PathBuf>> {
+    let result = copy_into_inner(paths, dir, progress);
+    progress.finished.store(true, Ordering::Relaxed);
+    result
+}
+
+fn copy_into_inner(paths: &[PathBuf], dir: &Path, progress: &Progress) -> io::Result<Vec<PathBuf>> {
+    let total = paths
+        .iter()
+        .filter_map(|x| fs::metadata(x).ok())
+        .map(|x| x.len())
+        .sum();
+    progress.total.store(total, Ordering::Relaxed);
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    let mut copies = Vec::with_capacity(paths.len());
+    for path in paths {
+        match copy_file(path, dir, progress) {
+            Ok(copy) => copies.push(copy),
+            Err(err) => {
+                for copy in &copies {
+                    let _ = fs::remove_file(copy);
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(copies)
+}
+
+fn copy_file(path: &Path, dir: &Path, progress: &Progress) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{path:?} has no file name"),
+        )
+    })?;
+    let mut src = File::open(path)?;
+    let (dest, mut out) = create_unique(dir, name)?;
+    let result = copy_chunks(&mut src, &mut out, progress);
+    if result.is_err() {
+        let _ = fs::remove_file(&dest);
+    }
+    result.map(|()| dest)
+}
+
+// Create a file named `name` in `dir`, or the first of "name (1)", "name (2)" and so on
+// not taken, keeping the extension last
+fn create_unique(dir: &Path, name: &OsStr) -> io::Result<(PathBuf, File)> {
+    let name = Path::new(name);
+    let stem = name.file_stem().unwrap_or(name.as_os_str());
+    for n in 0..=MAX_SUFFIX {
+        let candidate = if n == 0 {
+            name.as_os_str().to_owned()
+        } else {
+            let mut candidate = OsString::from(stem);
+            candidate.push(format!(" ({n})"));
+            if let Some(extension) = name.extension() {
+                candidate.push(".");
+                candidate.push(extension);
+            }
+            candidate
+        };
+        let path = dir.join(candidate);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{MAX_SUFFIX} copies of {name:?} exist already"),
+    ))
+}
+
+fn copy_chunks(src: &mut File, out: &mut File, progress: &Progress) -> io::Result<()> {
+    let mut buf = vec![0; CHUNK_SIZE];
+    loop {
+        if progress.is_cancelled() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let read = match src.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        out.write_all(&buf[..read])?;
+        progress.copied.fetch_add(read as u64, Ordering::Relaxed);
+    }
+    out.flush()
+}
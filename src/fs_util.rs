@@ -0,0 +1,223 @@
+// Atomic file writes, so a crash never leaves a partially written file behind.
+//
+// The new contents are written to an unnamed file in the target's directory
+// (`O_TMPFILE`), or a randomly named one where the file system doesn't support
+// that, then renamed over the target. A rename replaces a symlink at the target
+// rather than following it, so a symlink planted there can't redirect the write.
+
+use anyhow::Context;
+use rustix::fs::{AtFlags, Mode, OFlags, CWD};
+use std::{
+    fs::File,
+    io,
+    os::fd::{AsFd, AsRawFd},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Distinguishes temporary names of concurrent writes to the same path
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// Replace `path` with the contents `write` produces, creating it with `mode`.
+///
+/// Nothing is written to `path` if `write` fails.
+pub fn write_atomic<F>(path: &Path, mode: u32, write: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut File) -> anyhow::Result<()>,
+{
+    let resolved = resolve(path);
+    write_atomic_inner(path, mode, write)
+        .with_context(|| format!("failed to write {}", resolved.display()))
+}
+
+fn write_atomic_inner<F>(path: &Path, mode: u32, write: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut File) -> anyhow::Result<()>,
+{
+    let (dir, name) = split(path)?;
+    let dir_fd = rustix::fs::open(
+        dir,
+        OFlags::DIRECTORY | OFlags::RDONLY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+
+    let tmpfile = rustix::fs::openat(
+        &dir_fd,
+        ".",
+        OFlags::TMPFILE | OFlags::WRONLY | OFlags::CLOEXEC,
+        Mode::from_raw_mode(mode),
+    );
+    let fd = match tmpfile {
+        Ok(fd) => fd,
+        // Not supported by this file system
+        Err(rustix::io::Errno::OPNOTSUPP | rustix::io::Errno::ISDIR) => {
+            return write_named_temp(dir, path, mode, write);
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let mut file = File::from(fd);
+    write(&mut file)?;
+    file.sync_all()?;
+
+    // Give the file a unique name, then move it into place
+    let temp_name = format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    );
+    let proc_path = format!("/proc/self/fd/{}", file.as_raw_fd());
+    match rustix::fs::unlinkat(&dir_fd, temp_name.as_str(), AtFlags::empty()) {
+        Ok(()) | Err(rustix::io::Errno::NOENT) => {}
+        Err(err) => return Err(err.into()),
+    }
+    rustix::fs::linkat(
+        CWD,
+        proc_path.as_str(),
+        &dir_fd,
+        temp_name.as_str(),
+        AtFlags::SYMLINK_FOLLOW,
+    )?;
+    if let Err(err) = rustix::fs::renameat(&dir_fd, temp_name.as_str(), &dir_fd, name) {
+        let _ = rustix::fs::unlinkat(&dir_fd, temp_name.as_str(), AtFlags::empty());
+        return Err(err.into());
+    }
+    sync_dir(&dir_fd);
+    Ok(())
+}
+
+/// Fallback for file systems without `O_TMPFILE`
+fn write_named_temp<F>(dir: &Path, path: &Path, mode: u32, write: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut File) -> anyhow::Result<()>,
+{
+    use std::os::unix::fs::PermissionsExt;
+
+    // Created with `O_EXCL` and mode 0600, and removed if dropped before persisting
+    let mut temp = tempfile::Builder::new().prefix(".").tempfile_in(dir)?;
+    temp.as_file()
+        .set_permissions(std::fs::Permissions::from_mode(mode))?;
+    write(temp.as_file_mut())?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|err| err.error)?;
+    if let Ok(dir) = File::open(dir) {
+        sync_dir(&dir);
+    }
+    Ok(())
+}
+
+// Make the rename durable. Failure only risks losing it in a crash, so it isn't an error.
+fn sync_dir(dir: &impl AsFd) {
+    if let Err(err) = rustix::fs::fsync(dir) {
+        log::warn!("failed to sync directory: {err}");
+    }
+}
+
+fn split(path: &Path) -> io::Result<(&Path, &std::ffi::OsStr)> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Ok((dir, name))
+}
+
+/// `path` with its directory's symlinks resolved, for error messages
+fn resolve(path: &Path) -> PathBuf {
+    match (split(path), path.file_name()) {
+        (Ok((dir, _)), Some(name)) => dir
+            .canonicalize()
+            .map_or_else(|_| path.to_path_buf(), |dir| dir.join(name)),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Write, os::unix::fs::PermissionsExt};
+
+    fn write(contents: &'static str) -> impl FnOnce(&mut File) -> anyhow::Result<()> {
+        move |file| Ok(file.write_all(contents.as_bytes())?)
+    }
+
+    // Names in `dir`, to check no temporary file is left behind
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn creates_file_with_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.ron");
+        write_atomic(&path, 0o600, write("new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(names(dir.path()), ["state.ron"]);
+    }
+
+    #[test]
+    fn replaces_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.ron");
+        fs::write(&path, "old").unwrap();
+        write_atomic(&path, 0o600, write("new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(names(dir.path()), ["state.ron"]);
+    }
+
+    #[test]
+    fn failed_write_keeps_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.ron");
+        fs::write(&path, "old").unwrap();
+        let failed = write_atomic(&path, 0o600, |file| {
+            file.write_all(b"partial")?;
+            anyhow::bail!("out of space")
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert_eq!(names(dir.path()), ["state.ron"]);
+    }
+
+    #[test]
+    fn replaces_symlink_rather_than_following_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let target = outside.path().join("bashrc");
+        fs::write(&target, "old").unwrap();
+        let path = dir.path().join("Screenshot.png");
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        write_atomic(&path, 0o600, write("new")).unwrap();
+        assert!(fs::symlink_metadata(&path).unwrap().is_file());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+    }
+
+    #[test]
+    fn fallback_replaces_existing_file_and_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.ron");
+        fs::write(&path, "old").unwrap();
+        write_named_temp(dir.path(), &path, 0o600, write("new")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(names(dir.path()), ["state.ron"]);
+
+        let target = dir.path().join("target");
+        fs::write(&target, "old").unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        write_named_temp(dir.path(), &link, 0o600, write("new")).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_file());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+    }
+}
@@ -0,0 +1,27 @@
+// Starting programs from within the portal. They're children of the portal, so they must
+// not inherit any of its file descriptors, like the Wayland connection or PipeWire streams.
+
+use rustix::io::FdFlags;
+use std::{fs, os::fd::BorrowedFd};
+
+/// Mark every open descriptor close-on-exec, so children don't inherit those of libraries
+/// that didn't set it themselves
+pub fn set_cloexec_all() {
+    let Ok(entries) = fs::read_dir("/proc/self/fd") else {
+        log::warn!("failed to list open file descriptors");
+        return;
+    };
+    for entry in entries.flatten() {
+        let Some(fd) = entry.file_name().to_str().and_then(|x| x.parse().ok()) else {
+            continue;
+        };
+        if fd <= 2 {
+            continue;
+        }
+        // Descriptors closed since they were listed just fail
+        let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+        if let Ok(flags) = rustix::io::fcntl_getfd(fd) {
+            let _ = rustix::io::fcntl_setfd(fd, flags | FdFlags::CLOEXEC);
+        }
+    }
+}
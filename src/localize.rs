@@ -4,6 +4,7 @@ use i18n_embed::{
 };
 use once_cell::sync::Lazy;
 use rust_embed::RustEmbed;
+use std::{collections::HashSet, sync::Mutex};
 
 #[derive(RustEmbed)]
 #[folder = "i18n/"]
@@ -19,17 +20,65 @@ pub static LANGUAGE_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
     loader
 });
 
+// Only the fallback language, for messages the languages selected lack
+static FALLBACK_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
+    let loader: FluentLanguageLoader = fluent_language_loader!();
+
+    loader
+        .load_fallback_language(&Localizations)
+        .expect("Error while loading fallback language");
+
+    loader
+});
+
+// Messages found missing, so each is logged once
+static MISSING: Lazy<Mutex<HashSet<String>>> = Lazy::new(Default::default);
+
+/// Loader to look up `message_id` with: the one for the languages selected, or the
+/// fallback language's if none of them has it, so the message's ID is never shown
+pub fn loader_for(message_id: &str) -> &'static FluentLanguageLoader {
+    if LANGUAGE_LOADER.has(message_id) {
+        return &LANGUAGE_LOADER;
+    }
+    if MISSING.lock().unwrap().insert(message_id.to_string()) {
+        log::warn!(
+            "no {} localization of {message_id:?}, using {}",
+            LANGUAGE_LOADER.current_language(),
+            FALLBACK_LOADER.current_language()
+        );
+    }
+    &FALLBACK_LOADER
+}
+
 #[macro_export]
 macro_rules! fl {
     ($message_id:literal) => {{
-        i18n_embed_fl::fl!($crate::localize::LANGUAGE_LOADER, $message_id)
+        i18n_embed_fl::fl!($crate::localize::loader_for($message_id), $message_id)
     }};
 
     ($message_id:literal, $($args:expr),*) => {{
-        i18n_embed_fl::fl!($crate::localize::LANGUAGE_LOADER, $message_id, $($args), *)
+        i18n_embed_fl::fl!($crate::localize::loader_for($message_id), $message_id, $($args), *)
     }};
 }
 
+/// `items` joined as a list in the language in use, like "a, b, and c"
+pub fn list(mut items: Vec<String>) -> String {
+    let Some(last) = items.pop() else {
+        return String::new();
+    };
+    let Some(second_last) = items.pop() else {
+        return last;
+    };
+    let end = if items.is_empty() {
+        fl!("list-pair", first = second_last, second = last)
+    } else {
+        fl!("list-end", first = second_last, second = last)
+    };
+    items.into_iter().rev().fold(end, |rest, item| {
+        fl!("list-middle", first = item, rest = rest)
+    })
+}
+
 // Get the `Localizer` to be used for localizing this library.
 pub fn localizer() -> Box<dyn Localizer> {
     Box::from(DefaultLocalizer::new(&*LANGUAGE_LOADER, &Localizations))
@@ -43,3 +92,18 @@ pub fn localize() {
         log::error!("Error while loading language for Cosmic Portal {}", error);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_messages_fall_back_to_english() {
+        // German has the dialog buttons, but not messages added since
+        i18n_embed::select(&*LANGUAGE_LOADER, &Localizations, &["de".parse().unwrap()]).unwrap();
+        assert!(std::ptr::eq(loader_for("cancel"), &*LANGUAGE_LOADER));
+        assert_eq!(fl!("cancel"), "Abbrechen");
+        assert!(std::ptr::eq(loader_for("open-copy"), &*FALLBACK_LOADER));
+        assert_eq!(fl!("open-copy"), "Open a copy");
+    }
+}
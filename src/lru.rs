@@ -0,0 +1,116 @@
+// Bounded cache for per-app data, so memory use doesn't grow with the number of
+// distinct app IDs seen.
+
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+/// Capacity of per-app caches
+pub const APP_CACHE_CAPACITY: usize = 256;
+
+/// Map holding at most `capacity` entries, evicting the least recently used
+pub struct LruCache<K, V> {
+    capacity: usize,
+    entries: HashMap<K, (V, u64)>,
+    // Incremented on each access, to order entries by use
+    tick: u64,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            tick: 0,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Get the value for `key`, marking it as recently used
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let tick = self.next_tick();
+        let (value, used) = self.entries.get_mut(key)?;
+        *used = tick;
+        Some(value)
+    }
+
+    pub fn contains<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Insert `value` for `key`, evicting the least recently used entry if full
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            // Linear, but caches are small and inserts are rare next to lookups
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        let tick = self.next_tick();
+        self.entries.insert(key, (value, tick));
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.entries.remove(key).map(|(value, _)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // Using `a` makes `b` the oldest
+        assert_eq!(cache.get("a"), Some(&1));
+        cache.insert("c", 3);
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("a"));
+        assert!(cache.contains("c"));
+    }
+
+    #[test]
+    fn replacing_doesnt_evict() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        cache.insert("b", 3);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.get("b"), Some(&3));
+        assert_eq!(cache.remove("b"), Some(3));
+        assert_eq!(cache.remove("b"), None);
+    }
+
+    #[test]
+    fn holds_at_least_one_entry() {
+        let mut cache = LruCache::new(0);
+        cache.insert("a", 1);
+        assert!(cache.contains("a"));
+        cache.insert("b", 2);
+        assert!(!cache.contains("a"));
+        assert!(cache.contains("b"));
+    }
+}
@@ -7,15 +7,40 @@ pub use cosmic_portal_config as config;
 
 mod access;
 mod app;
+mod app_chooser;
+mod archive;
+mod background_review;
 mod buffer;
+mod collate;
 mod documents;
+mod file_category;
 mod file_chooser;
+mod file_copy;
+mod fs_util;
+mod launch;
 mod localize;
+mod lru;
+mod management;
+mod metrics;
+mod notification;
+mod ocr;
+mod ordered_dict;
+mod partial_file;
+mod permission_store;
+mod preview;
+mod request_limit;
+mod responder;
 mod screencast;
 mod screencast_dialog;
 mod screencast_thread;
 mod screenshot;
+mod secret;
+mod shortcuts;
+mod state;
 mod subscription;
+mod surfaces;
+mod toast;
+mod usb;
 mod wayland;
 mod widget;
 
@@ -25,13 +50,19 @@ static DBUS_PATH: &str = "/org/freedesktop/portal/desktop";
 const PORTAL_RESPONSE_SUCCESS: u32 = 0;
 const PORTAL_RESPONSE_CANCELLED: u32 = 1;
 const PORTAL_RESPONSE_OTHER: u32 = 2;
+// Vendor key for the reason of a `PortalResponse::Error`
+const ERROR_KEY: &str = "com.system76.cosmic.error";
+// Error reason when no output is enabled, or none could be captured
+const NO_OUTPUTS: &str = "no outputs available to capture";
 
-#[derive(zvariant::Type)]
+#[derive(zvariant::Type, Clone, Debug, PartialEq)]
 #[zvariant(signature = "(ua{sv})")]
 enum PortalResponse<T: zvariant::Type + serde::Serialize> {
     Success(T),
     Cancelled,
     Other,
+    /// Failure with a reason for the caller, returned in the `com.system76.cosmic.error` key
+    Error(String),
 }
 
 impl<T: zvariant::Type + serde::Serialize> serde::Serialize for PortalResponse<T> {
@@ -48,10 +79,123 @@ impl<T: zvariant::Type + serde::Serialize> serde::Serialize for PortalResponse<T
                 HashMap::<String, zvariant::Value>::new(),
             )
                 .serialize(serializer),
+            Self::Error(reason) => (
+                PORTAL_RESPONSE_OTHER,
+                HashMap::from([(ERROR_KEY, zvariant::Value::from(reason.as_str()))]),
+            )
+                .serialize(serializer),
         }
     }
 }
 
+/// Fail a capture with the `NO_OUTPUTS` reason if it has no `outputs` to capture, like
+/// when every output is disabled or asleep
+fn require_outputs<T, O>(outputs: &[O]) -> Result<(), PortalResponse<T>>
+where
+    T: zvariant::Type + serde::Serialize,
+{
+    if outputs.is_empty() {
+        Err(PortalResponse::Error(NO_OUTPUTS.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Serialize `response` as it is sent over D-Bus, and parse its code and results as a
+/// client would
+#[cfg(test)]
+fn parse_response<T>(
+    response: &PortalResponse<T>,
+) -> zvariant::Result<(u32, HashMap<String, OwnedValue>)>
+where
+    T: zvariant::Type + serde::Serialize,
+{
+    let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+    let encoded = zvariant::to_bytes(ctxt, response)?;
+    Ok(encoded.deserialize()?.0)
+}
+
+/// Serialize `response` as it is sent over D-Bus, and parse it back
+#[cfg(test)]
+fn round_trip<T>(response: &PortalResponse<T>) -> zvariant::Result<PortalResponse<T>>
+where
+    T: zvariant::Type + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+    let (code, results) = parse_response(response)?;
+    Ok(match code {
+        PORTAL_RESPONSE_SUCCESS => {
+            let encoded = zvariant::to_bytes(ctxt, &results)?;
+            PortalResponse::Success(encoded.deserialize()?.0)
+        }
+        PORTAL_RESPONSE_CANCELLED => PortalResponse::Cancelled,
+        _ => match results.get(ERROR_KEY) {
+            Some(reason) => PortalResponse::Error(<&str>::try_from(reason)?.to_string()),
+            None => PortalResponse::Other,
+        },
+    })
+}
+
+/// Assert that a successful response with `result` survives a D-Bus round trip unchanged.
+///
+/// Catches result types whose signature doesn't match how their fields serialize, before
+/// a client fails to parse them. Result types need `DeserializeDict`, `PartialEq` and
+/// `Debug` to use it.
+#[cfg(test)]
+fn assert_round_trips<T>(result: T)
+where
+    T: zvariant::Type
+        + serde::Serialize
+        + serde::de::DeserializeOwned
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let response = PortalResponse::Success(result);
+    assert_eq!(round_trip(&response).unwrap(), response);
+}
+
+/// Wait before sending a dialog's response, with the `slow-responses` feature.
+///
+/// Lets the spinner of frontends, and a `Request.Close` or timeout landing while an
+/// answer is on its way, be reproduced reliably.
+async fn response_delay() {
+    #[cfg(feature = "slow-responses")]
+    if let Some(ms) = std::env::var("COSMIC_PORTAL_RESPONSE_DELAY_MS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+    {
+        tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+    }
+}
+
+/// Emit `org.freedesktop.portal.Request::Response` at the request `handle`, with the
+/// same code and results as the method returns, for frontends that listen for the signal
+async fn emit_response<T>(
+    connection: &zbus::Connection,
+    handle: &zvariant::ObjectPath<'_>,
+    response: &PortalResponse<T>,
+) where
+    T: zvariant::Type + serde::Serialize,
+{
+    let result = async {
+        let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+        let encoded = zvariant::to_bytes(ctxt, response)?;
+        let (body, _) = encoded.deserialize::<(u32, HashMap<String, OwnedValue>)>()?;
+        connection
+            .emit_signal(
+                None::<zbus::names::BusName<'_>>,
+                handle,
+                "org.freedesktop.portal.Request",
+                "Response",
+                &body,
+            )
+            .await
+    };
+    if let Err(err) = result.await {
+        log::error!("Failed to emit response for {handle}: {err}");
+    }
+}
+
 struct Request(AbortHandle);
 
 #[zbus::interface(name = "org.freedesktop.impl.portal.Request")]
@@ -156,14 +300,24 @@ const COLOR_SCHEME_KEY: &str = "color-scheme";
 const ACCENT_COLOR_KEY: &str = "accent-color";
 const CONTRAST_KEY: &str = "contrast";
 
+/// Display scaling, for apps not using a toolkit that integrates with COSMIC
+const DISPLAY_NAMESPACE: &str = "com.system76.cosmic.display";
+/// Scale of the output at the origin of the global space, or the first output
+const SCALE_FACTOR_KEY: &str = "scale-factor";
+/// Text scale relative to `scale-factor`.
+///
+/// COSMIC scales text together with the display, so this is always 1.0.
+const TEXT_SCALING_FACTOR_KEY: &str = "text-scaling-factor";
+
 struct Settings {
     pub color_scheme: ColorScheme,
     pub contrast: Contrast,
     pub accent: Srgba<f64>,
+    pub scale_factor: f64,
 }
 
 impl Settings {
-    pub fn new() -> Self {
+    pub fn new(wayland_helper: &wayland::WaylandHelper) -> Self {
         let theme = cosmic::theme::system_preference();
         let cosmic = theme.cosmic();
         Self {
@@ -178,6 +332,7 @@ impl Settings {
                 ColorScheme::PreferLight
             },
             accent: cosmic.accent_color().into_format(),
+            scale_factor: wayland_helper.scale_factor(),
         }
     }
 }
@@ -197,10 +352,19 @@ impl Settings {
     ) -> HashMap<String, HashMap<String, OwnedValue>> {
         let mut map = HashMap::new();
         if namespaces.is_empty() {
-            namespaces = vec![APPEARANCE_NAMESPACE];
+            namespaces = vec![APPEARANCE_NAMESPACE, DISPLAY_NAMESPACE];
         }
         for ns in namespaces {
             let mut inner = HashMap::new();
+            if ns == DISPLAY_NAMESPACE {
+                inner.insert(
+                    SCALE_FACTOR_KEY.to_string(),
+                    OwnedValue::from(self.scale_factor),
+                );
+                inner.insert(TEXT_SCALING_FACTOR_KEY.to_string(), OwnedValue::from(1.0));
+                map.insert(DISPLAY_NAMESPACE.to_string(), inner);
+                continue;
+            }
             if ns != APPEARANCE_NAMESPACE {
                 map.insert(ns.to_string(), inner);
                 continue;
@@ -238,6 +402,8 @@ impl Settings {
                 blue: self.accent.blue,
             })
             .map_err(|e| zbus::fdo::Error::Failed(e.to_string())),
+            (DISPLAY_NAMESPACE, SCALE_FACTOR_KEY) => Ok(OwnedValue::from(self.scale_factor)),
+            (DISPLAY_NAMESPACE, TEXT_SCALING_FACTOR_KEY) => Ok(OwnedValue::from(1.0)),
             _ => Err(zbus::fdo::Error::Failed(
                 "Unknown namespace or key".to_string(),
             )),
@@ -265,5 +431,106 @@ impl Settings {
 async fn main() -> cosmic::iced::Result {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     localize::localize();
-    app::run()
+    if let Some(result) = metrics::stats_command(std::env::args().skip(1)) {
+        if let Err(err) = result {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+        std::process::exit(0);
+    }
+    let preview = match preview::Preview::from_args(std::env::args().skip(1)) {
+        Ok(preview) => preview,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    app::run(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::abortable;
+    use std::time::Duration;
+
+    use crate::responder::Responder;
+
+    type Results = HashMap<String, OwnedValue>;
+
+    #[test]
+    fn responses_without_results_round_trip() {
+        for response in [
+            PortalResponse::<Results>::Cancelled,
+            PortalResponse::Other,
+            PortalResponse::Error("no output".to_string()),
+        ] {
+            assert_eq!(round_trip(&response).unwrap(), response);
+        }
+    }
+
+    #[test]
+    fn captures_need_outputs() {
+        assert_eq!(require_outputs::<Results, _>(&[0]), Ok(()));
+        let response = require_outputs::<Results, u32>(&[]).unwrap_err();
+        let (code, results) = parse_response(&response).unwrap();
+        assert_eq!(code, PORTAL_RESPONSE_OTHER);
+        let reason = results.get(ERROR_KEY).map(|x| <&str>::try_from(x).unwrap());
+        assert_eq!(reason, Some(NO_OUTPUTS));
+    }
+
+    #[test]
+    fn responses_have_their_codes() {
+        let code = |response| parse_response::<Results>(&response).unwrap().0;
+        assert_eq!(
+            code(PortalResponse::Success(Results::new())),
+            PORTAL_RESPONSE_SUCCESS
+        );
+        assert_eq!(code(PortalResponse::Cancelled), PORTAL_RESPONSE_CANCELLED);
+        assert_eq!(code(PortalResponse::Other), PORTAL_RESPONSE_OTHER);
+        assert_eq!(
+            code(PortalResponse::Error(String::new())),
+            PORTAL_RESPONSE_OTHER
+        );
+    }
+
+    #[tokio::test]
+    async fn close_during_delayed_grant_cancels_once() {
+        let (tx, mut rx) = Responder::channel();
+        let timeout = tx.clone();
+        let (waiting, close) = abortable(async move { rx.recv().await });
+        let delay = tokio::time::sleep(Duration::from_millis(20));
+        assert!(tx.send_after(PortalResponse::Success(Results::new()), delay));
+        // `Request.Close` lands while the grant is on its way, and the method answers
+        // Cancelled
+        close.abort();
+        assert!(waiting.await.is_err());
+        // Nor does an unattended timeout answer again
+        assert!(!timeout.send_after(PortalResponse::Cancelled, async {}));
+    }
+
+    #[tokio::test]
+    async fn delayed_grant_beats_later_close() {
+        let (tx, mut rx) = Responder::channel();
+        let (waiting, close) = abortable(async move { rx.recv().await });
+        let delay = tokio::time::sleep(Duration::from_millis(20));
+        assert!(tx.send_after(PortalResponse::Success(Results::new()), delay));
+        let response = waiting.await;
+        close.abort();
+        assert_eq!(
+            response.unwrap(),
+            Some(PortalResponse::Success(Results::new()))
+        );
+    }
+
+    #[cfg(feature = "slow-responses")]
+    #[tokio::test]
+    async fn responses_wait_for_configured_delay() {
+        std::env::set_var("COSMIC_PORTAL_RESPONSE_DELAY_MS", "50");
+        let (tx, mut rx) = Responder::channel();
+        let sent = std::time::Instant::now();
+        assert!(tx.send_after(PortalResponse::<Results>::Other, response_delay()));
+        assert_eq!(rx.recv().await, Some(PortalResponse::Other));
+        assert!(sent.elapsed() >= Duration::from_millis(50));
+    }
 }
@@ -0,0 +1,143 @@
+// Reloading the config on request, over D-Bus or with SIGHUP, for when the change
+// notifications of cosmic-config were missed, like for a file replaced under it.
+//
+// A reload only swaps the config the portal reads settings from. Sessions, open dialogs
+// and the interfaces served are left alone, so nothing in progress is dropped.
+
+use cosmic::cosmic_config::{self, CosmicConfigEntry};
+use std::time::SystemTime;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::Sender;
+
+use crate::{config, subscription, DBUS_PATH};
+
+pub struct Management {
+    tx: Sender<subscription::Event>,
+    // Config as last loaded, to tell what a reload changed
+    config: config::Config,
+    // Seconds since the epoch of the last successful reload, or 0
+    last_reload: u64,
+}
+
+impl Management {
+    pub fn new(tx: Sender<subscription::Event>) -> Self {
+        Self {
+            tx,
+            config: config::Config::load().0,
+            last_reload: 0,
+        }
+    }
+
+    async fn reload_inner(&mut self) -> Result<Vec<String>, String> {
+        let config = read_config()?;
+        let changed = changed_sections(&self.config, &config);
+        if let Err(err) = self
+            .tx
+            .send(subscription::Event::Config(config.clone()))
+            .await
+        {
+            return Err(format!("failed to apply config: {err}"));
+        }
+        self.config = config;
+        self.last_reload = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs());
+        log::info!("reloaded config, changing {changed:?}");
+        Ok(changed)
+    }
+}
+
+#[zbus::interface(name = "com.system76.CosmicPortal.Management")]
+impl Management {
+    /// Read the config again, and apply it. Returns the sections, like `access`, that
+    /// changed since the portal started or was last reloaded.
+    ///
+    /// Every setting applies without a restart, since none changes which interfaces are
+    /// served. A config that fails to parse is left unapplied.
+    async fn reload(
+        &mut self,
+        #[zbus(signal_context)] signal_ctxt: zbus::SignalContext<'_>,
+    ) -> zbus::fdo::Result<Vec<String>> {
+        let changed = self.reload_inner().await.map_err(|err| {
+            log::error!("failed to reload config: {err}");
+            zbus::fdo::Error::Failed(err)
+        })?;
+        self.last_reload_changed(&signal_ctxt).await?;
+        Ok(changed)
+    }
+
+    /// When the config was last reloaded, in seconds since the epoch, or 0 if it wasn't
+    #[zbus(property)]
+    async fn last_reload(&self) -> u64 {
+        self.last_reload
+    }
+}
+
+/// Reload the config each time the process gets SIGHUP
+pub async fn reload_on_hangup(connection: zbus::Connection) {
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err) => {
+            log::error!("failed to handle SIGHUP: {err}");
+            return;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        let iface_ref = match connection
+            .object_server()
+            .interface::<_, Management>(DBUS_PATH)
+            .await
+        {
+            Ok(iface_ref) => iface_ref,
+            Err(err) => {
+                log::error!("failed to reload config: {err}");
+                continue;
+            }
+        };
+        let mut iface = iface_ref.get_mut().await;
+        match iface.reload_inner().await {
+            Ok(_) => {
+                if let Err(err) = iface.last_reload_changed(iface_ref.signal_context()).await {
+                    log::error!("failed to signal config reload: {err}");
+                }
+            }
+            Err(err) => log::error!("failed to reload config: {err}"),
+        }
+    }
+}
+
+// The config as stored, or why it can't be used
+fn read_config() -> Result<config::Config, String> {
+    let handler = cosmic_config::Config::new(config::APP_ID, config::CONFIG_VERSION)
+        .map_err(|err| err.to_string())?;
+    config::Config::get_entry(&handler).map_err(|(errors, _)| {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    })
+}
+
+fn changed_sections(old: &config::Config, new: &config::Config) -> Vec<String> {
+    let sections = [
+        ("screenshot", old.screenshot != new.screenshot),
+        ("screencast", old.screencast != new.screencast),
+        ("access", old.access != new.access),
+        ("background", old.background != new.background),
+        ("shortcuts", old.shortcuts != new.shortcuts),
+        ("open_uri", old.open_uri != new.open_uri),
+        ("requests", old.requests != new.requests),
+        ("file_chooser", old.file_chooser != new.file_chooser),
+        ("metrics", old.metrics != new.metrics),
+        (
+            "instant_dialogs",
+            old.instant_dialogs != new.instant_dialogs,
+        ),
+    ];
+    sections
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
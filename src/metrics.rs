@@ -0,0 +1,197 @@
+// Counts of how the portal is used, for users deciding what to configure, with the
+// `metrics` config. Counts are kept in the state directory, read back with
+// `xdg-desktop-portal-cosmic --stats`, and never leave the machine. App IDs are only
+// recorded with `metrics.per_app`.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::RwLock, time::Duration};
+use tokio::sync::broadcast;
+
+use crate::{config, state, PortalResponse};
+
+// State directory key for the counts
+const STATE_KEY: &str = "metrics";
+// Kind of access counted for dialogs that don't say which they ask for
+const OTHER_ACCESS: &str = "other";
+
+const USAGE: &str = "usage: xdg-desktop-portal-cosmic --stats [reset]";
+
+// The `metrics` config, kept current as the config changes so counting doesn't read it
+// from disk
+static SETTINGS: Lazy<RwLock<config::metrics::Metrics>> = Lazy::new(Default::default);
+
+/// Apply `config` to counting from now on
+pub fn configure(config: &config::Config) {
+    *SETTINGS.write().unwrap() = config.metrics.clone();
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Metrics {
+    /// Requests by interface, like `Access`
+    requests: BTreeMap<String, u64>,
+    /// Answers of access dialogs by the kind of access, like `camera`
+    access: BTreeMap<String, Answers>,
+    screencast_secs: u64,
+    /// Counts by app ID, with `per_app`
+    apps: BTreeMap<String, AppMetrics>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Answers {
+    granted: u64,
+    denied: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct AppMetrics {
+    access: Answers,
+    screencast_secs: u64,
+}
+
+impl Answers {
+    fn count(&mut self, granted: bool) {
+        if granted {
+            self.granted += 1;
+        } else {
+            self.denied += 1;
+        }
+    }
+}
+
+// Change the counts with `f` off the async runtime, as it reads and writes the state file
+fn update(f: impl FnOnce(&mut Metrics) + Send + 'static) {
+    tokio::task::spawn_blocking(move || {
+        state::update(STATE_KEY, |metrics: &mut Metrics| {
+            f(metrics);
+            true
+        })
+    });
+}
+
+/// Count a request to `interface`, if enabled
+pub fn count_request(interface: &str) {
+    if !SETTINGS.read().unwrap().enabled {
+        return;
+    }
+    let interface = interface.to_string();
+    update(move |metrics| *metrics.requests.entry(interface).or_default() += 1);
+}
+
+/// Count screen sharing by `app_id` that lasted `duration`, if enabled
+pub fn count_screencast(app_id: &str, duration: Duration) {
+    let settings = SETTINGS.read().unwrap().clone();
+    if !settings.enabled {
+        return;
+    }
+    let secs = duration.as_secs();
+    let app_id = app_id.to_string();
+    update(move |metrics| {
+        metrics.screencast_secs += secs;
+        if settings.per_app && !app_id.is_empty() {
+            metrics.apps.entry(app_id).or_default().screencast_secs += secs;
+        }
+    });
+}
+
+/// Count the answers of access dialogs, if enabled when they're answered
+pub(crate) async fn count_resolutions() {
+    let mut resolutions = crate::access::subscribe_resolutions();
+    loop {
+        let resolution = match resolutions.recv().await {
+            Ok(resolution) => resolution,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                log::warn!("missed counting {missed} access resolutions");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let granted = match resolution.response {
+            PortalResponse::Success(_) => true,
+            PortalResponse::Cancelled => false,
+            // Neither the user's answer
+            PortalResponse::Other | PortalResponse::Error(_) => continue,
+        };
+        let settings = SETTINGS.read().unwrap().clone();
+        if !settings.enabled {
+            continue;
+        }
+        let kind = resolution
+            .permission
+            .unwrap_or_else(|| OTHER_ACCESS.to_string());
+        let app_id = resolution.app_id;
+        update(move |metrics| {
+            metrics.access.entry(kind).or_default().count(granted);
+            if settings.per_app && !app_id.is_empty() {
+                metrics
+                    .apps
+                    .entry(app_id)
+                    .or_default()
+                    .access
+                    .count(granted);
+            }
+        });
+    }
+}
+
+/// Run `--stats`, printing the counts, or `--stats reset`, clearing them. `None` if `args`,
+/// without the program name, aren't either.
+pub fn stats_command(mut args: impl Iterator<Item = String>) -> Option<Result<(), String>> {
+    if args.next().as_deref() != Some("--stats") {
+        return None;
+    }
+    Some(match (args.next().as_deref(), args.next()) {
+        (None, _) => {
+            print(&state::load(STATE_KEY));
+            Ok(())
+        }
+        (Some("reset"), None) => {
+            state::save(STATE_KEY, &Metrics::default());
+            println!("Usage counts cleared");
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    })
+}
+
+fn print(metrics: &Metrics) {
+    // Counting may be off, which only the running portal knows
+    if metrics.requests.is_empty() {
+        println!("Nothing counted. Set metrics.enabled in the portal's config to count.\n");
+    }
+    println!("Requests:");
+    for (interface, count) in &metrics.requests {
+        println!("  {interface}: {count}");
+    }
+    println!("Access answers:");
+    for (kind, answers) in &metrics.access {
+        println!("  {kind}: {}", answers_summary(answers));
+    }
+    println!("Screen sharing: {} minutes", metrics.screencast_secs / 60);
+    if !metrics.apps.is_empty() {
+        println!("Apps:");
+    }
+    for (app_id, app) in &metrics.apps {
+        println!(
+            "  {app_id}: {}, {} minutes of screen sharing",
+            answers_summary(&app.access),
+            app.screencast_secs / 60
+        );
+    }
+}
+
+fn answers_summary(answers: &Answers) -> String {
+    let total = answers.granted + answers.denied;
+    let percent = if total == 0 {
+        0
+    } else {
+        answers.granted * 100 / total
+    };
+    format!(
+        "{} granted, {} denied ({percent}% granted)",
+        answers.granted, answers.denied
+    )
+}
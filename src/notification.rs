@@ -0,0 +1,504 @@
+// Desktop notifications, for feedback once the portal's own surfaces are gone, and the
+// Notification portal, which forwards apps' notifications to the notification daemon.
+//
+// Images apps pass as bytes or a file descriptor are decoded here, since the daemon
+// only takes a raw RGBA `image-data` hint or an icon name. Decoded images are cached by
+// the hash of their contents, as chat apps send the same avatars over and over.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    io::{self, Cursor, Read, Seek},
+    sync::{Arc, Mutex},
+};
+use zbus::zvariant;
+
+use crate::lru::LruCache;
+use crate::DBUS_PATH;
+
+// Largest encoded image read from an app
+const MAX_IMAGE_BYTES: u64 = 8 * 1024 * 1024;
+// Largest width or height of an image decoded
+const MAX_IMAGE_DIMENSION: u32 = 4096;
+// Images are downscaled to fit this before they are sent to the daemon
+const THUMBNAIL_SIZE: u32 = 256;
+// Decoded images kept, including images that failed to decode
+const IMAGE_CACHE_CAPACITY: usize = 64;
+// Action key the daemon sends when the notification itself is clicked
+const DEFAULT_ACTION_KEY: &str = "default";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+pub trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, zvariant::Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    fn close_notification(&self, id: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Show a transient notification, logging any failure
+pub async fn notify(summary: &str, body: &str) {
+    let result = async {
+        let connection = zbus::Connection::session().await?;
+        NotificationsProxy::new(&connection)
+            .await?
+            .notify(
+                crate::config::APP_ID,
+                0,
+                "",
+                summary,
+                body,
+                &[],
+                HashMap::from([("transient", zvariant::Value::from(true))]),
+                -1,
+            )
+            .await
+    };
+    if let Err(err) = result.await {
+        log::error!("Failed to show notification {summary:?}: {err}");
+    }
+}
+
+/// Show a notification that stays until dismissed, with `actions` as (key, label) pairs.
+///
+/// Returns the notification's ID, to match with `ActionInvoked` signals.
+pub async fn notify_with_actions(
+    connection: &zbus::Connection,
+    summary: &str,
+    body: &str,
+    actions: &[(String, String)],
+) -> zbus::Result<u32> {
+    let actions: Vec<&str> = actions
+        .iter()
+        .flat_map(|(key, label)| [key.as_str(), label.as_str()])
+        .collect();
+    NotificationsProxy::new(connection)
+        .await?
+        .notify(
+            crate::config::APP_ID,
+            0,
+            "",
+            summary,
+            body,
+            &actions,
+            HashMap::from([("resident", zvariant::Value::from(true))]),
+            0,
+        )
+        .await
+}
+
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug)]
+#[zvariant(signature = "a{sv}")]
+pub struct NotificationOptions {
+    title: Option<String>,
+    body: Option<String>,
+    #[zvariant(rename = "markup-body")]
+    markup_body: Option<String>,
+    icon: Option<zvariant::OwnedValue>,
+    priority: Option<String>,
+    #[zvariant(rename = "default-action")]
+    default_action: Option<String>,
+    #[zvariant(rename = "default-action-target")]
+    default_action_target: Option<zvariant::OwnedValue>,
+    buttons: Option<Vec<Button>>,
+}
+
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug)]
+#[zvariant(signature = "a{sv}")]
+struct Button {
+    label: Option<String>,
+    action: Option<String>,
+    target: Option<zvariant::OwnedValue>,
+}
+
+/// Image in the layout of the daemon's `image-data` hint
+#[derive(Debug)]
+struct RawImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl RawImage {
+    fn hint(&self) -> zvariant::Value<'static> {
+        let rowstride = self.width * 4;
+        // (iiibiiay): width, height, rowstride, alpha, bits per sample, channels, data
+        zvariant::Value::Structure(zvariant::Structure::from((
+            self.width as i32,
+            self.height as i32,
+            rowstride as i32,
+            true,
+            8i32,
+            4i32,
+            self.data.clone(),
+        )))
+    }
+}
+
+// Icon of a notification, as serialized by `GIcon`
+enum Icon {
+    Themed(Vec<String>),
+    Bytes(Vec<u8>),
+}
+
+// A notification sent to the daemon
+struct Posted {
+    app_id: String,
+    id: String,
+    // App's action and its target for each action key given to the daemon
+    actions: HashMap<String, (String, Option<zvariant::OwnedValue>)>,
+}
+
+#[derive(Default)]
+struct Posts {
+    // Daemon's ID of each app's notification
+    ids: HashMap<(String, String), u32>,
+    by_daemon_id: HashMap<u32, Posted>,
+}
+
+pub struct Notification {
+    posts: Arc<Mutex<Posts>>,
+    images: Mutex<LruCache<u64, Option<Arc<RawImage>>>>,
+    listening: tokio::sync::OnceCell<()>,
+}
+
+impl Notification {
+    pub fn new() -> Self {
+        Self {
+            posts: Arc::new(Mutex::new(Posts::default())),
+            images: Mutex::new(LruCache::new(IMAGE_CACHE_CAPACITY)),
+            listening: tokio::sync::OnceCell::new(),
+        }
+    }
+
+    // Decoded `bytes`, from the cache if they were seen before
+    async fn image(&self, bytes: Vec<u8>) -> Option<Arc<RawImage>> {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        let hash = hasher.finish();
+        if let Some(image) = self.images.lock().unwrap().get(&hash) {
+            return image.clone();
+        }
+        let image = match tokio::task::spawn_blocking(move || decode_image(&bytes)).await {
+            Ok(Ok(image)) => Some(Arc::new(image)),
+            Ok(Err(err)) => {
+                log::warn!("notification image not shown: {err}");
+                None
+            }
+            Err(err) => {
+                log::error!("failed to decode notification image: {err}");
+                None
+            }
+        };
+        self.images.lock().unwrap().insert(hash, image.clone());
+        image
+    }
+
+    // Forward the daemon's signals for our notifications, once
+    async fn listen(&self, connection: &zbus::Connection) {
+        self.listening
+            .get_or_init(|| async {
+                let connection = connection.clone();
+                let posts = self.posts.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = forward_signals(&connection, &posts).await {
+                        log::error!("failed to forward notification signals: {err}");
+                    }
+                });
+            })
+            .await;
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.Notification")]
+impl Notification {
+    async fn add_notification(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        app_id: &str,
+        id: &str,
+        notification: NotificationOptions,
+    ) {
+        log::debug!("add notification {app_id} {id} {notification:?}");
+        self.listen(connection).await;
+
+        let mut app_icon = app_id.to_string();
+        let mut hints = HashMap::new();
+        match notification.icon.as_deref().map(parse_icon) {
+            Some(Ok(Icon::Themed(names))) => {
+                if let Some(name) = names.into_iter().next() {
+                    app_icon = name;
+                }
+            }
+            Some(Ok(Icon::Bytes(bytes))) => {
+                // Falls back to the app's icon if the image can't be shown
+                if let Some(image) = self.image(bytes).await {
+                    hints.insert("image-data", image.hint());
+                }
+            }
+            Some(Err(err)) => log::warn!("notification icon from {app_id:?} not shown: {err}"),
+            None => {}
+        }
+        let urgency: u8 = match notification.priority.as_deref() {
+            Some("low") => 0,
+            Some("urgent") => 2,
+            _ => 1,
+        };
+        hints.insert("urgency", zvariant::Value::from(urgency));
+        if !app_id.is_empty() {
+            hints.insert("desktop-entry", zvariant::Value::from(app_id.to_string()));
+        }
+
+        // (key, label) pairs for the daemon, and what each key stands for
+        let mut labels = Vec::new();
+        let mut actions = HashMap::new();
+        if let Some(action) = notification.default_action {
+            labels.push((DEFAULT_ACTION_KEY.to_string(), String::new()));
+            actions.insert(
+                DEFAULT_ACTION_KEY.to_string(),
+                (action, notification.default_action_target),
+            );
+        }
+        for (i, button) in notification.buttons.into_iter().flatten().enumerate() {
+            let (Some(label), Some(action)) = (button.label, button.action) else {
+                continue;
+            };
+            let key = format!("button-{i}");
+            labels.push((key.clone(), label));
+            actions.insert(key, (action, button.target));
+        }
+        let action_list: Vec<&str> = labels
+            .iter()
+            .flat_map(|(key, label)| [key.as_str(), label.as_str()])
+            .collect();
+
+        let key = (app_id.to_string(), id.to_string());
+        let replaces_id = self
+            .posts
+            .lock()
+            .unwrap()
+            .ids
+            .get(&key)
+            .copied()
+            .unwrap_or(0);
+        let body = notification
+            .markup_body
+            .or(notification.body)
+            .unwrap_or_default();
+        let result = async {
+            NotificationsProxy::new(connection)
+                .await?
+                .notify(
+                    app_id,
+                    replaces_id,
+                    &app_icon,
+                    notification.title.as_deref().unwrap_or_default(),
+                    &body,
+                    &action_list,
+                    hints,
+                    -1,
+                )
+                .await
+        };
+        match result.await {
+            Ok(daemon_id) => {
+                let mut posts = self.posts.lock().unwrap();
+                if replaces_id != 0 {
+                    posts.by_daemon_id.remove(&replaces_id);
+                }
+                posts.ids.insert(key, daemon_id);
+                posts.by_daemon_id.insert(
+                    daemon_id,
+                    Posted {
+                        app_id: app_id.to_string(),
+                        id: id.to_string(),
+                        actions,
+                    },
+                );
+            }
+            Err(err) => log::error!("Failed to show notification {id:?} of {app_id:?}: {err}"),
+        }
+    }
+
+    async fn remove_notification(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        app_id: &str,
+        id: &str,
+    ) {
+        let daemon_id = {
+            let mut posts = self.posts.lock().unwrap();
+            let daemon_id = posts.ids.remove(&(app_id.to_string(), id.to_string()));
+            if let Some(daemon_id) = daemon_id {
+                posts.by_daemon_id.remove(&daemon_id);
+            }
+            daemon_id
+        };
+        let Some(daemon_id) = daemon_id else {
+            return;
+        };
+        let result = async {
+            NotificationsProxy::new(connection)
+                .await?
+                .close_notification(daemon_id)
+                .await
+        };
+        if let Err(err) = result.await {
+            log::error!("Failed to remove notification {id:?} of {app_id:?}: {err}");
+        }
+    }
+
+    #[zbus(signal)]
+    async fn action_invoked(
+        ctxt: &zbus::SignalContext<'_>,
+        app_id: &str,
+        id: &str,
+        action: &str,
+        parameter: Vec<zvariant::OwnedValue>,
+    ) -> zbus::Result<()>;
+
+    #[zbus(property, name = "SupportedOptions")]
+    async fn supported_options(&self) -> HashMap<String, zvariant::OwnedValue> {
+        HashMap::new()
+    }
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        2
+    }
+}
+
+// Send `ActionInvoked` for actions of our notifications, and forget closed ones
+async fn forward_signals(connection: &zbus::Connection, posts: &Mutex<Posts>) -> zbus::Result<()> {
+    use futures::StreamExt;
+
+    let proxy = NotificationsProxy::new(connection).await?;
+    let mut invoked = proxy.receive_action_invoked().await?;
+    let mut closed = proxy.receive_notification_closed().await?;
+    let ctxt = zbus::SignalContext::new(connection, DBUS_PATH)?;
+    loop {
+        tokio::select! {
+            Some(signal) = invoked.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let (app_id, id, action, parameter) = {
+                    let posts = posts.lock().unwrap();
+                    let Some(posted) = posts.by_daemon_id.get(args.id()) else {
+                        continue;
+                    };
+                    let Some((action, target)) = posted.actions.get(args.action_key()) else {
+                        continue;
+                    };
+                    let parameter: Vec<_> =
+                        target.iter().filter_map(|x| x.try_clone().ok()).collect();
+                    (
+                        posted.app_id.clone(),
+                        posted.id.clone(),
+                        action.clone(),
+                        parameter,
+                    )
+                };
+                Notification::action_invoked(&ctxt, &app_id, &id, &action, parameter).await?;
+            }
+            Some(signal) = closed.next() => {
+                let Ok(args) = signal.args() else {
+                    continue;
+                };
+                let mut posts = posts.lock().unwrap();
+                if let Some(posted) = posts.by_daemon_id.remove(args.id()) {
+                    posts.ids.remove(&(posted.app_id, posted.id));
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
+
+// Read a serialized `GIcon` of the forms the portal frontend passes on
+fn parse_icon(value: &zvariant::Value<'_>) -> io::Result<Icon> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let zvariant::Value::Structure(icon) = value else {
+        return Err(invalid("icon is not a (sv) structure"));
+    };
+    let [zvariant::Value::Str(kind), zvariant::Value::Value(data)] = icon.fields() else {
+        return Err(invalid("icon is not a (sv) structure"));
+    };
+    match (kind.as_str(), &**data) {
+        ("themed", names) => {
+            let names = Vec::<String>::try_from(names.try_clone().map_err(io::Error::other)?)
+                .map_err(io::Error::other)?;
+            Ok(Icon::Themed(names))
+        }
+        ("bytes", bytes) => {
+            let bytes = Vec::<u8>::try_from(bytes.try_clone().map_err(io::Error::other)?)
+                .map_err(io::Error::other)?;
+            if bytes.len() as u64 > MAX_IMAGE_BYTES {
+                return Err(invalid("image is too large"));
+            }
+            Ok(Icon::Bytes(bytes))
+        }
+        ("file-descriptor", zvariant::Value::Fd(fd)) => {
+            use std::os::fd::AsFd;
+
+            let mut file = std::fs::File::from(fd.as_fd().try_clone_to_owned()?);
+            // Sealed memfds can be read from the start, other files from where they are
+            let _ = file.rewind();
+            let mut bytes = Vec::new();
+            file.take(MAX_IMAGE_BYTES + 1).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 > MAX_IMAGE_BYTES {
+                return Err(invalid("image is too large"));
+            }
+            Ok(Icon::Bytes(bytes))
+        }
+        (kind, _) => Err(invalid(&format!("unsupported icon kind {kind:?}"))),
+    }
+}
+
+// Decode a PNG or JPEG, downscaled to fit `THUMBNAIL_SIZE`
+fn decode_image(bytes: &[u8]) -> image::ImageResult<RawImage> {
+    let format = image::guess_format(bytes)?;
+    if !matches!(format, image::ImageFormat::Png | image::ImageFormat::Jpeg) {
+        return Err(image::ImageError::Unsupported(
+            image::error::UnsupportedError::from(image::error::ImageFormatHint::Exact(format)),
+        ));
+    }
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+    let mut reader = image::ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let image = reader.decode()?.into_rgba8();
+    let image = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        let scale = f64::from(THUMBNAIL_SIZE) / f64::from(image.width().max(image.height()));
+        let width = ((f64::from(image.width()) * scale).round() as u32).max(1);
+        let height = ((f64::from(image.height()) * scale).round() as u32).max(1);
+        image::imageops::thumbnail(&image, width, height)
+    } else {
+        image
+    };
+    Ok(RawImage {
+        width: image.width(),
+        height: image.height(),
+        data: image.into_raw(),
+    })
+}
@@ -0,0 +1,103 @@
+// Text recognition for interactive screenshots, using the `tesseract` command.
+//
+// Language packs are discovered at runtime. Without the `ocr` feature, or when
+// `tesseract` isn't installed, no languages are found and OCR is not offered.
+
+use image::RgbaImage;
+use std::process::Command;
+
+use crate::screenshot::Screenshot;
+
+// Tesseract language pack names for the languages the portal is translated to
+#[cfg(feature = "ocr")]
+const LANGUAGE_PACKS: &[(&str, &str)] = &[
+    ("de", "deu"),
+    ("en", "eng"),
+    ("es", "spa"),
+    ("hu", "hun"),
+    ("it", "ita"),
+    ("pl", "pol"),
+    ("pt", "por"),
+    ("ru", "rus"),
+    ("sr", "srp"),
+    ("sv", "swe"),
+    ("tr", "tur"),
+    ("uk", "ukr"),
+];
+
+/// Language packs to use for recognition, matching the desktop languages where possible.
+///
+/// Empty if OCR is unavailable.
+#[cfg(feature = "ocr")]
+pub fn languages() -> Vec<String> {
+    let output = match Command::new("tesseract").arg("--list-langs").output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            log::warn!("`tesseract --list-langs` failed: {}", output.status);
+            return Vec::new();
+        }
+        Err(err) => {
+            log::info!("OCR unavailable, failed to run `tesseract`: {}", err);
+            return Vec::new();
+        }
+    };
+    // First line is a header, listing the tessdata directory
+    let available: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .map(str::trim)
+        // Orientation and script detection, not a language
+        .filter(|lang| !lang.is_empty() && *lang != "osd")
+        .map(String::from)
+        .collect();
+
+    let mut languages = Vec::new();
+    for requested in i18n_embed::DesktopLanguageRequester::requested_languages() {
+        let Some((_, pack)) = LANGUAGE_PACKS
+            .iter()
+            .find(|(lang, _)| *lang == requested.language.as_str())
+        else {
+            continue;
+        };
+        if available.iter().any(|x| x == pack) && !languages.iter().any(|x| x == pack) {
+            languages.push(pack.to_string());
+        }
+    }
+    if languages.is_empty() {
+        if available.iter().any(|x| x == "eng") {
+            languages.push("eng".to_string());
+        } else if let Some(first) = available.into_iter().next() {
+            languages.push(first);
+        }
+    }
+    languages
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn languages() -> Vec<String> {
+    Vec::new()
+}
+
+/// Recognize text in `img`, blocking until `tesseract` exits.
+pub fn recognize(img: &RgbaImage, languages: &[String]) -> anyhow::Result<String> {
+    let file = tempfile::Builder::new()
+        .prefix("screenshot-ocr-")
+        .suffix(".png")
+        .tempfile()?;
+    Screenshot::save_rgba(img, &file.path().to_path_buf())?;
+
+    let output = Command::new("tesseract")
+        .arg(file.path())
+        .arg("stdout")
+        .arg("-l")
+        .arg(languages.join("+"))
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "tesseract failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,102 @@
+// A D-Bus dict that keeps its entries in the order they were added, for results whose
+// callers may rely on that order. A `HashMap` sends them in a different order each time.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::HashMap, fmt, marker::PhantomData};
+use zbus::zvariant;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderedDict<T>(Vec<(String, T)>);
+
+impl<T> OrderedDict<T> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Set `key` to `value`, keeping the position of a key already present
+    pub fn insert(&mut self, key: String, value: T) {
+        match self.0.iter_mut().find(|(x, _)| *x == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((key, value)),
+        }
+    }
+}
+
+impl<T> Default for OrderedDict<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: zvariant::Type> zvariant::Type for OrderedDict<T> {
+    fn signature() -> zvariant::Signature<'static> {
+        HashMap::<String, T>::signature()
+    }
+}
+
+impl<T: Serialize> Serialize for OrderedDict<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OrderedDict<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> de::Visitor<'de> for Visitor<T> {
+            type Value = OrderedDict<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a dict")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut dict = OrderedDict::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    dict.insert(key, value);
+                }
+                Ok(dict)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict<T>(entries: impl IntoIterator<Item = (&'static str, T)>) -> OrderedDict<T> {
+        let mut dict = OrderedDict::new();
+        for (key, value) in entries {
+            dict.insert(key.to_string(), value);
+        }
+        dict
+    }
+
+    #[test]
+    fn keeps_insertion_order() {
+        let mut keys = dict([("zoom", 1), ("audio", 2), ("mode", 3)]);
+        keys.insert("audio".to_string(), 4);
+        assert_eq!(
+            keys.0,
+            [("zoom", 1), ("audio", 4), ("mode", 3)].map(|(key, value)| (key.to_string(), value))
+        );
+    }
+
+    #[test]
+    fn order_survives_round_trip() {
+        let ctxt = zvariant::serialized::Context::new_dbus(zvariant::LE, 0);
+        // Enough entries that a hash map would be unlikely to keep their order
+        let original = OrderedDict((0..32).map(|i| (format!("choice-{}", 31 - i), i)).collect());
+        let encoded = zvariant::to_bytes(ctxt, &original).unwrap();
+        let (parsed, _) = encoded.deserialize::<OrderedDict<i64>>().unwrap();
+        assert_eq!(parsed, original);
+    }
+}
@@ -0,0 +1,70 @@
+// Telling whether a chosen file is still being written, like a download in progress or a
+// video still rendering, so apps aren't handed a truncated file without a warning.
+//
+// There is no reliable way to know, so it's guessed: the file changing between two stats a
+// moment apart, or a download's partial file next to it.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+// Time between the two stats of a file
+const STAT_INTERVAL: Duration = Duration::from_millis(300);
+// Files larger than this aren't checked. Disk images, databases and the like are written
+// to for as long as they're in use, so a warning about them would be noise.
+const MAX_CHECKED_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+// Suffixes browsers and download managers give files they're still downloading
+const PARTIAL_SUFFIXES: &[&str] = &[".part", ".crdownload", ".partial"];
+
+/// Those of `paths` that look like they're still being written.
+///
+/// Blocks for a moment if any file needs a second stat.
+pub fn being_written(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut written = Vec::new();
+    let mut changing = Vec::new();
+    for path in paths {
+        let Some(stat) = checked_stat(path) else {
+            continue;
+        };
+        if is_partial(path) || has_partial_sibling(path) {
+            written.push(path.clone());
+        } else {
+            changing.push((path, stat));
+        }
+    }
+    if changing.is_empty() {
+        return written;
+    }
+    // One pause for all of them
+    std::thread::sleep(STAT_INTERVAL);
+    for (path, stat) in changing {
+        if checked_stat(path) != Some(stat) {
+            written.push(path.clone());
+        }
+    }
+    written
+}
+
+// Size and modification time of a file that should be checked
+fn checked_stat(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_CHECKED_SIZE {
+        return None;
+    }
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+fn is_partial(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    PARTIAL_SUFFIXES.iter().any(|x| name.ends_with(x))
+}
+
+fn has_partial_sibling(path: &Path) -> bool {
+    PARTIAL_SUFFIXES.iter().any(|suffix| {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(suffix);
+        Path::new(&name).exists()
+    })
+}
@@ -0,0 +1,28 @@
+// Client for the permission store, where portals persist the user's decisions.
+
+use std::collections::HashMap;
+use zbus::zvariant;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.impl.portal.PermissionStore",
+    default_service = "org.freedesktop.impl.portal.PermissionStore",
+    default_path = "/org/freedesktop/impl/portal/PermissionStore"
+)]
+pub trait PermissionStore {
+    fn lookup(
+        &self,
+        table: &str,
+        id: &str,
+    ) -> zbus::Result<(HashMap<String, Vec<String>>, zvariant::OwnedValue)>;
+
+    fn get_permission(&self, table: &str, id: &str, app: &str) -> zbus::Result<Vec<String>>;
+
+    fn set_permission(
+        &self,
+        table: &str,
+        create: bool,
+        id: &str,
+        app: &str,
+        permissions: &[&str],
+    ) -> zbus::Result<()>;
+}
@@ -0,0 +1,141 @@
+// A sample access dialog shown by `xdg-desktop-portal-cosmic --preview-access`, without
+// serving the portal on D-Bus, for working on the dialog's look. The real dialog is shown,
+// with the config and theme in use, and its response is printed when it's answered.
+
+use std::sync::Arc;
+use std::time::Instant;
+use zbus::zvariant;
+
+use crate::access::{self, AccessDialogArgs, AccessDialogOptions};
+use crate::app::CosmicPortal;
+use crate::responder::Responder;
+
+/// Argument starting a preview, which must come first
+const ARG: &str = "--preview-access";
+
+const USAGE: &str = "usage: xdg-desktop-portal-cosmic --preview-access [--app-id ID] \
+    [--title TEXT] [--subtitle TEXT] [--body TEXT] [--details TEXT] [--icon NAME] \
+    [--permission KIND] [--grant-label TEXT] [--deny-label TEXT] [--placement EDGES] \
+    [--width PIXELS] [--modal] [--inline-choices] [--all-outputs] \
+    [--choice ID:LABEL[:OPTION,...]]...";
+
+const HANDLE: &str = "/org/freedesktop/portal/desktop/request/preview";
+
+/// The dialog to preview
+#[derive(Clone, Debug)]
+pub struct Preview {
+    app_id: String,
+    title: String,
+    subtitle: String,
+    body: String,
+    options: AccessDialogOptions,
+}
+
+impl Preview {
+    /// The preview asked for by `args`, without the program name, or `None` to serve the
+    /// portal
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        if args.next().as_deref() != Some(ARG) {
+            return Ok(None);
+        }
+        let mut preview = Self {
+            app_id: "com.system76.CosmicFiles".to_string(),
+            title: "Access Request".to_string(),
+            subtitle: String::new(),
+            body: "Files wants to access something. Allow it?".to_string(),
+            options: AccessDialogOptions::default(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--modal" => preview.options.modal = Some(true),
+                "--inline-choices" => preview.options.inline_choices = Some(true),
+                "--all-outputs" => preview.options.all_outputs = Some(true),
+                _ => {
+                    let Some(value) = args.next() else {
+                        return Err(format!("{arg} needs a value\n{USAGE}"));
+                    };
+                    preview.set(&arg, value)?;
+                }
+            }
+        }
+        Ok(Some(preview))
+    }
+
+    fn set(&mut self, arg: &str, value: String) -> Result<(), String> {
+        let options = &mut self.options;
+        match arg {
+            "--app-id" => self.app_id = value,
+            "--title" => self.title = value,
+            "--subtitle" => self.subtitle = value,
+            "--body" => self.body = value,
+            "--details" => options.details = Some(value),
+            "--icon" => options.icon = Some(value),
+            "--permission" => options.permission = Some(value),
+            "--grant-label" => options.grant_label = Some(value),
+            "--deny-label" => options.deny_label = Some(value),
+            "--placement" => options.placement = Some(value),
+            "--width" => {
+                let width = value
+                    .parse()
+                    .map_err(|_| format!("invalid width {value:?}\n{USAGE}"))?;
+                options.width = Some(width);
+            }
+            "--choice" => {
+                let choice = parse_choice(&value)
+                    .ok_or_else(|| format!("invalid choice {value:?}\n{USAGE}"))?;
+                options.choices.get_or_insert_with(Vec::new).push(choice);
+            }
+            _ => return Err(format!("unknown argument {arg}\n{USAGE}")),
+        }
+        Ok(())
+    }
+
+    /// Show the dialog, then print its response and exit once it's answered
+    pub fn show(self, portal: &mut CosmicPortal) -> cosmic::Command<crate::app::Msg> {
+        let (tx, mut rx) = Responder::channel();
+        let args = AccessDialogArgs {
+            handle: zvariant::ObjectPath::from_static_str_unchecked(HANDLE),
+            app_id: self.app_id,
+            parent_window: String::new(),
+            title: self.title,
+            subtitle: self.subtitle,
+            body: self.body,
+            body_from_app: true,
+            options: self.options,
+            raw_options: Arc::default(),
+            tx,
+            modal: false,
+            outputs: Vec::new(),
+            relocations: 0,
+            shown: false,
+            instant: false,
+            requested: Some(Instant::now()),
+            attached: Vec::new(),
+        };
+        let show = access::update_args(portal, args);
+        let answered = cosmic::iced::Command::perform(async move { rx.recv().await }, |response| {
+            println!("{response:?}");
+            std::process::exit(0)
+        });
+        cosmic::iced::Command::batch([show, answered])
+    }
+}
+
+// A boolean choice from `ID:LABEL`, or one of options from `ID:LABEL:OPTION,...`, with
+// each option's label as its ID
+fn parse_choice(value: &str) -> Option<(String, String, Vec<(String, String)>, String)> {
+    let mut parts = value.splitn(3, ':');
+    let id = parts.next().filter(|x| !x.is_empty())?.to_string();
+    let label = parts.next()?.to_string();
+    match parts.next() {
+        Some(options) => {
+            let options: Vec<_> = options
+                .split(',')
+                .map(|x| (x.to_string(), x.to_string()))
+                .collect();
+            let initial = options[0].0.clone();
+            Some((id, label, options, initial))
+        }
+        None => Some((id, label, Vec::new(), "false".to_string())),
+    }
+}
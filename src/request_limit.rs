@@ -0,0 +1,108 @@
+// Limits on how many requests of an interface wait for the user at once, from the
+// `requests` config. A request takes a slot before its dialog is queued, and frees
+// it when answered.
+//
+// This is where backpressure applies, rather than in the channel each request is answered
+// through: a `Responder` sends exactly one response, so that channel never holds more.
+//
+// Requests cancelled to make room are cancelled with `Event::CancelAccess`, so every
+// interface limited here must prompt through `access::prompt`, as Access, AppChooser and
+// Usb do.
+
+use once_cell::sync::Lazy;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+};
+use tokio::sync::{mpsc::Sender, Notify};
+use zbus::zvariant;
+
+use crate::config::{
+    self,
+    requests::{Overflow, Requests},
+};
+use crate::subscription;
+
+// The `requests` config, kept current as the config changes so requests don't read it
+// from disk
+static SETTINGS: Lazy<RwLock<Requests>> = Lazy::new(Default::default);
+
+/// Apply `config` to requests from now on
+pub fn configure(config: &config::Config) {
+    *SETTINGS.write().unwrap() = config.requests.clone();
+}
+
+pub struct RequestLimit {
+    interface: &'static str,
+    // Pending requests, oldest first
+    pending: Mutex<VecDeque<(u64, zvariant::ObjectPath<'static>)>>,
+    freed: Notify,
+    next_id: AtomicU64,
+}
+
+impl RequestLimit {
+    pub fn new(interface: &'static str) -> Self {
+        Self {
+            interface,
+            pending: Mutex::new(VecDeque::new()),
+            freed: Notify::new(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait for a slot for the request `handle`, held until the returned guard is dropped
+    pub async fn acquire(
+        &self,
+        handle: &zvariant::ObjectPath<'_>,
+        tx: &Sender<subscription::Event>,
+    ) -> Slot<'_> {
+        crate::metrics::count_request(self.interface);
+        let limit = SETTINGS.read().unwrap().limit(self.interface);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        loop {
+            // Registered before checking, so a slot freed in between isn't missed
+            let freed = self.freed.notified();
+            let oldest = {
+                let mut pending = self.pending.lock().unwrap();
+                if limit
+                    .capacity
+                    .map_or(true, |capacity| pending.len() < capacity.max(1))
+                {
+                    pending.push_back((id, handle.to_owned()));
+                    return Slot { limit: self, id };
+                }
+                pending.front().map(|(_, handle)| handle.clone())
+            };
+            if let (Overflow::CancelOldest, Some(oldest)) = (limit.overflow, oldest) {
+                log::info!(
+                    "{} requests are at capacity, cancelling {oldest}",
+                    self.interface
+                );
+                if let Err(err) = tx.send(subscription::Event::CancelAccess(oldest)).await {
+                    log::error!("Failed to send access dialog cancel event, {err}");
+                }
+            }
+            freed.await;
+        }
+    }
+}
+
+/// A request's place among the pending requests of its interface
+pub struct Slot<'a> {
+    limit: &'a RequestLimit,
+    id: u64,
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        self.limit
+            .pending
+            .lock()
+            .unwrap()
+            .retain(|(id, _)| *id != self.id);
+        self.limit.freed.notify_waiters();
+    }
+}
@@ -0,0 +1,137 @@
+// Where a dialog sends the response to the request that showed it. A request is answered
+// once: later sends, like a Close racing the user's choice, are dropped.
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Sends a dialog's response, at most once. Clones share the one response; dropping all of
+/// them unanswered closes the channel, which requests take as cancelled.
+pub struct Responder<T> {
+    tx: Arc<Mutex<Option<mpsc::Sender<T>>>>,
+}
+
+impl<T> Clone for Responder<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Responder<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Responder")
+            .field("sent", &self.sent())
+            .finish()
+    }
+}
+
+impl<T> Responder<T> {
+    /// A responder and the receiver its response arrives on
+    pub fn channel() -> (Self, mpsc::Receiver<T>) {
+        // One response per request, so the channel never needs more room
+        let (tx, rx) = mpsc::channel(1);
+        let responder = Self {
+            tx: Arc::new(Mutex::new(Some(tx))),
+        };
+        (responder, rx)
+    }
+
+    /// Whether a response was sent, by this or a clone
+    pub fn sent(&self) -> bool {
+        self.tx.lock().unwrap().is_none()
+    }
+
+    /// Send `response`, unless one was already sent
+    pub fn send(self, response: T) {
+        if let Some(tx) = self.take() {
+            deliver(&tx, response);
+        }
+    }
+
+    /// Send `response` once `delay` is over, unless one was already sent. The request is
+    /// answered right away, so any other response is dropped, even one sent meanwhile.
+    ///
+    /// Returns whether `response` is the one the request gets.
+    pub fn send_after(self, response: T, delay: impl Future<Output = ()> + Send + 'static) -> bool
+    where
+        T: Send + 'static,
+    {
+        let Some(tx) = self.take() else {
+            return false;
+        };
+        tokio::spawn(async move {
+            delay.await;
+            deliver(&tx, response);
+        });
+        true
+    }
+
+    fn take(self) -> Option<mpsc::Sender<T>> {
+        let tx = self.tx.lock().unwrap().take();
+        if tx.is_none() {
+            log::debug!("dropping response to a request already answered");
+        }
+        tx
+    }
+}
+
+fn deliver<T>(tx: &mpsc::Sender<T>, response: T) {
+    // Nothing else is sent on the channel, so it's only full if closed
+    if tx.try_send(response).is_err() {
+        log::debug!("dropping response to a request no longer waiting for one");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_send_is_dropped() {
+        let (allow, mut rx) = Responder::channel();
+        let close = allow.clone();
+        assert!(!close.sent());
+        allow.send("allow");
+        assert!(close.sent());
+        close.send("cancelled");
+        assert_eq!(rx.try_recv(), Ok("allow"));
+        // Every responder was used up, so the channel is closed
+        assert_eq!(rx.try_recv(), Err(mpsc::error::TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn dropped_unanswered_closes_channel() {
+        let (responder, mut rx) = Responder::<&str>::channel();
+        let clone = responder.clone();
+        drop(responder);
+        assert_eq!(rx.try_recv(), Err(mpsc::error::TryRecvError::Empty));
+        drop(clone);
+        assert_eq!(rx.try_recv(), Err(mpsc::error::TryRecvError::Disconnected));
+    }
+
+    #[tokio::test]
+    async fn one_of_many_triggers_answers() {
+        let (responder, mut rx) = Responder::channel();
+        let (grant, close, timeout) = (responder.clone(), responder.clone(), responder);
+        let delay = tokio::time::sleep(std::time::Duration::from_millis(20));
+        // The delayed grant answers the request right away
+        assert!(grant.send_after("allow", delay));
+        assert!(close.sent());
+        close.send("cancelled");
+        assert!(!timeout.send_after("deny", async {}));
+        assert_eq!(rx.recv().await, Some("allow"));
+        assert_eq!(rx.recv().await, None);
+    }
+
+    #[tokio::test]
+    async fn answered_requests_ignore_delayed_sends() {
+        let (responder, mut rx) = Responder::channel();
+        let delayed = responder.clone();
+        responder.send("cancelled");
+        assert!(!delayed.send_after("allow", async {}));
+        assert_eq!(rx.recv().await, Some("cancelled"));
+        assert_eq!(rx.recv().await, None);
+    }
+}
@@ -5,15 +5,22 @@ use futures::{
     future::abortable,
     stream::{FuturesOrdered, StreamExt},
 };
-use std::{collections::HashMap, mem};
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc::Sender;
 use zbus::zvariant;
 
+use crate::config;
 use crate::screencast_dialog;
-use crate::screencast_thread::ScreencastThread;
+use crate::screencast_thread::{self, Consumer, ScreencastThread};
+use crate::screenshot::Rect;
 use crate::subscription;
 use crate::wayland::{CaptureSource, WaylandHelper};
-use crate::{PortalResponse, Request};
+use crate::{fl, PortalResponse, Request};
 
 const CURSOR_MODE_HIDDEN: u32 = 1;
 const CURSOR_MODE_EMBEDDED: u32 = 2;
@@ -23,7 +30,131 @@ const SOURCE_TYPE_MONITOR: u32 = 1;
 const SOURCE_TYPE_WINDOW: u32 = 2;
 const SOURCE_TYPE_VIRTUAL: u32 = 4;
 
-#[derive(zvariant::SerializeDict, zvariant::Type)]
+// Vendor stream property naming the colorimetry of the captured source
+const COLORIMETRY_KEY: &str = "com.system76.cosmic.colorimetry";
+// Vendor stream property with the stream's cursor mode, which may differ from the
+// session's when the user hid the cursor on some sources
+const CURSOR_MODE_KEY: &str = "com.system76.cosmic.cursor-mode";
+// Vendor `Start` option asking for the compact picker, for apps embedding it in a small dialog
+const COMPACT_KEY: &str = "com.system76.cosmic.compact";
+// Vendor `Start` option asking for `StreamStatistics` signals on the session, described
+// in `data/com.system76.CosmicPortal.ScreenCastSession.xml`
+const STATISTICS_KEY: &str = "com.system76.cosmic.statistics";
+const STATISTICS_INTERFACE: &str = "com.system76.CosmicPortal.ScreenCastSession";
+const STATISTICS_INTERVAL: Duration = Duration::from_secs(5);
+
+// Sessions shorter than this end without a summary, so apps probing the portal don't
+// cause notifications
+const MIN_SUMMARY_DURATION: Duration = Duration::from_secs(5);
+
+fn compact_picker(options: &HashMap<String, zvariant::OwnedValue>) -> bool {
+    flag_option(options, COMPACT_KEY)
+}
+
+fn flag_option(options: &HashMap<String, zvariant::OwnedValue>, key: &str) -> bool {
+    options
+        .get(key)
+        .and_then(|x| bool::try_from(x).ok())
+        .unwrap_or(false)
+}
+
+/// Emit `StreamStatistics` on the session every `STATISTICS_INTERVAL`, until it closes.
+///
+/// Statistics are sampled from each stream's counters, so the capture threads only wait
+/// for the copy.
+async fn send_statistics(connection: zbus::Connection, session_handle: zvariant::OwnedObjectPath) {
+    let mut interval = tokio::time::interval(STATISTICS_INTERVAL);
+    // The first tick is immediate, before any frames are sent
+    interval.tick().await;
+    // Frames each stream had sent at the last sample, and when
+    let mut last = HashMap::new();
+    loop {
+        interval.tick().await;
+        let Some(interface) =
+            crate::session_interface::<SessionData>(&connection, (*session_handle).clone()).await
+        else {
+            return;
+        };
+        let stats: Vec<_> = {
+            let session_data = interface.get().await;
+            if session_data.closed {
+                return;
+            }
+            session_data
+                .screencast_threads
+                .iter()
+                .map(|thread| (thread.node_id(), thread.pacing()))
+                .collect()
+        };
+        let now = Instant::now();
+        let streams: Vec<(u32, HashMap<&str, zvariant::Value<'_>>)> = stats
+            .into_iter()
+            .map(|(node_id, pacing)| {
+                let fps = last.insert(node_id, (pacing.frames, now)).map_or(
+                    0.,
+                    |(frames, at): (u64, Instant)| {
+                        pacing.frames.saturating_sub(frames) as f64
+                            / now.duration_since(at).as_secs_f64()
+                    },
+                );
+                let buffer_type = if pacing.dmabuf { "dmabuf" } else { "shm" };
+                let properties = HashMap::from([
+                    ("fps", zvariant::Value::from(fps)),
+                    ("dropped", zvariant::Value::from(pacing.dropped)),
+                    ("size", zvariant::Value::from(pacing.size)),
+                    ("buffer_type", zvariant::Value::from(buffer_type)),
+                ]);
+                (node_id, properties)
+            })
+            .collect();
+        if let Err(err) = connection
+            .emit_signal(
+                None::<zbus::names::BusName<'_>>,
+                &session_handle,
+                STATISTICS_INTERFACE,
+                "StreamStatistics",
+                &(streams,),
+            )
+            .await
+        {
+            log::error!("Failed to emit statistics for {session_handle}: {err}");
+            return;
+        }
+    }
+}
+
+/// Output mostly covered by `region`, with `region` clipped to it both relative to the
+/// output and in global coordinates, all logical
+fn output_region(
+    wayland_helper: &WaylandHelper,
+    region: Rect,
+) -> Option<(wayland_client::protocol::wl_output::WlOutput, Rect, Rect)> {
+    let area = |r: Rect| i64::from(r.right - r.left) * i64::from(r.bottom - r.top);
+    wayland_helper
+        .outputs()
+        .into_iter()
+        .filter_map(|output| {
+            let info = wayland_helper.output_info(&output)?;
+            let (left, top) = info.logical_position?;
+            let (width, height) = info.logical_size?;
+            let clipped = region.intersect(Rect {
+                left,
+                top,
+                right: left + width,
+                bottom: top + height,
+            })?;
+            let relative = Rect {
+                left: clipped.left - left,
+                top: clipped.top - top,
+                right: clipped.right - left,
+                bottom: clipped.bottom - top,
+            };
+            Some((output, relative, clipped))
+        })
+        .max_by_key(|(_, _, clipped)| area(*clipped))
+}
+
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
 #[zvariant(signature = "a{sv}")]
 struct CreateSessionResult {
     session_id: String,
@@ -42,7 +173,7 @@ struct SelectSourcesOptions {
     persist_mode: Option<u32>,
 }
 
-#[derive(zvariant::SerializeDict, zvariant::Type)]
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
 #[zvariant(signature = "a{sv}")]
 struct StartResult {
     streams: Vec<(u32, HashMap<String, zvariant::OwnedValue>)>,
@@ -53,85 +184,97 @@ struct StartResult {
 #[derive(Default)]
 struct SessionData {
     screencast_threads: Vec<ScreencastThread>,
+    /// Cursor mode of each stream, in the order of `screencast_threads`
+    stream_cursor_modes: Vec<u32>,
     cursor_mode: Option<u32>,
     multiple: bool,
     source_types: BitFlags<SourceType>,
+    app_id: String,
+    paused: bool,
     closed: bool,
+    /// When streams started, for the end of session summary
+    started_at: Option<Instant>,
+    app_name: String,
+    source_names: Vec<String>,
 }
 
 impl SessionData {
     fn close(&mut self) {
         for thread in mem::take(&mut self.screencast_threads) {
+            let pacing = thread.pacing();
+            log::info!(
+                "stream {} sent {} frames and dropped {}, presented every {:?} with {:?} jitter",
+                thread.node_id(),
+                pacing.frames,
+                pacing.dropped,
+                pacing.presentation_interval,
+                pacing.jitter
+            );
             thread.stop();
         }
-        self.closed = true
+        self.closed = true;
+        if let Some(started_at) = self.started_at.take() {
+            let duration = started_at.elapsed();
+            crate::metrics::count_screencast(&self.app_id, duration);
+            self.summarize(duration);
+        }
+    }
+
+    /// Notify what was shared with whom, after a session lasting `duration` ends
+    fn summarize(&self, duration: Duration) {
+        if duration < MIN_SUMMARY_DURATION || !config::Config::load().0.screencast.session_summary {
+            return;
+        }
+        let minutes = duration.as_secs() / 60;
+        let duration = if minutes > 0 {
+            fl!("share-duration-minutes", minutes = minutes)
+        } else {
+            fl!("share-duration-seconds", seconds = duration.as_secs())
+        };
+        let summary = fl!("share-summary");
+        let body = fl!(
+            "share-summary",
+            "description",
+            sources = self.source_names.join(", "),
+            app_name = self.app_name.as_str(),
+            duration = duration
+        );
+        tokio::spawn(async move {
+            crate::notification::notify(&summary, &body).await;
+        });
     }
 }
 
+// Handles of sessions that have been started, which may since have closed
+type SessionHandles = Arc<Mutex<Vec<zvariant::OwnedObjectPath>>>;
+
 pub struct ScreenCast {
     wayland_helper: WaylandHelper,
     tx: Sender<subscription::Event>,
+    sessions: SessionHandles,
 }
 
 impl ScreenCast {
     pub fn new(wayland_helper: WaylandHelper, tx: Sender<subscription::Event>) -> Self {
-        Self { wayland_helper, tx }
-    }
-}
-
-#[zbus::interface(name = "org.freedesktop.impl.portal.ScreenCast")]
-impl ScreenCast {
-    async fn create_session(
-        &self,
-        #[zbus(connection)] connection: &zbus::Connection,
-        handle: zvariant::ObjectPath<'_>,
-        session_handle: zvariant::ObjectPath<'_>,
-        app_id: String,
-        options: HashMap<String, zvariant::OwnedValue>,
-    ) -> PortalResponse<CreateSessionResult> {
-        // TODO: handle
-        let session_data = SessionData::default();
-        connection
-            .object_server()
-            .at(
-                &session_handle,
-                crate::Session::new(session_data, |session_data| session_data.close()),
-            )
-            .await
-            .unwrap(); // XXX unwrap
-        PortalResponse::Success(CreateSessionResult {
-            session_id: "foo".to_string(), // XXX
-        })
+        Self {
+            wayland_helper,
+            tx,
+            sessions: SessionHandles::default(),
+        }
     }
 
-    async fn select_sources(
-        &self,
-        #[zbus(connection)] connection: &zbus::Connection,
-        handle: zvariant::ObjectPath<'_>,
-        session_handle: zvariant::ObjectPath<'_>,
-        app_id: String,
-        options: SelectSourcesOptions,
-    ) -> PortalResponse<HashMap<String, zvariant::OwnedValue>> {
-        // TODO: Handle other options
-        match crate::session_interface::<SessionData>(connection, session_handle).await {
-            Some(interface) => {
-                let mut session_data = interface.get_mut().await;
-                session_data.cursor_mode = options.cursor_mode;
-                session_data.multiple = options.multiple.unwrap_or(false);
-                session_data.source_types =
-                    BitFlags::from_bits_truncate(options.types.unwrap_or(0));
-                if session_data.source_types.is_empty() {
-                    session_data.source_types = SourceType::Monitor.into();
-                }
-                PortalResponse::Success(HashMap::new())
-            }
-            None => PortalResponse::Other,
+    /// Vendor interface for controlling sessions started through this one
+    pub fn sessions(&self) -> ScreenCastSessions {
+        ScreenCastSessions {
+            sessions: self.sessions.clone(),
         }
     }
+}
 
-    async fn start(
+impl ScreenCast {
+    async fn start_inner(
         &self,
-        #[zbus(connection)] connection: &zbus::Connection,
+        connection: &zbus::Connection,
         handle: zvariant::ObjectPath<'_>,
         session_handle: zvariant::ObjectPath<'_>,
         app_id: String,
@@ -152,20 +295,22 @@ impl ScreenCast {
             (cursor_mode, multiple, source_types)
         };
 
-        // XXX
         let outputs = self.wayland_helper.outputs();
-        if outputs.is_empty() {
-            log::error!("No output");
-            return PortalResponse::Other;
+        if let Err(response) = crate::require_outputs(&outputs) {
+            // There is nothing to share or pick from
+            log::error!("Screencast for {app_id:?} failed: no outputs");
+            return response;
         }
 
         // Show dialog to prompt for what to capture
         let (abortable, abort_handle) = abortable(screencast_dialog::show_screencast_prompt(
             &self.tx,
             session_handle.to_owned(),
-            app_id,
+            app_id.clone(),
             multiple,
             source_types,
+            compact_picker(&options),
+            cursor_mode == CURSOR_MODE_EMBEDDED,
             &self.wayland_helper,
         ));
         let _ = connection
@@ -181,21 +326,62 @@ impl ScreenCast {
             return PortalResponse::Cancelled;
         };
 
-        let overlay_cursor = cursor_mode == CURSOR_MODE_EMBEDDED;
+        let app_name = if app_id.is_empty() {
+            fl!("unknown-application")
+        } else {
+            screencast_dialog::app_name(&app_id)
+                .await
+                .unwrap_or_else(|| app_id.clone())
+        };
+        let consumer = Consumer {
+            app_id: app_id.clone(),
+            name: app_name.clone(),
+            legacy: config::Config::load().0.screencast.legacy_consumers,
+        };
+        let regions = capture_sources
+            .regions
+            .iter()
+            .filter_map(|region| output_region(&self.wayland_helper, *region));
+        // Sources with the part of their output to share, and where that is on screen
+        let sources: Vec<_> = capture_sources
+            .outputs
+            .into_iter()
+            .map(|output| (CaptureSource::Output(output), None))
+            .chain(
+                capture_sources
+                    .toplevels
+                    .into_iter()
+                    .map(|toplevel| (CaptureSource::Toplevel(toplevel), None)),
+            )
+            .chain(regions.map(|(output, region, geometry)| {
+                (CaptureSource::Output(output), Some((region, geometry)))
+            }))
+            .collect();
+        if sources.is_empty() {
+            log::error!("Screencast for {app_id:?} failed: selected region is on no output");
+            return PortalResponse::Other;
+        }
+        let source_names = sources
+            .iter()
+            .map(|(source, _)| screencast_thread::source_name(&self.wayland_helper, source))
+            .collect();
+        let geometries: Vec<_> = sources.iter().map(|(_, x)| x.map(|x| x.1)).collect();
         // Use `FuturesOrdered` so streams are in consistent order
         let mut res_futures = FuturesOrdered::new();
-        for output in capture_sources.outputs {
-            res_futures.push_back(ScreencastThread::new(
-                self.wayland_helper.clone(),
-                CaptureSource::Output(output),
-                overlay_cursor,
-            ));
-        }
-        for toplevel in capture_sources.toplevels {
+        let mut stream_cursor_modes = Vec::new();
+        for (source, region) in sources {
+            let stream_cursor_mode = if capture_sources.hide_cursor.contains(&source) {
+                CURSOR_MODE_HIDDEN
+            } else {
+                cursor_mode
+            };
+            stream_cursor_modes.push(stream_cursor_mode);
             res_futures.push_back(ScreencastThread::new(
                 self.wayland_helper.clone(),
-                CaptureSource::Toplevel(toplevel),
-                overlay_cursor,
+                source,
+                region.map(|x| x.0),
+                stream_cursor_mode == CURSOR_MODE_EMBEDDED,
+                consumer.clone(),
             ));
         }
 
@@ -229,10 +415,71 @@ impl ScreenCast {
 
         let streams = screencast_threads
             .iter()
-            .map(|thread| (thread.node_id(), HashMap::new()))
+            .zip(&stream_cursor_modes)
+            .zip(&geometries)
+            .map(|((thread, stream_cursor_mode), geometry)| {
+                let mut properties = HashMap::new();
+                if let Ok(value) = zvariant::OwnedValue::try_from(zvariant::Value::from(
+                    thread.colorimetry().name(),
+                )) {
+                    properties.insert(COLORIMETRY_KEY.to_string(), value);
+                }
+                properties.insert(
+                    CURSOR_MODE_KEY.to_string(),
+                    zvariant::OwnedValue::from(*stream_cursor_mode),
+                );
+                if let Some(geometry) = geometry {
+                    let position = (geometry.left, geometry.top);
+                    let size = (
+                        geometry.right - geometry.left,
+                        geometry.bottom - geometry.top,
+                    );
+                    for (key, value) in [("position", position), ("size", size)] {
+                        if let Ok(value) =
+                            zvariant::OwnedValue::try_from(zvariant::Value::from(value))
+                        {
+                            properties.insert(key.to_string(), value);
+                        }
+                    }
+                }
+                (thread.node_id(), properties)
+            })
             .collect();
-        interface.get_mut().await.screencast_threads = screencast_threads;
+        // TODO: Audio. Shares are video only, since neither the ScreenCast portal nor
+        // the frontend has a way to offer an audio stream yet. A window share would then
+        // need its app's output streams, found in the PipeWire graph by the app's pid or
+        // `application.name`, linked into a null sink of its own, rather than the
+        // monitor of the whole mix. Muting such a share belongs with that node: a `muted`
+        // flag in `SessionData`, set before its first buffer for pre-muted shares, kept
+        // across source switches, and listed by `ListSessions`, while the node goes on
+        // queueing silent buffers so consumers keep their audio track.
+        {
+            let mut session_data = interface.get_mut().await;
+            session_data.screencast_threads = screencast_threads;
+            session_data.stream_cursor_modes = stream_cursor_modes;
+            session_data.app_id = app_id;
+            session_data.started_at = Some(Instant::now());
+            session_data.app_name = app_name;
+            session_data.source_names = source_names;
+        }
+        self.sessions
+            .lock()
+            .unwrap()
+            .push(session_handle.to_owned().into());
+        if flag_option(&options, STATISTICS_KEY) {
+            tokio::spawn(send_statistics(
+                connection.clone(),
+                session_handle.to_owned().into(),
+            ));
+        }
 
+        // TODO: Restore tokens. None are issued, so every session asks again. Grants
+        // persisted for them would need a place to be revoked, on the `ScreenCast`
+        // vendor interface: listing each with its ID, app name, source description and
+        // created and last used times, revoking one by ID or all of an app's, and
+        // signalling changes so cosmic-settings stays current. Revoking a grant must
+        // also close sessions started from it, which means recording the grant in
+        // `SessionData`.
         PortalResponse::Success(StartResult {
             // XXX
             streams,
@@ -240,6 +487,80 @@ impl ScreenCast {
             restore_data: None,
         })
     }
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.ScreenCast")]
+impl ScreenCast {
+    async fn create_session(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        session_handle: zvariant::ObjectPath<'_>,
+        app_id: String,
+        options: HashMap<String, zvariant::OwnedValue>,
+    ) -> PortalResponse<CreateSessionResult> {
+        // TODO: handle
+        let session_data = SessionData::default();
+        connection
+            .object_server()
+            .at(
+                &session_handle,
+                crate::Session::new(session_data, |session_data| session_data.close()),
+            )
+            .await
+            .unwrap(); // XXX unwrap
+        PortalResponse::Success(CreateSessionResult {
+            session_id: "foo".to_string(), // XXX
+        })
+    }
+
+    async fn select_sources(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        session_handle: zvariant::ObjectPath<'_>,
+        app_id: String,
+        options: SelectSourcesOptions,
+    ) -> PortalResponse<HashMap<String, zvariant::OwnedValue>> {
+        // TODO: Handle other options
+        match crate::session_interface::<SessionData>(connection, session_handle).await {
+            Some(interface) => {
+                let mut session_data = interface.get_mut().await;
+                session_data.cursor_mode = options.cursor_mode;
+                session_data.multiple = options.multiple.unwrap_or(false);
+                session_data.source_types =
+                    BitFlags::from_bits_truncate(options.types.unwrap_or(0));
+                if session_data.source_types.is_empty() {
+                    session_data.source_types = SourceType::Monitor.into();
+                }
+                PortalResponse::Success(HashMap::new())
+            }
+            None => PortalResponse::Other,
+        }
+    }
+
+    async fn start(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        session_handle: zvariant::ObjectPath<'_>,
+        app_id: String,
+        parent_window: String,
+        options: HashMap<String, zvariant::OwnedValue>,
+    ) -> PortalResponse<StartResult> {
+        let response = self
+            .start_inner(
+                connection,
+                handle.clone(),
+                session_handle,
+                app_id,
+                parent_window,
+                options,
+            )
+            .await;
+        crate::emit_response(connection, &handle, &response).await;
+        response
+    }
 
     #[zbus(property)]
     async fn available_source_types(&self) -> u32 {
@@ -258,3 +579,92 @@ impl ScreenCast {
         4
     }
 }
+
+pub struct ScreenCastSessions {
+    sessions: SessionHandles,
+}
+
+#[zbus::interface(name = "com.system76.CosmicPortal.ScreenCast")]
+impl ScreenCastSessions {
+    /// Active sessions, as session handle, app ID, and whether sharing is paused
+    async fn list_sessions(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> Vec<(zvariant::OwnedObjectPath, String, bool)> {
+        let handles = self.sessions.lock().unwrap().clone();
+        let mut sessions = Vec::new();
+        let mut closed = Vec::new();
+        for handle in handles {
+            match crate::session_interface::<SessionData>(connection, (*handle).clone()).await {
+                Some(interface) => {
+                    let session_data = interface.get().await;
+                    sessions.push((handle, session_data.app_id.clone(), session_data.paused));
+                }
+                None => closed.push(handle),
+            }
+        }
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|handle| !closed.contains(handle));
+        sessions
+    }
+
+    /// Pause or resume sharing, without stopping the session's streams
+    async fn set_paused(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(signal_context)] signal_ctxt: zbus::SignalContext<'_>,
+        session_handle: zvariant::ObjectPath<'_>,
+        paused: bool,
+    ) -> zbus::fdo::Result<()> {
+        let Some(interface) =
+            crate::session_interface::<SessionData>(connection, session_handle.clone()).await
+        else {
+            return Err(zbus::fdo::Error::InvalidArgs(format!(
+                "No session {session_handle}"
+            )));
+        };
+        let mut session_data = interface.get_mut().await;
+        if session_data.paused == paused {
+            return Ok(());
+        }
+        // Loaded here so a change to the setting applies on the next pause
+        let frame = config::Config::load().0.screencast.paused_frame;
+        for thread in &session_data.screencast_threads {
+            if paused {
+                thread.pause(frame);
+            } else {
+                thread.resume();
+            }
+        }
+        session_data.paused = paused;
+        drop(session_data);
+        Self::paused_changed(&signal_ctxt, session_handle, paused).await?;
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn paused_changed(
+        signal_ctxt: &zbus::SignalContext<'_>,
+        session_handle: zvariant::ObjectPath<'_>,
+        paused: bool,
+    ) -> zbus::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_round_trip() {
+        crate::assert_round_trips(CreateSessionResult {
+            session_id: "session".to_string(),
+        });
+        crate::assert_round_trips(StartResult {
+            streams: vec![(42, HashMap::new())],
+            persist_mode: Some(2),
+            restore_data: None,
+        });
+    }
+}
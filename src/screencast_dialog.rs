@@ -1,18 +1,21 @@
 use crate::app::CosmicPortal;
+use crate::config::shortcuts::Action;
 use crate::fl;
+use crate::lru::{LruCache, APP_CACHE_CAPACITY};
+use crate::responder::Responder;
+use crate::screenshot::{Rect, Screenshot};
+use crate::shortcuts;
 use crate::wayland::{CaptureSource, WaylandHelper};
 use crate::widget::{keyboard_wrapper::KeyboardWrapper, screenshot::MyImage};
 use ashpd::{desktop::screencast::SourceType, enumflags2::BitFlags};
 use cosmic::desktop::IconSource;
-use cosmic::iced::{
-    self,
-    keyboard::{key::Named, Key},
-    window,
-};
+use cosmic::iced::wayland::actions::window::SctkWindowSettings;
+use cosmic::iced::{self, window};
 use cosmic::iced_runtime::command::platform_specific::wayland::layer_surface::SctkLayerSurfaceSettings;
 use cosmic::iced_sctk::commands::layer_surface::{
-    destroy_layer_surface, get_layer_surface, KeyboardInteractivity, Layer,
+    destroy_layer_surface, get_layer_surface, KeyboardInteractivity,
 };
+use cosmic::iced_sctk::commands::window::{close_window, get_window};
 use cosmic::{theme, widget};
 use cosmic_client_toolkit::sctk::output::OutputInfo;
 use cosmic_client_toolkit::toplevel_info::ToplevelInfo;
@@ -21,13 +24,22 @@ use freedesktop_desktop_entry as fde;
 use freedesktop_desktop_entry::{get_languages_from_env, DesktopEntry};
 use once_cell::sync::Lazy;
 use std::mem;
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
 use wayland_client::protocol::wl_output::WlOutput;
 use zbus::zvariant;
 
 pub static SCREENCAST_ID: Lazy<window::Id> = Lazy::new(window::Id::unique);
 
+// Most windows previewed in the picker, and captured at once
+const MAX_WINDOW_PREVIEWS: usize = 12;
+const CONCURRENT_WINDOW_PREVIEWS: usize = 2;
+// How long to wait for a window's preview before leaving it without one
+const WINDOW_PREVIEW_TIMEOUT: Duration = Duration::from_secs(2);
+// Largest width and height of window previews
+const WINDOW_PREVIEW_SIZE: u32 = 160;
+
 struct HideDialogOnDrop(
     Option<(
         mpsc::Sender<crate::subscription::Event>,
@@ -36,6 +48,10 @@ struct HideDialogOnDrop(
 );
 
 impl HideDialogOnDrop {
+    fn session_handle(&self) -> zvariant::ObjectPath<'static> {
+        self.0.as_ref().unwrap().1.clone()
+    }
+
     fn clear(mut self) {
         self.0 = None;
     }
@@ -57,6 +73,8 @@ pub async fn show_screencast_prompt(
     app_id: String,
     multiple: bool,
     source_types: BitFlags<SourceType>,
+    compact: bool,
+    cursor_embedded: bool,
     wayland_helper: &WaylandHelper,
 ) -> Option<CaptureSources> {
     let hide_on_drop = HideDialogOnDrop(Some((subscription_tx.clone(), session_handle.clone())));
@@ -79,31 +97,38 @@ pub async fn show_screencast_prompt(
         let Some(info) = wayland_helper.output_info(&output) else {
             continue;
         };
-        let source = CaptureSource::Output(output.clone());
-        let image = wayland_helper
-            .capture_source_shm(source, false)
-            .await
-            .and_then(|image| image.image().ok())
-            .map(|image| {
-                widget::image::Handle::from_pixels(
-                    image.width(),
-                    image.height(),
-                    MyImage(Arc::new(image)),
-                )
-            });
+        // The compact picker has no thumbnails
+        let image = if compact {
+            None
+        } else {
+            let source = CaptureSource::Output(output.clone());
+            wayland_helper
+                .capture_source_shm(source, false)
+                .await
+                .and_then(|image| image.image().ok())
+                .map(|image| {
+                    widget::image::Handle::from_pixels(
+                        image.width(),
+                        image.height(),
+                        MyImage(Arc::new(image)),
+                    )
+                })
+        };
         outputs.push((output, info, image));
     }
 
-    let app_name = get_desktop_entry(&desktop_entries, &app_id)
-        .and_then(|x| Some(x.name(&locales)?.into_owned()));
+    let app_name = app_name(&app_id).await;
 
-    let (tx, mut rx) = mpsc::channel(1);
+    let (tx, mut rx) = Responder::channel();
     let args = Args {
         session_handle,
         outputs,
         toplevels,
+        window_previews: Vec::new(),
         multiple,
         source_types,
+        compact,
+        per_source_cursor: multiple && cursor_embedded,
         app_name,
         tx,
         capture_sources: Default::default(),
@@ -112,11 +137,50 @@ pub async fn show_screencast_prompt(
         .send(crate::subscription::Event::Screencast(args))
         .await
         .unwrap();
-    let resp = rx.recv().await.unwrap();
+    // Closed without a response if the dialog's state was dropped, which cancels
+    let mut resp = rx.recv().await.flatten();
+    if resp.as_ref().is_some_and(|x| x.select_region) {
+        // TODO: Close the overlay if the request is closed while selecting
+        let screenshot = Screenshot::new(wayland_helper.clone(), subscription_tx.clone());
+        resp = screenshot
+            .select_region(hide_on_drop.session_handle(), &app_id)
+            .await
+            .map(|region| CaptureSources {
+                regions: vec![region],
+                ..Default::default()
+            });
+    }
     hide_on_drop.clear();
     resp
 }
 
+// Names resolved by `app_name`
+static APP_NAMES: Lazy<Mutex<LruCache<String, Option<String>>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(APP_CACHE_CAPACITY)));
+
+/// Localized name of the app with `app_id`, from its desktop entry
+pub async fn app_name(app_id: &str) -> Option<String> {
+    if let Some(name) = APP_NAMES.lock().unwrap().get(app_id) {
+        return name.clone();
+    }
+    let locales = get_languages_from_env();
+    let desktop_entries = load_desktop_entries(&locales).await;
+    let name = get_desktop_entry(&desktop_entries, app_id)
+        .and_then(|x| Some(x.name(&locales)?.into_owned()));
+    APP_NAMES
+        .lock()
+        .unwrap()
+        .insert(app_id.to_string(), name.clone());
+    name
+}
+
+/// Icon name of the app with `app_id`, from its desktop entry
+pub async fn app_icon(app_id: &str) -> Option<String> {
+    let locales = get_languages_from_env();
+    let desktop_entries = load_desktop_entries(&locales).await;
+    get_desktop_entry(&desktop_entries, app_id).and_then(|x| Some(x.icon()?.to_owned()))
+}
+
 async fn load_desktop_entries(locales: &[String]) -> Vec<DesktopEntry<'static>> {
     let mut entries = Vec::new();
     for p in fde::Iter::new(fde::default_paths()) {
@@ -136,15 +200,31 @@ fn get_desktop_entry<'a>(
     fde::matching::get_best_match(&[id], &entries, fde::matching::MatchAppIdOptions::default())
 }
 
-fn create_dialog() -> cosmic::Command<crate::app::Msg> {
-    get_layer_surface(SctkLayerSurfaceSettings {
-        id: *SCREENCAST_ID,
-        keyboard_interactivity: KeyboardInteractivity::Exclusive,
-        namespace: "screencast".into(),
-        layer: Layer::Overlay,
-        size: None,
-        ..Default::default()
-    })
+fn create_dialog(compact: bool) -> cosmic::Command<crate::app::Msg> {
+    if compact {
+        get_window(SctkWindowSettings {
+            window_id: *SCREENCAST_ID,
+            app_id: Some(crate::DBUS_NAME.to_string()),
+            title: Some(fl!("share-screen")),
+            parent: None, // TODO parse parent window and set parent
+            autosize: true,
+            resizable: None,
+            ..Default::default()
+        })
+    } else {
+        get_layer_surface(SctkLayerSurfaceSettings {
+            keyboard_interactivity: KeyboardInteractivity::Exclusive,
+            ..crate::surfaces::centered_dialog(*SCREENCAST_ID, "screencast")
+        })
+    }
+}
+
+fn destroy_dialog(compact: bool) -> cosmic::Command<crate::app::Msg> {
+    if compact {
+        close_window(*SCREENCAST_ID)
+    } else {
+        destroy_layer_surface(*SCREENCAST_ID)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -158,21 +238,23 @@ pub struct Args {
     session_handle: zvariant::ObjectPath<'static>,
     multiple: bool,
     source_types: BitFlags<SourceType>,
+    /// Modal window with a plain list of sources, instead of the full picker
+    compact: bool,
+    /// Whether the cursor may be hidden on some of the selected sources, for sessions
+    /// embedding it in multiple streams
+    per_source_cursor: bool,
     outputs: Vec<(WlOutput, OutputInfo, Option<widget::image::Handle>)>,
     toplevels: Vec<(ZcosmicToplevelHandleV1, ToplevelInfo, Option<String>)>,
+    /// Snapshots of windows, captured after the picker is shown
+    window_previews: Vec<(ZcosmicToplevelHandleV1, widget::image::Handle)>,
     app_name: Option<String>,
-    // Should be oneshot, but need `Clone` bound
-    tx: mpsc::Sender<Option<CaptureSources>>,
+    tx: Responder<Option<CaptureSources>>,
     capture_sources: CaptureSources,
 }
 
 impl Args {
     fn send_response(self, response: Option<CaptureSources>) {
-        tokio::spawn(async move {
-            if let Err(err) = self.tx.send(response).await {
-                log::error!("Failed to send screencast event: {}", err);
-            }
-        });
+        self.tx.send(response);
     }
 }
 
@@ -181,16 +263,28 @@ impl Args {
 pub struct CaptureSources {
     pub outputs: Vec<WlOutput>,
     pub toplevels: Vec<ZcosmicToplevelHandleV1>,
+    /// Selected sources captured without the cursor, though the session embeds it
+    pub hide_cursor: Vec<CaptureSource>,
+    /// Rectangles of outputs to share, in global logical coordinates
+    pub regions: Vec<Rect>,
+    /// Whether the user chose to select a region instead, which isn't yet in `regions`
+    select_region: bool,
 }
 
 impl CaptureSources {
     pub fn is_empty(&self) -> bool {
-        self.outputs.is_empty() && self.toplevels.is_empty()
+        self.outputs.is_empty() && self.toplevels.is_empty() && self.regions.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len() + self.toplevels.len() + self.regions.len()
     }
 
     pub fn clear(&mut self) {
         self.outputs.clear();
         self.toplevels.clear();
+        self.hide_cursor.clear();
+        self.regions.clear();
     }
 }
 
@@ -199,6 +293,9 @@ pub enum Msg {
     ActivateTab(widget::segmented_button::Entity),
     SelectOutput(WlOutput),
     SelectToplevel(ZcosmicToplevelHandleV1),
+    ShowCursor(CaptureSource, bool),
+    WindowPreview(ZcosmicToplevelHandleV1, widget::image::Handle),
+    SelectRegion,
     Share,
     Cancel,
 }
@@ -224,6 +321,8 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
                 .position(|x| x == &output)
             {
                 args.capture_sources.outputs.remove(idx);
+                let source = CaptureSource::Output(output);
+                args.capture_sources.hide_cursor.retain(|x| *x != source);
             } else {
                 if !args.multiple && !args.capture_sources.is_empty() {
                     args.capture_sources.clear();
@@ -239,6 +338,8 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
                 .position(|x| x == &toplevel)
             {
                 args.capture_sources.toplevels.remove(idx);
+                let source = CaptureSource::Toplevel(toplevel);
+                args.capture_sources.hide_cursor.retain(|x| *x != source);
             } else {
                 if !args.multiple && !args.capture_sources.is_empty() {
                     args.capture_sources.clear();
@@ -246,17 +347,40 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
                 args.capture_sources.toplevels.push(toplevel);
             }
         }
+        Msg::ShowCursor(source, show) => {
+            args.capture_sources.hide_cursor.retain(|x| *x != source);
+            if !show {
+                args.capture_sources.hide_cursor.push(source);
+            }
+        }
+        Msg::WindowPreview(toplevel, handle) => {
+            if args.toplevels.iter().any(|(x, _, _)| *x == toplevel) {
+                args.window_previews.push((toplevel, handle));
+            }
+        }
+        Msg::SelectRegion => {
+            if let Some(args) = portal.screencast_args.take() {
+                let compact = args.compact;
+                args.send_response(Some(CaptureSources {
+                    select_region: true,
+                    ..Default::default()
+                }));
+                return destroy_dialog(compact);
+            }
+        }
         Msg::Share => {
             if let Some(mut args) = portal.screencast_args.take() {
                 let response = mem::take(&mut args.capture_sources);
+                let compact = args.compact;
                 args.send_response(Some(response));
-                return destroy_layer_surface(*SCREENCAST_ID);
+                return destroy_dialog(compact);
             }
         }
         Msg::Cancel => {
             if let Some(args) = portal.screencast_args.take() {
+                let compact = args.compact;
                 args.send_response(None);
-                return destroy_layer_surface(*SCREENCAST_ID);
+                return destroy_dialog(compact);
             }
         }
     }
@@ -265,11 +389,17 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
 
 pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<crate::app::Msg> {
     // If the dialog is already open, cancel previous request, but re-use dialog surface
-    let command = if let Some(args) = portal.screencast_args.take() {
-        args.send_response(None);
-        cosmic::Command::none()
-    } else {
-        create_dialog()
+    let command = match portal.screencast_args.take() {
+        Some(old_args) if old_args.compact == args.compact => {
+            old_args.send_response(None);
+            cosmic::Command::none()
+        }
+        Some(old_args) => {
+            let compact = old_args.compact;
+            old_args.send_response(None);
+            cosmic::Command::batch([destroy_dialog(compact), create_dialog(args.compact)])
+        }
+        None => create_dialog(args.compact),
     };
 
     portal.screencast_tab_model.clear();
@@ -289,9 +419,86 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<cra
     }
     portal.screencast_tab_model.activate_position(0);
 
+    // Previews arrive after the dialog is shown, and change its layout
+    let previews = if args.compact
+        || portal.config.instant_dialogs
+        || !args.source_types.contains(SourceType::Window)
+    {
+        cosmic::Command::none()
+    } else {
+        window_previews(&portal.wayland_helper, &args)
+    };
     portal.screencast_args = Some(args);
 
-    command
+    cosmic::Command::batch([command, previews])
+}
+
+// Capture snapshots of the first windows in the picker, a few at a time. Windows that
+// can't be captured, or take too long, are left with just their icon.
+fn window_previews(
+    wayland_helper: &WaylandHelper,
+    args: &Args,
+) -> cosmic::Command<crate::app::Msg> {
+    let permits = Arc::new(Semaphore::new(CONCURRENT_WINDOW_PREVIEWS));
+    let captures = args
+        .toplevels
+        .iter()
+        .take(MAX_WINDOW_PREVIEWS)
+        .map(|(toplevel, _, _)| {
+            let wayland_helper = wayland_helper.clone();
+            let permits = permits.clone();
+            let toplevel = toplevel.clone();
+            cosmic::Command::perform(
+                async move {
+                    let _permit = permits.acquire_owned().await.ok()?;
+                    let source = CaptureSource::Toplevel(toplevel.clone());
+                    // Spawned, so a capture that never finishes is only abandoned
+                    let capture =
+                        tokio::spawn(async move { capture_preview(&wayland_helper, source).await });
+                    match tokio::time::timeout(WINDOW_PREVIEW_TIMEOUT, capture).await {
+                        Ok(Ok(Some(handle))) => Some((toplevel, handle)),
+                        Ok(_) => None,
+                        Err(_) => {
+                            log::warn!("Timed out capturing window preview");
+                            None
+                        }
+                    }
+                },
+                |preview| match preview {
+                    Some((toplevel, handle)) => cosmic::app::message::app(
+                        crate::app::Msg::Screencast(Msg::WindowPreview(toplevel, handle)),
+                    ),
+                    None => cosmic::app::message::none(),
+                },
+            )
+        });
+    cosmic::Command::batch(captures)
+}
+
+async fn capture_preview(
+    wayland_helper: &WaylandHelper,
+    source: CaptureSource,
+) -> Option<widget::image::Handle> {
+    let image = wayland_helper
+        .capture_source_shm(source, false)
+        .await?
+        .image()
+        .ok()?;
+    let scale = f64::from(WINDOW_PREVIEW_SIZE) / f64::from(image.width().max(image.height()));
+    let image = if scale < 1.0 {
+        image::imageops::thumbnail(
+            &image,
+            ((f64::from(image.width()) * scale) as u32).max(1),
+            ((f64::from(image.height()) * scale) as u32).max(1),
+        )
+    } else {
+        image
+    };
+    Some(widget::image::Handle::from_pixels(
+        image.width(),
+        image.height(),
+        MyImage(Arc::new(image)),
+    ))
 }
 
 pub fn cancel(
@@ -304,8 +511,9 @@ pub fn cancel(
         .map_or(false, |args| args.session_handle == session_handle)
     {
         let args = portal.screencast_args.take().unwrap();
+        let compact = args.compact;
         args.send_response(None);
-        destroy_layer_surface(*SCREENCAST_ID)
+        destroy_dialog(compact)
     } else {
         cosmic::Command::none()
     }
@@ -374,12 +582,13 @@ fn output_button<'a>(
         .into()
 }
 
-fn toplevel_button(
-    label: &str,
+fn toplevel_button<'a>(
+    label: &'a str,
     is_selected: bool,
     icon: IconSource,
+    preview: Option<&'a widget::image::Handle>,
     msg: Msg,
-) -> cosmic::Element<Msg> {
+) -> cosmic::Element<'a, Msg> {
     let text = widget::text(label).style(theme::style::Text::Custom(|theme| {
         let container = theme.current_container();
         cosmic::iced_core::widget::text::Appearance {
@@ -396,6 +605,9 @@ fn toplevel_button(
         .on_press(msg);
     let mut children = Vec::new();
     children.push(icon.as_cosmic_icon().size(24).into());
+    if let Some(preview) = preview {
+        children.push(widget::image::Image::new(preview.clone()).into());
+    }
     children.push(button.into());
     // TODO
     if is_selected {
@@ -415,6 +627,45 @@ pub(crate) fn view(portal: &CosmicPortal) -> cosmic::Element<Msg> {
         share_button = share_button.on_press(Msg::Share);
     }
 
+    let unknown = fl!("unknown-application");
+    let app_name = args.app_name.as_deref().unwrap_or(&unknown);
+
+    let sources = if args.compact {
+        compact_source_list(args)
+    } else {
+        source_tabs(portal, args)
+    };
+    let control = if args.per_source_cursor && args.capture_sources.len() > 1 {
+        widget::column::with_children(vec![sources, cursor_toggles(args)])
+            .spacing(8)
+            .into()
+    } else {
+        sources
+    };
+
+    KeyboardWrapper::new(
+        widget::dialog("Share your screen")
+            // TODO adjust text for multiple select, types?
+            .body(fl!("share-screen", "description", app_name = app_name))
+            .secondary_action(cancel_button)
+            .primary_action(share_button)
+            .control(control),
+        |key, modifiers| match shortcuts::action(
+            &portal.config.shortcuts.bindings,
+            true,
+            false,
+            key,
+            modifiers,
+        )? {
+            Action::Accept => Some(Msg::Share),
+            Action::Cancel => Some(Msg::Cancel),
+        },
+    )
+    .into()
+}
+
+// Tabs of output thumbnails and windows, for the full picker
+fn source_tabs<'a>(portal: &'a CosmicPortal, args: &'a Args) -> cosmic::Element<'a, Msg> {
     let tabs =
         widget::tab_bar::horizontal(&portal.screencast_tab_model).on_activate(Msg::ActivateTab);
 
@@ -431,7 +682,14 @@ pub(crate) fn view(portal: &CosmicPortal) -> cosmic::Element<Msg> {
                     Msg::SelectOutput(output.clone()),
                 ));
             }
-            widget::row::with_children(children).spacing(8).into()
+            let select_region =
+                widget::button::text(fl!("select-region")).on_press(Msg::SelectRegion);
+            widget::column::with_children(vec![
+                widget::row::with_children(children).spacing(8).into(),
+                select_region.into(),
+            ])
+            .spacing(8)
+            .into()
         }
         Tab::Windows => {
             let mut list = widget::ListColumn::new();
@@ -439,10 +697,16 @@ pub(crate) fn view(portal: &CosmicPortal) -> cosmic::Element<Msg> {
                 let icon = IconSource::from_unknown(icon.as_deref().unwrap_or_default());
                 let label = &toplevel_info.title;
                 let is_selected = args.capture_sources.toplevels.contains(toplevel);
+                let preview = args
+                    .window_previews
+                    .iter()
+                    .find(|(x, _)| x == toplevel)
+                    .map(|(_, handle)| handle);
                 list = list.add(toplevel_button(
                     label,
                     is_selected,
                     icon,
+                    preview,
                     Msg::SelectToplevel(toplevel.clone()),
                 ));
             }
@@ -453,23 +717,66 @@ pub(crate) fn view(portal: &CosmicPortal) -> cosmic::Element<Msg> {
         }
     };
 
-    let unknown = fl!("unknown-application");
-    let app_name = args.app_name.as_deref().unwrap_or(&unknown);
+    widget::column::with_children(vec![tabs.into(), list])
+        .spacing(8)
+        .into()
+}
 
-    let control = widget::column::with_children(vec![tabs.into(), list.into()]).spacing(8);
+// Whether to show the cursor on each selected source
+fn cursor_toggles(args: &Args) -> cosmic::Element<Msg> {
+    let outputs = args.outputs.iter().filter_map(|(output, info, _)| {
+        args.capture_sources.outputs.contains(output).then(|| {
+            let name = info.name.clone().unwrap_or_default();
+            (CaptureSource::Output(output.clone()), name)
+        })
+    });
+    let toplevels = args.toplevels.iter().filter_map(|(toplevel, info, _)| {
+        args.capture_sources.toplevels.contains(toplevel).then(|| {
+            let name = info.title.clone();
+            (CaptureSource::Toplevel(toplevel.clone()), name)
+        })
+    });
+    let toggles = outputs.chain(toplevels).map(|(source, name)| {
+        let shown = !args.capture_sources.hide_cursor.contains(&source);
+        widget::checkbox(fl!("show-cursor-on", source = name), shown)
+            .on_toggle(move |show| Msg::ShowCursor(source.clone(), show))
+            .into()
+    });
+    widget::column::with_children(toggles.collect())
+        .spacing(8)
+        .into()
+}
 
-    KeyboardWrapper::new(
-        widget::dialog("Share your screen")
-            // TODO adjust text for multiple select, types?
-            .body(fl!("share-screen", "description", app_name = app_name))
-            .secondary_action(cancel_button)
-            .primary_action(share_button)
-            .control(control),
-        |key| match key {
-            Key::Named(Named::Enter) => Some(Msg::Share),
-            Key::Named(Named::Escape) => Some(Msg::Cancel),
-            _ => None,
-        },
-    )
-    .into()
+// One list of outputs then windows, without thumbnails, for the compact picker
+fn compact_source_list(args: &Args) -> cosmic::Element<Msg> {
+    let mut list = widget::ListColumn::new();
+    if args.source_types.contains(SourceType::Monitor) {
+        for (output, output_info, _) in &args.outputs {
+            let label = output_info.name.as_deref().unwrap_or_default();
+            let is_selected = args.capture_sources.outputs.contains(output);
+            list = list.add(toplevel_button(
+                label,
+                is_selected,
+                IconSource::from_unknown("video-display-symbolic"),
+                None,
+                Msg::SelectOutput(output.clone()),
+            ));
+        }
+    }
+    if args.source_types.contains(SourceType::Window) {
+        for (toplevel, toplevel_info, icon) in &args.toplevels {
+            let icon = IconSource::from_unknown(icon.as_deref().unwrap_or_default());
+            let is_selected = args.capture_sources.toplevels.contains(toplevel);
+            list = list.add(toplevel_button(
+                &toplevel_info.title,
+                is_selected,
+                icon,
+                None,
+                Msg::SelectToplevel(toplevel.clone()),
+            ));
+        }
+    }
+    cosmic::widget::scrollable(list)
+        .width(iced::Length::Fill)
+        .into()
 }
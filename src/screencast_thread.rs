@@ -14,37 +14,173 @@ use pipewire::{
     stream::{StreamRef, StreamState},
     sys::pw_buffer,
 };
-use std::{ffi::c_void, io, iter, os::fd::IntoRawFd, slice};
+use std::{
+    ffi::{c_void, CString},
+    io, iter, mem,
+    os::fd::{AsRawFd, IntoRawFd, OwnedFd, RawFd},
+    slice,
+    sync::{mpsc, Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
 use tokio::sync::oneshot;
 use wayland_client::protocol::{wl_buffer, wl_shm};
 
 use crate::{
     buffer,
+    config::screencast::PausedFrame,
+    screenshot::Rect,
     wayland::{CaptureSource, DmabufHelper, Session, WaylandHelper},
 };
 
+// Longest a stopped thread takes to tear down its stream before it's given up on
+const STOP_TIMEOUT: Duration = Duration::from_secs(2);
+// Frame rate of streams to consumers that only support BGRA, which are swizzled on the CPU
+const LEGACY_FRAMERATE: u32 = 30;
+
+/// SPA colorimetry of a stream, as negotiated in its format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Colorimetry {
+    pub range: u32,
+    pub matrix: u32,
+    pub transfer: u32,
+    pub primaries: u32,
+}
+
+impl Colorimetry {
+    pub const SRGB: Self = Self {
+        range: spa_sys::SPA_VIDEO_COLOR_RANGE_0_255,
+        matrix: spa_sys::SPA_VIDEO_COLOR_MATRIX_RGB,
+        transfer: spa_sys::SPA_VIDEO_TRANSFER_SRGB,
+        primaries: spa_sys::SPA_VIDEO_COLOR_PRIMARIES_BT709,
+    };
+
+    /// Name used for the `com.system76.cosmic.colorimetry` stream property
+    pub fn name(&self) -> &'static str {
+        match self.transfer {
+            spa_sys::SPA_VIDEO_TRANSFER_SMPTE2084 => "bt2100-pq",
+            _ => "srgb",
+        }
+    }
+
+    fn properties(&self) -> [pod::Property; 4] {
+        [
+            (spa_sys::SPA_FORMAT_VIDEO_colorRange, self.range),
+            (spa_sys::SPA_FORMAT_VIDEO_colorMatrix, self.matrix),
+            (spa_sys::SPA_FORMAT_VIDEO_transferFunction, self.transfer),
+            (spa_sys::SPA_FORMAT_VIDEO_colorPrimaries, self.primaries),
+        ]
+        .map(|(key, value)| pod::Property {
+            key,
+            flags: pod::PropertyFlags::empty(),
+            value: pod::Value::Id(Id(value)),
+        })
+    }
+
+    // Colorimetry of the source being captured
+    fn for_source(_capture_source: &CaptureSource) -> Self {
+        // TODO: PQ-encoded BT.2020 for outputs in HDR mode, once the compositor
+        // exposes color management state to clients. Consumers that only negotiate
+        // SDR would then need the stream tone-mapped, which should be done on the GPU,
+        // and only for formats with more than 8 bits per channel.
+        Self::SRGB
+    }
+}
+
+/// The app a stream is shared with, named in the stream's node properties
+#[derive(Clone, Debug)]
+pub struct Consumer {
+    pub app_id: String,
+    pub name: String,
+    /// Whether the consumer may negotiate BGRA, for old clients without RGBA
+    pub legacy: bool,
+}
+
+/// Frames a stream delivered, and how evenly they follow the output's presentation of them
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PacingStats {
+    /// Frames sent to the consumer
+    pub frames: u64,
+    /// Frames that failed to capture, and were skipped
+    pub dropped: u64,
+    /// Size of the stream's buffers
+    pub size: (u32, u32),
+    /// Whether the consumer negotiated dmabufs rather than shm
+    pub dmabuf: bool,
+    /// Average time between presentations of the captured frames, which follows the
+    /// actual refresh rate of variable refresh displays
+    pub presentation_interval: Duration,
+    /// Average deviation of the time between presentations from `presentation_interval`
+    pub jitter: Duration,
+}
+
+impl PacingStats {
+    // Weight of each new interval in the running averages
+    const SMOOTHING: f64 = 1.0 / 16.0;
+
+    fn record(&mut self, interval: Duration) {
+        if self.presentation_interval.is_zero() {
+            self.presentation_interval = interval;
+            return;
+        }
+        let average = self.presentation_interval.as_secs_f64();
+        let deviation = (interval.as_secs_f64() - average).abs();
+        self.presentation_interval =
+            Duration::from_secs_f64(average + (interval.as_secs_f64() - average) * Self::SMOOTHING);
+        let jitter = self.jitter.as_secs_f64();
+        self.jitter = Duration::from_secs_f64(jitter + (deviation - jitter) * Self::SMOOTHING);
+    }
+}
+
 pub struct ScreencastThread {
     node_id: u32,
+    colorimetry: Colorimetry,
+    paused: Arc<Mutex<Option<PausedFrame>>>,
+    pacing: Arc<Mutex<PacingStats>>,
     thread_stop_tx: pipewire::channel::Sender<()>,
+    // Sent once the thread has torn down its stream. Locked only so sessions can be shared.
+    stopped_rx: Mutex<mpsc::Receiver<()>>,
+    thread: JoinHandle<()>,
 }
 
 impl ScreencastThread {
     pub async fn new(
         wayland_helper: WaylandHelper,
         capture_source: CaptureSource,
+        region: Option<Rect>,
         overlay_cursor: bool,
+        consumer: Consumer,
     ) -> anyhow::Result<Self> {
         let (tx, rx) = oneshot::channel();
         let (thread_stop_tx, thread_stop_rx) = pipewire::channel::channel::<()>();
-        std::thread::spawn(move || {
-            match start_stream(wayland_helper, capture_source, overlay_cursor) {
-                Ok((loop_, _stream, _listener, _context, node_id_rx)) => {
+        let colorimetry = Colorimetry::for_source(&capture_source);
+        let paused = Arc::new(Mutex::new(None));
+        let thread_paused = paused.clone();
+        let pacing = Arc::new(Mutex::new(PacingStats::default()));
+        let thread_pacing = pacing.clone();
+        let (stopped_tx, stopped_rx) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            match start_stream(
+                wayland_helper,
+                capture_source,
+                region,
+                overlay_cursor,
+                colorimetry,
+                thread_paused,
+                thread_pacing,
+                consumer,
+            ) {
+                Ok((loop_, stream, listener, context, node_id_rx)) => {
                     tx.send(Ok(node_id_rx)).unwrap();
                     let weak_loop = loop_.downgrade();
-                    let _receiver = thread_stop_rx.attach(loop_.loop_(), move |()| {
+                    // No frames are pulled once the loop quits
+                    let receiver = thread_stop_rx.attach(loop_.loop_(), move |()| {
                         weak_loop.upgrade().unwrap().quit();
                     });
                     loop_.run();
+                    drop(receiver);
+                    shut_down(loop_, stream, listener, context);
+                    let _ = stopped_tx.send(());
                 }
                 Err(err) => tx.send(Err(err)).unwrap(),
             }
@@ -52,7 +188,12 @@ impl ScreencastThread {
         Ok(Self {
             // XXX can second unwrap fail?
             node_id: rx.await.unwrap()?.await.unwrap()?,
+            colorimetry,
+            paused,
+            pacing,
             thread_stop_tx,
+            stopped_rx: Mutex::new(stopped_rx),
+            thread,
         })
     }
 
@@ -60,11 +201,88 @@ impl ScreencastThread {
         self.node_id
     }
 
+    /// Colorimetry of the captured source
+    pub fn colorimetry(&self) -> Colorimetry {
+        self.colorimetry
+    }
+
+    /// Stop capturing, and send `frame` until resumed. The stream stays
+    /// connected, so consumers don't need to renegotiate.
+    pub fn pause(&self, frame: PausedFrame) {
+        *self.paused.lock().unwrap() = Some(frame);
+    }
+
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = None;
+    }
+
+    pub fn pacing(&self) -> PacingStats {
+        *self.pacing.lock().unwrap()
+    }
+
+    /// Stop the thread, which tears down its stream.
+    ///
+    /// Returns at once. The thread is joined apart, so a stuck thread only leaks rather
+    /// than holding up the session's `Close`.
     pub fn stop(self) {
-        let _ = self.thread_stop_tx.send(());
+        let Self {
+            node_id,
+            thread_stop_tx,
+            stopped_rx,
+            thread,
+            ..
+        } = self;
+        log::debug!("stream {node_id}: stopping");
+        if thread_stop_tx.send(()).is_err() {
+            log::error!("stream {node_id}: thread already exited");
+        }
+        let stopped_rx = stopped_rx.into_inner().unwrap();
+        std::thread::spawn(move || match stopped_rx.recv_timeout(STOP_TIMEOUT) {
+            Ok(()) => {
+                let _ = thread.join();
+                log::debug!("stream {node_id}: thread joined");
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                // Most likely waiting on a capture the compositor never completes
+                log::error!(
+                    "stream {node_id}: not torn down within {STOP_TIMEOUT:?}, leaking its thread"
+                );
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                log::error!("stream {node_id}: thread exited without tearing down its stream");
+            }
+        });
     }
 }
 
+/// Tear down a stream whose loop has quit, in order: return its buffers to PipeWire,
+/// disconnect it, and release its screencopy session and Wayland buffers with its data.
+fn shut_down(
+    loop_: pipewire::main_loop::MainLoop,
+    stream: pipewire::stream::Stream,
+    listener: pipewire::stream::StreamListener<StreamData>,
+    context: pipewire::context::Context,
+) {
+    let node_id = stream.node_id();
+    log::debug!("stream {node_id}: deactivating");
+    if let Err(err) = stream.set_active(false) {
+        log::error!("stream {node_id}: failed to deactivate: {err}");
+    }
+    // Buffers are removed as the stream disconnects, which destroys their `wl_buffer`s
+    // and closes their descriptors
+    log::debug!("stream {node_id}: disconnecting");
+    if let Err(err) = stream.disconnect() {
+        log::error!("stream {node_id}: failed to disconnect: {err}");
+    }
+    // Drops the screencopy session and the rescale buffer
+    log::debug!("stream {node_id}: releasing capture session");
+    drop(listener);
+    drop(stream);
+    drop(context);
+    drop(loop_);
+    log::debug!("stream {node_id}: torn down");
+}
+
 struct StreamData {
     dmabuf_helper: Option<DmabufHelper>,
     wayland_helper: WaylandHelper,
@@ -72,7 +290,42 @@ struct StreamData {
     session: Session,
     width: u32,
     height: u32,
+    colorimetry: Colorimetry,
+    // Consumer may negotiate BGRA
+    legacy: bool,
+    // Consumer negotiated BGRA, so captured RGBA frames are swizzled
+    swizzle: bool,
+    paused: Arc<Mutex<Option<PausedFrame>>>,
+    // Buffer captured to when the source's size no longer matches the stream's, or
+    // when sharing a region of it
+    rescale_buffer: Option<RescaleBuffer>,
     node_id_tx: Option<oneshot::Sender<Result<u32, anyhow::Error>>>,
+    capture_source: CaptureSource,
+    // `media.name` of the node, updated when a toplevel's title changes
+    media_name: String,
+    // Part of the output shared, in logical coordinates relative to it
+    region: Option<Rect>,
+    // Size the source was first captured at
+    source_size: (u32, u32),
+    // Shortest time between frames at the negotiated frame rate
+    frame_interval: Option<Duration>,
+    // Presentation time of the last frame sent, and when its capture completed
+    last_frame: Option<(Duration, Instant)>,
+    pacing: Arc<Mutex<PacingStats>>,
+    seq: u64,
+}
+
+struct RescaleBuffer {
+    fd: OwnedFd,
+    wl_buffer: wl_buffer::WlBuffer,
+    width: u32,
+    height: u32,
+}
+
+impl Drop for RescaleBuffer {
+    fn drop(&mut self) {
+        self.wl_buffer.destroy();
+    }
 }
 
 impl StreamData {
@@ -141,6 +394,36 @@ impl StreamData {
         if let Some(pod) = pod {
             let value = PodDeserializer::deserialize_from::<pod::Value>(pod.as_bytes());
             if let Ok((_, pod::Value::Object(object))) = &value {
+                log::debug!("stream {}: negotiated {:?}", stream.node_id(), object);
+                let video_format = object
+                    .properties
+                    .iter()
+                    .find(|p| p.key == spa_sys::SPA_FORMAT_VIDEO_format)
+                    .and_then(|p| match p.value {
+                        pod::Value::Id(Id(format)) => Some(format),
+                        _ => None,
+                    });
+                self.swizzle = video_format == Some(spa_sys::SPA_VIDEO_FORMAT_BGRA);
+                if self.swizzle {
+                    self.negotiated_legacy(stream);
+                    return;
+                }
+                // Variable rate streams have a framerate of 0 and a max framerate
+                self.frame_interval = [
+                    spa_sys::SPA_FORMAT_VIDEO_framerate,
+                    spa_sys::SPA_FORMAT_VIDEO_maxFramerate,
+                ]
+                .into_iter()
+                .find_map(|key| {
+                    let property = object.properties.iter().find(|p| p.key == key)?;
+                    match property.value {
+                        pod::Value::Fraction(spa::utils::Fraction { num, denom }) if num > 0 => {
+                            Some(Duration::from_secs_f64(f64::from(denom) / f64::from(num)))
+                        }
+                        _ => None,
+                    }
+                });
+
                 if let Some(modifier_prop) = object
                     .properties
                     .iter()
@@ -175,6 +458,7 @@ impl StreamData {
                                 plane_count,
                                 self.dmabuf_helper.as_ref(),
                                 Some(modifier),
+                                self.colorimetry,
                             );
                             let mut params: Vec<_> = params
                                 .iter()
@@ -191,6 +475,34 @@ impl StreamData {
         }
     }
 
+    // Stream to a consumer that negotiated BGRA, which is offered to all so those without
+    // RGBA are told why they can't stream, rather than getting no frames
+    fn negotiated_legacy(&mut self, stream: &StreamRef) {
+        if !self.legacy {
+            let message = "consumer doesn't support the RGBA video format, only BGRA, \
+                which needs `screencast.legacy_consumers`";
+            log::error!("stream {}: {message}", stream.node_id());
+            set_error(stream, message);
+            return;
+        }
+        log::warn!(
+            "stream {}: consumer only supports BGRA, streaming at up to {LEGACY_FRAMERATE} fps",
+            stream.node_id()
+        );
+        self.frame_interval = Some(Duration::from_secs(1) / LEGACY_FRAMERATE);
+        let params = [
+            buffers(self.width, self.height, 1, true),
+            meta_header_param(),
+        ];
+        let mut params: Vec<_> = params
+            .iter()
+            .map(|x| Pod::from_bytes(x.as_slice()).unwrap())
+            .collect();
+        if let Err(err) = stream.update_params(&mut params) {
+            log::error!("failed to update pipewire params: {}", err);
+        }
+    }
+
     fn add_buffer(&mut self, _stream: &StreamRef, buffer: *mut pw_buffer) {
         let buf = unsafe { &mut *(*buffer).buffer };
         let datas = unsafe { slice::from_raw_parts_mut(buf.datas, buf.n_datas as usize) };
@@ -246,6 +558,12 @@ impl StreamData {
             chunk.stride = 4 * self.width as i32;
         }
 
+        {
+            let mut pacing = self.pacing.lock().unwrap();
+            pacing.size = (self.width, self.height);
+            pacing.dmabuf = datas[0].type_ == spa_sys::SPA_DATA_DmaBuf;
+        }
+
         let user_data = Box::into_raw(Box::new(wl_buffer)) as *mut c_void;
         unsafe { (*buffer).user_data = user_data };
     }
@@ -265,23 +583,292 @@ impl StreamData {
     }
 
     fn process(&mut self, stream: &StreamRef) {
+        self.update_media_name(stream);
         let buffer = unsafe { stream.dequeue_raw_buffer() };
         if !buffer.is_null() {
-            let wl_buffer = unsafe { &*((*buffer).user_data as *const wl_buffer::WlBuffer) };
-            if let Err(err) = block_on(self.session.capture_wl_buffer(wl_buffer)) {
+            let paused = *self.paused.lock().unwrap();
+            match paused {
+                // Buffers keep the last frames captured into them
+                Some(PausedFrame::Frozen) => {}
+                Some(PausedFrame::Slate) => {
+                    let buf = unsafe { &*(*buffer).buffer };
+                    let data = unsafe { &*buf.datas };
+                    // TODO: Fill dmabufs, which can't be mapped here; they stay frozen
+                    if data.type_ == spa_sys::SPA_DATA_MemFd {
+                        match unsafe { map_buffer(buffer) } {
+                            Ok(mut mmap) => fill_slate(&mut mmap),
+                            Err(err) => log::error!("failed to map buffer for slate: {}", err),
+                        }
+                    }
+                }
+                None => {
+                    self.wait_for_frame_interval();
+                    match self.capture(buffer) {
+                        Some(present_time) => {
+                            if self.swizzle {
+                                // Only shm is negotiated with BGRA, so this is mappable
+                                match unsafe { map_buffer(buffer) } {
+                                    Ok(mut mmap) => swap_red_blue(&mut mmap),
+                                    Err(err) => log::error!("failed to map buffer: {}", err),
+                                }
+                            }
+                            self.frame_presented(buffer, present_time)
+                        }
+                        None => self.pacing.lock().unwrap().dropped += 1,
+                    }
+                }
+            }
+            unsafe { stream.queue_raw_buffer(buffer) };
+        }
+    }
+
+    fn update_media_name(&mut self, stream: &StreamRef) {
+        let media_name = source_name(&self.wayland_helper, &self.capture_source);
+        if media_name == self.media_name {
+            return;
+        }
+        let properties = pipewire::properties::properties! {
+            "media.name" => media_name.as_str(),
+        };
+        unsafe {
+            pipewire::sys::pw_stream_update_properties(
+                stream.as_raw_ptr(),
+                &(*properties.as_raw_ptr()).dict,
+            );
+        }
+        self.media_name = media_name;
+    }
+
+    // Wait until the next frame presented is due at the negotiated frame rate.
+    //
+    // Captures complete when the compositor presents a frame, so starting the next one
+    // half a presentation early lines it up with the next display frame due, rather
+    // than with whichever follows a fixed timer.
+    fn wait_for_frame_interval(&self) {
+        let (Some(frame_interval), Some((_, completed))) = (self.frame_interval, self.last_frame)
+        else {
+            return;
+        };
+        let presentation_interval = self.pacing.lock().unwrap().presentation_interval;
+        let wait = frame_interval.saturating_sub(presentation_interval / 2);
+        if let Some(remaining) = (completed + wait).checked_duration_since(Instant::now()) {
+            std::thread::sleep(remaining);
+        }
+    }
+
+    // Record the timing of a captured frame, and timestamp the buffer with it
+    fn frame_presented(&mut self, buffer: *mut pw_buffer, present_time: Duration) {
+        {
+            let mut pacing = self.pacing.lock().unwrap();
+            pacing.frames += 1;
+            if let Some((last_present_time, _)) = self.last_frame {
+                if let Some(interval) = present_time.checked_sub(last_present_time) {
+                    pacing.record(interval);
+                }
+            }
+        }
+        self.last_frame = Some((present_time, Instant::now()));
+        self.seq += 1;
+        // Consumers time frames by when they were shown, not when capture completed
+        if let Some(header) = unsafe { meta_header(buffer) } {
+            header.flags = 0;
+            header.offset = 0;
+            header.pts = present_time.as_nanos() as i64;
+            header.dts_offset = 0;
+            header.seq = self.seq;
+        }
+    }
+
+    // Capture into `buffer`, returning the time the frame was presented
+    fn capture(&mut self, buffer: *mut pw_buffer) -> Option<Duration> {
+        if let Some(region) = self.region {
+            return self.capture_region(buffer, region);
+        }
+        // A toplevel moved to an output with another scale is rendered at a new size
+        let size = self
+            .session
+            .buffer_size()
+            .unwrap_or((self.width, self.height));
+        if size != (self.width, self.height) {
+            return self.capture_rescaled(buffer, size);
+        }
+
+        let wl_buffer = unsafe { &*((*buffer).user_data as *const wl_buffer::WlBuffer) };
+        match block_on(self.session.capture_wl_buffer(wl_buffer)) {
+            Ok(frame) => frame.present_time,
+            Err(err) => {
                 log::error!("screencopy failed: {:?}", err);
                 // TODO terminate screencasting?
+                None
+            }
+        }
+    }
+
+    // Capture at the source's current size, and resample to the stream's
+    fn capture_rescaled(
+        &mut self,
+        buffer: *mut pw_buffer,
+        (width, height): (u32, u32),
+    ) -> Option<Duration> {
+        let data = unsafe { &*(*(*buffer).buffer).datas };
+        if data.type_ != spa_sys::SPA_DATA_MemFd {
+            // Only shm buffers are resampled; dmabuf frames are dropped until the size
+            // matches again
+            // TODO: Renegotiate the stream size for dmabuf consumers
+            log::warn!(
+                "source is {}x{}, but dmabuf stream is {}x{}",
+                width,
+                height,
+                self.width,
+                self.height
+            );
+            return None;
+        }
+        let (source, present_time) = self.capture_image((width, height))?;
+        match unsafe { map_buffer(buffer) } {
+            Ok(mut mmap) => buffer::resample_into(&source, &mut mmap, self.width, self.height),
+            Err(err) => {
+                log::error!("failed to map buffer for rescaling: {}", err);
+                return None;
+            }
+        }
+        present_time
+    }
+
+    // Capture the whole output, and copy the shared region out of it. The region is
+    // clipped to the output if the output shrinks, and scaled to the stream's size if
+    // the output's scale changes.
+    fn capture_region(&mut self, buffer: *mut pw_buffer, region: Rect) -> Option<Duration> {
+        let size = self.session.buffer_size().unwrap_or(self.source_size);
+        let Some((x, y, width, height)) =
+            region_crop(&self.wayland_helper, &self.capture_source, region, size)
+        else {
+            log::warn!("shared region is outside its output");
+            return None;
+        };
+        let (source, present_time) = self.capture_image(size)?;
+        let cropped = image::imageops::crop_imm(&source, x, y, width, height).to_image();
+        match unsafe { map_buffer(buffer) } {
+            Ok(mut mmap) => {
+                if (width, height) == (self.width, self.height) {
+                    mmap[..cropped.len()].copy_from_slice(&cropped);
+                } else {
+                    buffer::resample_into(&cropped, &mut mmap, self.width, self.height);
+                }
+            }
+            Err(err) => {
+                log::error!("failed to map buffer for region: {}", err);
+                return None;
             }
-            unsafe { stream.queue_raw_buffer(buffer) };
         }
+        present_time
+    }
+
+    // Capture the source at `width`x`height` to a buffer of our own, returning the image
+    // and the time it was presented
+    fn capture_image(
+        &mut self,
+        (width, height): (u32, u32),
+    ) -> Option<(image::RgbaImage, Option<Duration>)> {
+        if self
+            .rescale_buffer
+            .as_ref()
+            .map_or(true, |x| (x.width, x.height) != (width, height))
+        {
+            let fd = buffer::create_memfd(width, height);
+            let wl_buffer = self.wayland_helper.create_shm_buffer(
+                &fd,
+                width,
+                height,
+                width * 4,
+                wl_shm::Format::Abgr8888,
+            );
+            self.rescale_buffer = Some(RescaleBuffer {
+                fd,
+                wl_buffer,
+                width,
+                height,
+            });
+        }
+        let rescale_buffer = self.rescale_buffer.as_ref().unwrap();
+        let frame = match block_on(self.session.capture_wl_buffer(&rescale_buffer.wl_buffer)) {
+            Ok(frame) => frame,
+            Err(err) => {
+                log::error!("screencopy failed: {:?}", err);
+                return None;
+            }
+        };
+        let source = match unsafe { memmap2::Mmap::map(rescale_buffer.fd.as_raw_fd()) } {
+            Ok(mmap) => image::RgbaImage::from_raw(width, height, mmap.to_vec()),
+            Err(err) => {
+                log::error!("failed to map capture for rescaling: {}", err);
+                return None;
+            }
+        };
+        let Some(source) = source else {
+            log::error!("capture for rescaling had incorrect size");
+            return None;
+        };
+        Some((source, frame.present_time))
     }
 }
 
+/// Pixels of a capture of `width`x`height` showing `region` of the output, as
+/// `(x, y, width, height)`, clipped to the capture
+fn region_crop(
+    wayland_helper: &WaylandHelper,
+    capture_source: &CaptureSource,
+    region: Rect,
+    (width, height): (u32, u32),
+) -> Option<(u32, u32, u32, u32)> {
+    let CaptureSource::Output(output) = capture_source else {
+        return None;
+    };
+    let (logical_width, logical_height) = wayland_helper.output_info(output)?.logical_size?;
+    if logical_width <= 0 || logical_height <= 0 {
+        return None;
+    }
+    let scale_x = f64::from(width) / f64::from(logical_width);
+    let scale_y = f64::from(height) / f64::from(logical_height);
+    let clamp = |value: i32, scale: f64, max: u32| {
+        ((f64::from(value) * scale).round().max(0.0) as u32).min(max)
+    };
+    let left = clamp(region.left, scale_x, width);
+    let top = clamp(region.top, scale_y, height);
+    let right = clamp(region.right, scale_x, width);
+    let bottom = clamp(region.bottom, scale_y, height);
+    (left < right && top < bottom).then(|| (left, top, right - left, bottom - top))
+}
+
+// Header metadata of a buffer, if the consumer allocated it
+unsafe fn meta_header<'a>(buffer: *mut pw_buffer) -> Option<&'a mut spa_sys::spa_meta_header> {
+    let buf = &*(*buffer).buffer;
+    let metas = slice::from_raw_parts(buf.metas, buf.n_metas as usize);
+    let meta = metas.iter().find(|meta| {
+        meta.type_ == spa_sys::SPA_META_Header
+            && meta.size as usize >= mem::size_of::<spa_sys::spa_meta_header>()
+    })?;
+    (meta.data as *mut spa_sys::spa_meta_header).as_mut()
+}
+
+// Map the first data of a shm buffer
+unsafe fn map_buffer(buffer: *mut pw_buffer) -> io::Result<memmap2::MmapMut> {
+    let data = &*(*(*buffer).buffer).datas;
+    memmap2::MmapOptions::new()
+        .len(data.maxsize as usize)
+        .map_mut(data.fd as RawFd)
+}
+
 #[allow(clippy::type_complexity)]
 fn start_stream(
     wayland_helper: WaylandHelper,
     capture_source: CaptureSource,
+    region: Option<Rect>,
     overlay_cursor: bool,
+    colorimetry: Colorimetry,
+    paused: Arc<Mutex<Option<PausedFrame>>>,
+    pacing: Arc<Mutex<PacingStats>>,
+    consumer: Consumer,
 ) -> anyhow::Result<(
     pipewire::main_loop::MainLoop,
     pipewire::stream::Stream,
@@ -297,24 +884,30 @@ fn start_stream(
 
     let (node_id_tx, node_id_rx) = oneshot::channel();
 
-    let (width, height) =
+    let source_size =
         match block_on(wayland_helper.capture_source_shm(capture_source.clone(), overlay_cursor)) {
             Some(frame) => (frame.width, frame.height),
             None => return Err(anyhow::anyhow!("failed to use shm capture to get size")),
         };
+    let (width, height) = match region {
+        Some(region) => region_crop(&wayland_helper, &capture_source, region, source_size)
+            .map(|(_, _, width, height)| (width, height))
+            .ok_or_else(|| anyhow::anyhow!("shared region is outside its output"))?,
+        None => source_size,
+    };
 
-    let dmabuf_helper = wayland_helper.dmabuf();
+    // Regions are cropped on the CPU, so need buffers that can be mapped
+    let dmabuf_helper = if region.is_some() {
+        None
+    } else {
+        wayland_helper.dmabuf()
+    };
 
-    let stream = pipewire::stream::Stream::new(
-        &core,
-        &name,
-        pipewire::properties::properties! {
-            "media.class" => "Video/Source",
-            "node.name" => "cosmic-screenshot", // XXX
-        },
-    )?;
+    let media_name = source_name(&wayland_helper, &capture_source);
+    let stream =
+        pipewire::stream::Stream::new(&core, &name, node_properties(&media_name, &consumer))?;
 
-    let initial_params = params(width, height, 1, dmabuf_helper.as_ref(), None);
+    let initial_params = params(width, height, 1, dmabuf_helper.as_ref(), None, colorimetry);
     let mut initial_params: Vec<_> = initial_params
         .iter()
         .map(|x| Pod::from_bytes(x.as_slice()).unwrap())
@@ -329,7 +922,7 @@ fn start_stream(
         &mut initial_params,
     )?;
 
-    let session = wayland_helper.capture_source_session(capture_source, overlay_cursor);
+    let session = wayland_helper.capture_source_session(capture_source.clone(), overlay_cursor);
 
     let data = StreamData {
         wayland_helper,
@@ -339,7 +932,20 @@ fn start_stream(
         modifier: gbm::Modifier::Linear,
         width,
         height,
+        colorimetry,
+        legacy: consumer.legacy,
+        swizzle: false,
+        paused,
+        rescale_buffer: None,
         node_id_tx: Some(node_id_tx),
+        capture_source,
+        media_name,
+        region,
+        source_size,
+        frame_interval: None,
+        last_frame: None,
+        pacing,
+        seq: 0,
     };
 
     let listener = stream
@@ -354,25 +960,84 @@ fn start_stream(
     Ok((loop_, stream, listener, context, node_id_rx))
 }
 
+/// Properties of the stream's node, identifying it in tools like `pw-top` and to
+/// session manager policy
+fn node_properties(media_name: &str, consumer: &Consumer) -> pipewire::properties::Properties {
+    let description = format!("{media_name} shared with {}", consumer.name);
+    pipewire::properties::properties! {
+        "media.class" => "Video/Source",
+        "node.name" => "cosmic-screenshot", // XXX
+        "media.name" => media_name,
+        "node.description" => description.as_str(),
+        "portal.app_id" => consumer.app_id.as_str(),
+    }
+}
+
+/// Name of the captured output or title of the captured toplevel
+pub fn source_name(wayland_helper: &WaylandHelper, capture_source: &CaptureSource) -> String {
+    match capture_source {
+        CaptureSource::Output(output) => wayland_helper
+            .output_info(output)
+            .and_then(|info| info.name)
+            .unwrap_or_default(),
+        CaptureSource::Toplevel(toplevel) => wayland_helper
+            .toplevel_info(toplevel)
+            .map(|info| info.title)
+            .unwrap_or_default(),
+    }
+}
+
 fn params(
     width: u32,
     height: u32,
     blocks: u32,
     dmabuf: Option<&DmabufHelper>,
     fixated_modifier: Option<gbm::Modifier>,
+    colorimetry: Colorimetry,
 ) -> Vec<Vec<u8>> {
     [
-        Some(buffers(width, height, blocks)),
-        fixated_modifier.map(|x| format(width, height, None, Some(x))),
+        Some(buffers(width, height, blocks, false)),
+        Some(meta_header_param()),
+        fixated_modifier.map(|x| format(width, height, None, Some(x), colorimetry)),
         // Favor dmabuf over shm by listing it first
-        dmabuf.map(|x| format(width, height, Some(x), None)),
-        Some(format(width, height, None, None)),
+        dmabuf.map(|x| format(width, height, Some(x), None, colorimetry)),
+        Some(format(width, height, None, None, colorimetry)),
+        Some(legacy_format(width, height)),
     ]
     .into_iter()
     .flatten()
     .collect()
 }
 
+// Opaque dark gray RGBA pixels, shown while sharing is paused
+fn fill_slate(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&[0x24, 0x24, 0x24, 0xff]);
+    }
+}
+
+// Convert RGBA pixels to BGRA, in place
+fn swap_red_blue(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+// Fail the stream, with `message` for the consumer
+fn set_error(stream: &StreamRef, message: &str) {
+    let Ok(message) = CString::new(message) else {
+        return;
+    };
+    unsafe {
+        pipewire::sys::pw_stream_set_error(
+            stream.as_raw_ptr(),
+            -rustix::io::Errno::INVAL.raw_os_error(),
+            b"%s\0".as_ptr().cast(),
+            message.as_ptr(),
+        );
+    }
+}
+
 fn value_to_bytes(value: pod::Value) -> Vec<u8> {
     let mut bytes = Vec::new();
     let mut cursor = io::Cursor::new(&mut bytes);
@@ -380,7 +1045,31 @@ fn value_to_bytes(value: pod::Value) -> Vec<u8> {
     bytes
 }
 
-fn buffers(width: u32, height: u32, blocks: u32) -> Vec<u8> {
+// With `single_shm`, for legacy consumers, a single shm buffer
+fn buffers(width: u32, height: u32, blocks: u32, single_shm: bool) -> Vec<u8> {
+    let data_type = if single_shm {
+        pod::Value::Int(1 << spa_sys::SPA_DATA_MemFd)
+    } else {
+        pod::Value::Choice(pod::ChoiceValue::Int(spa::utils::Choice(
+            spa::utils::ChoiceFlags::empty(),
+            spa::utils::ChoiceEnum::Flags {
+                default: 1 << spa_sys::SPA_DATA_DmaBuf, // ?
+                flags: vec![1 << spa_sys::SPA_DATA_MemFd, 1 << spa_sys::SPA_DATA_DmaBuf],
+            },
+        )))
+    };
+    let count = if single_shm {
+        pod::Value::Int(1)
+    } else {
+        pod::Value::Choice(pod::ChoiceValue::Int(spa::utils::Choice(
+            spa::utils::ChoiceFlags::empty(),
+            spa::utils::ChoiceEnum::Range {
+                default: 4,
+                min: 1,
+                max: 32,
+            },
+        )))
+    };
     value_to_bytes(pod::Value::Object(pod::Object {
         type_: spa_sys::SPA_TYPE_OBJECT_ParamBuffers,
         id: spa_sys::SPA_PARAM_Buffers,
@@ -388,13 +1077,7 @@ fn buffers(width: u32, height: u32, blocks: u32) -> Vec<u8> {
             pod::Property {
                 key: spa_sys::SPA_PARAM_BUFFERS_dataType,
                 flags: pod::PropertyFlags::empty(),
-                value: pod::Value::Choice(pod::ChoiceValue::Int(spa::utils::Choice(
-                    spa::utils::ChoiceFlags::empty(),
-                    spa::utils::ChoiceEnum::Flags {
-                        default: 1 << spa_sys::SPA_DATA_DmaBuf, // ?
-                        flags: vec![1 << spa_sys::SPA_DATA_MemFd, 1 << spa_sys::SPA_DATA_DmaBuf],
-                    },
-                ))),
+                value: data_type,
             },
             pod::Property {
                 key: spa_sys::SPA_PARAM_BUFFERS_size,
@@ -419,14 +1102,27 @@ fn buffers(width: u32, height: u32, blocks: u32) -> Vec<u8> {
             pod::Property {
                 key: spa_sys::SPA_PARAM_BUFFERS_buffers,
                 flags: pod::PropertyFlags::empty(),
-                value: pod::Value::Choice(pod::ChoiceValue::Int(spa::utils::Choice(
-                    spa::utils::ChoiceFlags::empty(),
-                    spa::utils::ChoiceEnum::Range {
-                        default: 4,
-                        min: 1,
-                        max: 32,
-                    },
-                ))),
+                value: count,
+            },
+        ],
+    }))
+}
+
+// Header metadata on each buffer, carrying its presentation timestamp
+fn meta_header_param() -> Vec<u8> {
+    value_to_bytes(pod::Value::Object(pod::Object {
+        type_: spa_sys::SPA_TYPE_OBJECT_ParamMeta,
+        id: spa_sys::SPA_PARAM_Meta,
+        properties: vec![
+            pod::Property {
+                key: spa_sys::SPA_PARAM_META_type,
+                flags: pod::PropertyFlags::empty(),
+                value: pod::Value::Id(Id(spa_sys::SPA_META_Header)),
+            },
+            pod::Property {
+                key: spa_sys::SPA_PARAM_META_size,
+                flags: pod::PropertyFlags::empty(),
+                value: pod::Value::Int(mem::size_of::<spa_sys::spa_meta_header>() as i32),
             },
         ],
     }))
@@ -438,6 +1134,7 @@ fn format(
     height: u32,
     dmabuf: Option<&DmabufHelper>,
     fixated_modifier: Option<gbm::Modifier>,
+    colorimetry: Colorimetry,
 ) -> Vec<u8> {
     let mut properties = vec![
         pod::Property {
@@ -467,6 +1164,7 @@ fn format(
         },
         // TODO max framerate
     ];
+    properties.extend(colorimetry.properties());
     if let Some(modifier) = fixated_modifier {
         properties.push(pod::Property {
             key: spa_sys::SPA_FORMAT_VIDEO_modifier,
@@ -502,3 +1200,64 @@ fn format(
         properties,
     }))
 }
+
+// BGRA shm format at `LEGACY_FRAMERATE`, for old consumers without RGBA
+fn legacy_format(width: u32, height: u32) -> Vec<u8> {
+    let mut properties = vec![
+        pod::Property {
+            key: spa_sys::SPA_FORMAT_mediaType,
+            flags: pod::PropertyFlags::empty(),
+            value: pod::Value::Id(Id(spa_sys::SPA_MEDIA_TYPE_video)),
+        },
+        pod::Property {
+            key: spa_sys::SPA_FORMAT_mediaSubtype,
+            flags: pod::PropertyFlags::empty(),
+            value: pod::Value::Id(Id(spa_sys::SPA_MEDIA_SUBTYPE_raw)),
+        },
+        pod::Property {
+            key: spa_sys::SPA_FORMAT_VIDEO_format,
+            flags: pod::PropertyFlags::empty(),
+            value: pod::Value::Id(Id(spa_sys::SPA_VIDEO_FORMAT_BGRA)),
+        },
+        pod::Property {
+            key: spa_sys::SPA_FORMAT_VIDEO_size,
+            flags: pod::PropertyFlags::empty(),
+            value: pod::Value::Rectangle(spa::utils::Rectangle { width, height }),
+        },
+        pod::Property {
+            key: spa_sys::SPA_FORMAT_VIDEO_framerate,
+            flags: pod::PropertyFlags::empty(),
+            value: pod::Value::Fraction(spa::utils::Fraction {
+                num: LEGACY_FRAMERATE,
+                denom: 1,
+            }),
+        },
+    ];
+    properties.extend(Colorimetry::SRGB.properties());
+    value_to_bytes(pod::Value::Object(pod::Object {
+        type_: spa_sys::SPA_TYPE_OBJECT_Format,
+        id: spa_sys::SPA_PARAM_EnumFormat,
+        properties,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nodes_name_source_and_consumer() {
+        let consumer = Consumer {
+            app_id: "org.example.Meeting".to_string(),
+            name: "Meeting".to_string(),
+        };
+        let properties = node_properties("eDP-1", &consumer);
+        assert_eq!(properties.get("media.class"), Some("Video/Source"));
+        assert_eq!(properties.get("media.name"), Some("eDP-1"));
+        assert_eq!(
+            properties.get("node.description"),
+            Some("eDP-1 shared with Meeting")
+        );
+        assert_eq!(properties.get("portal.app_id"), Some("org.example.Meeting"));
+    }
+}
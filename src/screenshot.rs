@@ -1,34 +1,48 @@
 #![allow(dead_code, unused_variables)]
 
+use anyhow::Context;
 use cosmic::cosmic_config::CosmicConfigEntry;
 use cosmic::iced::clipboard::mime::AsMimeTypes;
-use cosmic::iced::keyboard::{key::Named, Key};
+use cosmic::iced::keyboard::{key::Named, Key, Modifiers};
 use cosmic::iced::wayland::actions::data_device::ActionInner;
-use cosmic::iced::wayland::actions::layer_surface::{IcedOutput, SctkLayerSurfaceSettings};
-use cosmic::iced::{window, Limits};
+use cosmic::iced::wayland::actions::layer_surface::IcedOutput;
+use cosmic::iced::window;
 use cosmic::iced_core::Length;
 use cosmic::iced_runtime::clipboard;
 use cosmic::iced_sctk::commands::data_device;
 use cosmic::iced_sctk::commands::layer_surface::{destroy_layer_surface, get_layer_surface};
 use cosmic::widget::horizontal_space;
-use cosmic_client_toolkit::sctk::shell::wlr_layer::{Anchor, KeyboardInteractivity, Layer};
 use image::RgbaImage;
 use rustix::fd::AsFd;
 use std::borrow::Cow;
 use std::num::NonZeroU32;
+use std::process::Stdio;
 use std::sync::Arc;
-use std::{collections::HashMap, fmt::Debug, path::PathBuf};
+use std::time::Duration;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
 use tokio::sync::mpsc::Sender;
 
 use wayland_client::protocol::wl_output::{self, WlOutput};
 use zbus::zvariant;
 
 use crate::app::{CosmicPortal, OutputState};
-use crate::config::{self, screenshot::ImageSaveLocation};
+use crate::config::{self, screenshot::ImageSaveLocation, shortcuts::Action as ShortcutAction};
+use crate::responder::Responder;
+use crate::shortcuts;
 use crate::wayland::{CaptureSource, WaylandHelper};
 use crate::widget::{keyboard_wrapper::KeyboardWrapper, rectangle_selection::DragState};
 use crate::{fl, subscription, PortalResponse};
 
+// Logical pixels the selected rectangle moves or grows by with each arrow key press
+const NUDGE_STEP: i32 = 16;
+// Config of the panel's clock, whose 12 or 24 hour choice file names follow
+const CLOCK_CONFIG_ID: &str = "com.system76.CosmicAppletTime";
+const CLOCK_CONFIG_VERSION: u64 = 1;
+
 // TODO save to /run/user/$UID/doc/ with document portal fuse filesystem?
 #[derive(Clone)]
 pub struct DndCommand(pub Arc<Box<dyn Send + Sync + Fn() -> ActionInner>>);
@@ -39,7 +53,7 @@ impl Debug for DndCommand {
     }
 }
 
-#[derive(zvariant::DeserializeDict, zvariant::Type, Clone, Debug)]
+#[derive(zvariant::DeserializeDict, zvariant::Type, Clone, Debug, Default)]
 #[zvariant(signature = "a{sv}")]
 pub struct ScreenshotOptions {
     modal: Option<bool>,
@@ -48,42 +62,73 @@ pub struct ScreenshotOptions {
     ///
     /// Defaults to false
     choose_destination: Option<bool>,
+    /// Custom value requesting text recognition of the interactively captured region. The text
+    /// is copied to the clipboard and returned in `text`.
+    ///
+    /// Defaults to false. Ignored if OCR is unavailable.
+    ocr: Option<bool>,
+    /// Custom value requesting a comparison with the previous interactive capture of the same
+    /// region. The difference heatmap is saved alongside, and returned in `comparison_uri`.
+    ///
+    /// Defaults to false
+    #[zvariant(rename = "com.system76.cosmic.compare")]
+    compare: Option<bool>,
 }
 
-#[derive(zvariant::SerializeDict, zvariant::Type)]
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
 #[zvariant(signature = "a{sv}")]
 pub struct ScreenshotResult {
     uri: String,
+    /// Custom value with the text recognized in the screenshot, if OCR was used
+    text: Option<String>,
+    /// Custom value with the difference from the previous capture, if it was compared
+    #[zvariant(rename = "com.system76.cosmic.comparison_uri")]
+    comparison_uri: Option<String>,
+    /// Custom value with the scale the screenshot was downscaled by to fit the configured
+    /// maximum dimension, if it was
+    #[zvariant(rename = "com.system76.cosmic.scale")]
+    scale: Option<f64>,
 }
 
+const TEXT_MIME_TYPES: &[&str] = &["text/plain;charset=utf-8", "text/plain", "UTF8_STRING"];
+
 struct ScreenshotBytes {
     bytes: Vec<u8>,
+    text: Option<String>,
 }
 
 impl ScreenshotBytes {
-    fn new(bytes: Vec<u8>) -> Self {
-        Self { bytes }
+    fn new(bytes: Vec<u8>, text: Option<String>) -> Self {
+        Self { bytes, text }
     }
 }
 
 impl AsMimeTypes for ScreenshotBytes {
     fn available(&self) -> std::borrow::Cow<'static, [String]> {
-        Cow::Owned(vec!["image/png".to_string()])
+        let mut mime_types = vec!["image/png".to_string()];
+        if self.text.is_some() {
+            mime_types.extend(TEXT_MIME_TYPES.iter().map(|x| x.to_string()));
+        }
+        Cow::Owned(mime_types)
     }
 
     fn as_bytes(&self, mime_type: &str) -> Option<std::borrow::Cow<'static, [u8]>> {
+        if TEXT_MIME_TYPES.contains(&mime_type) {
+            let text = self.text.as_ref()?;
+            return Some(Cow::Owned(text.as_bytes().to_vec()));
+        }
         Some(Cow::Owned(self.bytes.clone()))
     }
 }
 
-#[derive(zvariant::SerializeDict, zvariant::Type)]
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
 #[zvariant(signature = "a{sv}")]
 struct PickColorResult {
     color: (f64, f64, f64), // (ddd)
 }
 
 /// Logical Size and Position of a rectangle
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Rect {
     pub left: i32,
     pub top: i32,
@@ -92,7 +137,7 @@ pub struct Rect {
 }
 
 impl Rect {
-    fn intersect(&self, other: Rect) -> Option<Rect> {
+    pub fn intersect(&self, other: Rect) -> Option<Rect> {
         let left = self.left.max(other.left);
         let top = self.top.max(other.top);
         let right = self.right.min(other.right);
@@ -125,6 +170,24 @@ impl Rect {
     }
 }
 
+/// Geometry of an interactive capture, matched to compare before and after captures
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Region {
+    Output(String),
+    Rectangle(Rect),
+}
+
+impl Region {
+    // Windows have no stable geometry to match
+    fn for_choice(choice: &Choice) -> Option<Self> {
+        match choice {
+            Choice::Output(name) => Some(Region::Output(name.clone())),
+            Choice::Rectangle(r, _) => Some(Region::Rectangle(*r)),
+            Choice::Window(..) => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct RectDimension {
     width: NonZeroU32,
@@ -141,6 +204,67 @@ impl Screenshot {
         Self { wayland_helper, tx }
     }
 
+    // Outputs that can be captured, with their logical positions and names
+    fn outputs(&self) -> Vec<(wl_output::WlOutput, (i32, i32), String)> {
+        let mut outputs = Vec::new();
+        for output in self.wayland_helper.outputs() {
+            let Some(info) = self.wayland_helper.output_info(&output) else {
+                log::warn!("Output {:?} has no info", output);
+                continue;
+            };
+            let Some(name) = info.name.clone() else {
+                log::warn!("Output {:?} has no name", output);
+                continue;
+            };
+            let Some(pos) = info.logical_position else {
+                log::warn!("Output {:?} has no position", output);
+                continue;
+            };
+            outputs.push((output, pos, name));
+        }
+        outputs
+    }
+
+    /// Let the user select a rectangle of the screen with the interactive screenshot
+    /// overlay, returning it in global logical coordinates
+    pub async fn select_region(
+        &self,
+        handle: zvariant::ObjectPath<'static>,
+        app_id: &str,
+    ) -> Option<Rect> {
+        let outputs = self.outputs();
+        let output_images = self
+            .interactive_output_images(outputs, app_id)
+            .await
+            .inspect_err(|err| log::error!("Failed to capture outputs for region: {err}"))
+            .ok()?;
+        // Only the region is used, but the overlay expects somewhere to send a response
+        let (tx, _rx) = Responder::channel();
+        let (region_tx, mut region_rx) = tokio::sync::mpsc::channel(1);
+        let args = Args {
+            handle,
+            app_id: app_id.to_string(),
+            parent_window: String::new(),
+            options: ScreenshotOptions::default(),
+            output_images,
+            toplevel_images: HashMap::new(),
+            tx,
+            choice: Choice::Rectangle(Rect::default(), DragState::default()),
+            location: ImageSaveLocation::Clipboard,
+            action: Action::ReturnPath,
+            ocr_languages: Vec::new(),
+            recognizing: false,
+            compare: false,
+            region_tx: Some(region_tx),
+            pointer_seen: false,
+        };
+        if let Err(err) = self.tx.send(subscription::Event::Screenshot(args)).await {
+            log::error!("Failed to send screenshot event, {}", err);
+            return None;
+        }
+        region_rx.recv().await
+    }
+
     async fn interactive_toplevel_images(
         &self,
         outputs: Vec<(wl_output::WlOutput, (i32, i32), String)>,
@@ -162,6 +286,9 @@ impl Screenshot {
         Ok(map)
     }
 
+    // Captured before the overlay's surfaces are created, and without the cursor. The
+    // overlay shows these frozen frames as its background, and the screenshot is cropped
+    // from them, so neither live content nor the selection UI can end up in it.
     async fn interactive_output_images(
         &self,
         outputs: Vec<(wl_output::WlOutput, (i32, i32), String)>,
@@ -184,13 +311,15 @@ impl Screenshot {
     }
 
     pub fn save_rgba(img: &RgbaImage, path: &PathBuf) -> anyhow::Result<()> {
-        let mut encoder =
-            png::Encoder::new(std::fs::File::create(path)?, img.width(), img.height());
-        encoder.set_color(png::ColorType::Rgba);
-        encoder.set_depth(png::BitDepth::Eight);
-        let mut writer = encoder.write_header()?;
-        writer.write_image_data(img.as_raw())?;
-        Ok(())
+        crate::fs_util::write_atomic(path, 0o644, |file| {
+            let mut encoder = png::Encoder::new(file, img.width(), img.height());
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header()?;
+            writer.write_image_data(img.as_raw())?;
+            writer.finish()?;
+            Ok(())
+        })
     }
 
     pub fn save_rgba_to_buffer(img: &RgbaImage, buffer: &mut Vec<u8>) -> anyhow::Result<()> {
@@ -213,15 +342,7 @@ impl Screenshot {
             ImageSaveLocation::Clipboard => None,
             // ImageSaveLocation::Custom(path) => Some(path),
         }?;
-        let date_format =
-            time::macros::format_description!("[year]-[month]-[day]-[hour]-[minute]-[second]");
-
-        let name = format!(
-            "screenshot-{}.png",
-            time::OffsetDateTime::now_utc().format(&date_format).ok()?
-        );
-
-        path.push(name);
+        path.push(file_name(time::OffsetDateTime::now_utc(), clock_24h())?);
         Some(path)
     }
 
@@ -229,11 +350,12 @@ impl Screenshot {
         &self,
         outputs: Vec<(wl_output::WlOutput, (i32, i32), String)>,
         app_id: &str,
-    ) -> anyhow::Result<PathBuf> {
+        max_dimension: u32,
+    ) -> anyhow::Result<(PathBuf, Option<f64>)> {
         use ashpd::documents::Permission;
 
         let wayland_helper = self.wayland_helper.clone();
-        let (file, path) = async {
+        let ((file, path), scale) = async {
             let mut bounds_opt: Option<Rect> = None;
             let mut frames = Vec::with_capacity(outputs.len());
             for (output, (output_x, output_y), _) in outputs {
@@ -271,6 +393,9 @@ impl Screenshot {
                     .saturating_sub(bounds.top)
                     .try_into()
                     .unwrap_or_default();
+                if width == 0 || height == 0 {
+                    return Err(anyhow::anyhow!("captured outputs have no area"));
+                }
                 let mut image = image::RgbaImage::new(width, height);
                 for (frame, rect) in frames {
                     let frame_image = frame.image()?;
@@ -281,6 +406,10 @@ impl Screenshot {
                         rect.top.into(),
                     );
                 }
+                let (image, scale) = match limit_dimension(&image, max_dimension) {
+                    Some((image, scale)) => (image, Some(scale)),
+                    None => (image, None),
+                };
 
                 let mut file = tempfile::Builder::new()
                     .prefix("screenshot-")
@@ -293,7 +422,7 @@ impl Screenshot {
                     let mut writer = encoder.write_header()?;
                     writer.write_image_data(image.as_raw())?;
                 }
-                Ok(file.keep()?)
+                Ok((file.keep()?, scale))
             })
             .await?
         }
@@ -325,7 +454,7 @@ impl Screenshot {
         doc_path.push(&**doc_id);
         doc_path.push(path.file_name().unwrap());
 
-        Ok(doc_path)
+        Ok((doc_path, scale))
     }
 }
 
@@ -338,6 +467,13 @@ pub enum Msg {
     DragCommand(DndCommand),
     WindowChosen(String, usize),
     Location(usize),
+    Ocr,
+    OcrResult(Option<String>),
+    Compare(bool),
+    /// Move the selected rectangle by a step in a direction, or resize it, from the keyboard.
+    /// Starts a selection on the named output if there is none.
+    Nudge(String, (i32, i32), bool),
+    PointerSeen,
 }
 
 #[derive(Debug, Clone)]
@@ -377,10 +513,21 @@ pub struct Args {
     pub options: ScreenshotOptions,
     pub output_images: HashMap<String, Arc<RgbaImage>>,
     pub toplevel_images: HashMap<String, Vec<Arc<RgbaImage>>>,
-    pub tx: Sender<PortalResponse<ScreenshotResult>>,
+    pub tx: Responder<PortalResponse<ScreenshotResult>>,
     pub choice: Choice,
     pub location: ImageSaveLocation,
     pub action: Action,
+    /// OCR language packs, empty if OCR is unavailable
+    pub ocr_languages: Vec<String>,
+    pub recognizing: bool,
+    /// Compare with the previous capture of the same region
+    pub compare: bool,
+    /// Set when selecting a region to share in a screen cast, which is sent here instead
+    /// of being captured. Dropped unsent if the selection is cancelled.
+    pub region_tx: Option<Sender<Rect>>,
+    /// Whether the pointer has moved over the overlay since it was shown. If it hasn't, it may
+    /// be locked by a game or other app, and selection from the keyboard is offered.
+    pub pointer_seen: bool,
 }
 
 #[zbus::interface(name = "org.freedesktop.impl.portal.Screenshot")]
@@ -403,39 +550,35 @@ impl Screenshot {
         let config = config::Config::load().0.screenshot;
 
         // TODO create handle, show dialog
-        let mut outputs = Vec::new();
-        for output in self.wayland_helper.outputs() {
-            let Some(info) = self.wayland_helper.output_info(&output) else {
-                log::warn!("Output {:?} has no info", output);
-                continue;
-            };
-            let Some(name) = info.name.clone() else {
-                log::warn!("Output {:?} has no name", output);
-                continue;
-            };
-            let Some(pos) = info.logical_position else {
-                log::warn!("Output {:?} has no position", output);
-                continue;
-            };
-            outputs.push((output, pos, name));
+        let outputs = self.outputs();
+        if let Err(response) = crate::require_outputs(&outputs) {
+            // Or no output has been configured yet
+            log::error!(
+                "Screenshot for {app_id:?} failed: no usable outputs ({} known)",
+                self.wayland_helper.outputs().len()
+            );
+            return response;
         }
-        if outputs.is_empty() {
-            log::error!("No output");
-            return PortalResponse::Other;
-        };
 
         // if interactive, send image to be used by screenshot editor & await response via channel
         if options.interactive.unwrap_or_default() {
-            let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+            let (tx, mut rx) = Responder::channel();
             let first_output = &*outputs[0].2;
             let output_images = self
                 .interactive_output_images(outputs.clone(), app_id)
                 .await
                 .unwrap_or_default();
+            if output_images.is_empty() {
+                log::error!("Screenshot for {app_id:?} failed: no output could be captured");
+                return PortalResponse::Error(crate::NO_OUTPUTS.to_string());
+            }
             let toplevel_images = self
                 .interactive_toplevel_images(outputs.clone())
                 .await
                 .unwrap_or_default();
+            let ocr_languages = tokio::task::spawn_blocking(crate::ocr::languages)
+                .await
+                .unwrap_or_default();
             // TODO: Maybe replace config's Choice with Choice from this file
             let choice = match config.choice {
                 config::screenshot::Choice::Output(Some(output))
@@ -449,6 +592,7 @@ impl Screenshot {
                 }
                 config::screenshot::Choice::Window => Choice::Window(first_output.into(), None),
             };
+            let compare = options.compare.unwrap_or_default();
             if let Err(err) = self
                 .tx
                 .send(subscription::Event::Screenshot(Args {
@@ -467,6 +611,11 @@ impl Screenshot {
                     location: config.save_location,
                     // TODO cover all outputs at start of rectangle?
                     choice,
+                    ocr_languages,
+                    recognizing: false,
+                    compare,
+                    region_tx: None,
+                    pointer_seen: false,
                     // will be updated
                 }))
                 .await
@@ -481,7 +630,10 @@ impl Screenshot {
             }
         }
 
-        let doc_path = match self.screenshot_inner(outputs, app_id).await {
+        let (doc_path, scale) = match self
+            .screenshot_inner(outputs, app_id, config.max_dimension)
+            .await
+        {
             Ok(res) => res,
             Err(err) => {
                 log::error!("Failed to capture screenshot: {}", err);
@@ -492,6 +644,9 @@ impl Screenshot {
         // connection.object_server().remove::<Request, _>(&handle);
         PortalResponse::Success(ScreenshotResult {
             uri: format!("file:///{}", doc_path.display()),
+            text: None,
+            comparison_uri: None,
+            scale,
         })
     }
 
@@ -543,12 +698,35 @@ pub(crate) fn view(portal: &CosmicPortal, id: window::Id) -> cosmic::Element<Msg
             &portal.location_options,
             args.location as usize,
             Msg::Location,
+            (!args.ocr_languages.is_empty()).then_some(Msg::Ocr),
+            args.recognizing,
+            Msg::Compare(!args.compare),
+            args.compare,
+            matches!(args.choice, Choice::Rectangle(..)) && !args.pointer_seen,
             theme.spacing,
         ),
-        |key| match key {
-            Key::Named(Named::Enter) => Some(Msg::Capture),
-            Key::Named(Named::Escape) => Some(Msg::Cancel),
-            _ => None,
+        |key, modifiers| {
+            if let Choice::Rectangle(..) = args.choice {
+                if let Some(step) = nudge_direction(key) {
+                    // Shift resizes rather than moves
+                    let resize = match modifiers {
+                        m if m.is_empty() => false,
+                        m if m == Modifiers::SHIFT => true,
+                        _ => return None,
+                    };
+                    return Some(Msg::Nudge(output.name.clone(), step, resize));
+                }
+            }
+            match shortcuts::action(
+                &portal.config.shortcuts.bindings,
+                true,
+                false,
+                key,
+                modifiers,
+            )? {
+                ShortcutAction::Accept => Some(Msg::Capture),
+                ShortcutAction::Cancel => Some(Msg::Cancel),
+            }
         },
     )
     .into()
@@ -557,177 +735,15 @@ pub(crate) fn view(portal: &CosmicPortal, id: window::Id) -> cosmic::Element<Msg
 pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate::app::Msg> {
     match msg {
         Msg::Capture => {
-            let mut cmds: Vec<cosmic::Command<crate::app::Msg>> = portal
-                .outputs
-                .iter()
-                .map(|o| destroy_layer_surface(o.id))
-                .collect();
-            let Some(args) = portal.screenshot_args.take() else {
-                log::error!("Failed to find screenshot Args for Capture message.");
-                return cosmic::Command::batch(cmds);
-            };
-            let outputs = portal.outputs.clone();
-            let Args {
-                tx,
-                choice,
-                output_images: mut images,
-                location,
-                ..
-            } = args;
-
-            let mut success = true;
-            let image_path = Screenshot::get_img_path(location);
-
-            match choice {
-                Choice::Output(name) => {
-                    if let Some(img) = images.remove(&name) {
-                        if let Some(ref image_path) = image_path {
-                            if let Err(err) = Screenshot::save_rgba(&img, image_path) {
-                                log::error!("Failed to capture screenshot: {:?}", err);
-                            };
-                        } else {
-                            let mut buffer = Vec::new();
-                            if let Err(e) = Screenshot::save_rgba_to_buffer(&img, &mut buffer) {
-                                log::error!("Failed to save screenshot to buffer: {:?}", e);
-                                success = false;
-                            } else {
-                                cmds.push(clipboard::write_data(ScreenshotBytes::new(buffer)))
-                            };
-                        }
-                    } else {
-                        log::error!("Failed to find output {}", name);
-                        success = false;
-                    }
+            if let Some(args) = portal.screenshot_args.as_ref() {
+                if args.recognizing {
+                    return cosmic::Command::none();
                 }
-                Choice::Rectangle(r, s) => {
-                    if let Some(RectDimension { width, height }) = r.dimensions() {
-                        // Construct Rgba image with size of rect
-                        // then overlay the part of each image that intersects with the rect
-                        let mut img = RgbaImage::new(width.get(), height.get());
-
-                        for (name, raw_img) in images {
-                            let Some(output) = outputs.iter().find(|o| o.name == name) else {
-                                continue;
-                            };
-                            let pos = output.logical_pos;
-                            let output_rect = Rect {
-                                left: pos.0,
-                                top: pos.1,
-                                right: pos.0 + output.logical_size.0 as i32,
-                                bottom: pos.1 + output.logical_size.1 as i32,
-                            };
-
-                            let Some(intersect) = r.intersect(output_rect) else {
-                                continue;
-                            };
-                            let mut translated_intersect = intersect.translate(-pos.0, -pos.1);
-                            let scale = raw_img.width() as f32 / output.logical_size.0 as f32;
-                            translated_intersect.left =
-                                (translated_intersect.left as f32 * scale).round() as i32;
-                            translated_intersect.top =
-                                (translated_intersect.top as f32 * scale).round() as i32;
-                            translated_intersect.right =
-                                (translated_intersect.right as f32 * scale).round() as i32;
-                            translated_intersect.bottom =
-                                (translated_intersect.bottom as f32 * scale).round() as i32;
-
-                            let overlay = image::imageops::crop_imm(
-                                raw_img.as_ref(),
-                                u32::try_from(translated_intersect.left).unwrap_or_default(),
-                                u32::try_from(translated_intersect.top).unwrap_or_default(),
-                                (translated_intersect.right - translated_intersect.left)
-                                    .unsigned_abs(),
-                                (translated_intersect.bottom - translated_intersect.top)
-                                    .unsigned_abs(),
-                            );
-
-                            if img.width() != output.logical_size.0 as u32 {
-                                let overlay = image::imageops::resize(
-                                    &overlay.to_image(),
-                                    (intersect.right - intersect.left) as u32,
-                                    (intersect.bottom - intersect.top) as u32,
-                                    image::imageops::FilterType::Lanczos3,
-                                );
-                                image::imageops::overlay(
-                                    &mut img,
-                                    &overlay,
-                                    (intersect.left - r.left).into(),
-                                    (intersect.top - r.top).into(),
-                                );
-                            } else {
-                                image::imageops::overlay(
-                                    &mut img,
-                                    &*overlay,
-                                    (intersect.left - r.left).into(),
-                                    (intersect.top - r.top).into(),
-                                );
-                            }
-                        }
-
-                        if let Some(ref image_path) = image_path {
-                            if let Err(err) = Screenshot::save_rgba(&img, image_path) {
-                                success = false;
-                            }
-                        } else {
-                            let mut buffer = Vec::new();
-                            if let Err(e) = Screenshot::save_rgba_to_buffer(&img, &mut buffer) {
-                                log::error!("Failed to save screenshot to buffer: {:?}", e);
-                                success = false;
-                            } else {
-                                cmds.push(clipboard::write_data(ScreenshotBytes::new(buffer)))
-                            };
-                        }
-                    } else {
-                        success = false;
-                    }
-                }
-                Choice::Window(output, Some(window_i)) => {
-                    if let Some(img) = args
-                        .toplevel_images
-                        .get(&output)
-                        .and_then(|imgs| imgs.get(window_i))
-                    {
-                        if let Some(ref image_path) = image_path {
-                            if let Err(err) = Screenshot::save_rgba(img, image_path) {
-                                log::error!("Failed to capture screenshot: {:?}", err);
-                                success = false;
-                            }
-                        } else {
-                            let mut buffer = Vec::new();
-                            if let Err(e) = Screenshot::save_rgba_to_buffer(&img, &mut buffer) {
-                                log::error!("Failed to save screenshot to buffer: {:?}", e);
-                                success = false;
-                            } else {
-                                cmds.push(clipboard::write_data(ScreenshotBytes::new(buffer)))
-                            };
-                        }
-                    } else {
-                        success = false;
-                    }
-                }
-                _ => {
-                    success = false;
+                if args.options.ocr.unwrap_or_default() && !args.ocr_languages.is_empty() {
+                    return update_msg(portal, Msg::Ocr);
                 }
             }
-
-            let response = if success && image_path.is_some() {
-                PortalResponse::Success(ScreenshotResult {
-                    uri: format!("file:///{}", image_path.unwrap().display()),
-                })
-            } else if success && image_path.is_none() {
-                PortalResponse::Success(ScreenshotResult {
-                    uri: format!("clipboard:///"),
-                })
-            } else {
-                PortalResponse::Other
-            };
-
-            tokio::spawn(async move {
-                if let Err(err) = tx.send(response).await {
-                    log::error!("Failed to send screenshot event");
-                }
-            });
-            cosmic::Command::batch(cmds)
+            capture(portal, None)
         }
         Msg::Cancel => {
             let cmds = portal.outputs.iter().map(|o| destroy_layer_surface(o.id));
@@ -735,12 +751,7 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
                 log::error!("Failed to find screenshot Args for Cancel message.");
                 return cosmic::Command::batch(cmds);
             };
-            let Args { tx, .. } = args;
-            tokio::spawn(async move {
-                if let Err(err) = tx.send(PortalResponse::Cancelled).await {
-                    log::error!("Failed to send screenshot event");
-                }
-            });
+            args.tx.send(PortalResponse::Cancelled);
 
             cosmic::Command::batch(cmds)
         }
@@ -757,7 +768,7 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
             cosmic::command::message(crate::app::Msg::ConfigSetScreenshot(
                 config::screenshot::Screenshot {
                     choice,
-                    ..portal.config.screenshot
+                    ..portal.config.screenshot.clone()
                 },
             ))
         }
@@ -811,6 +822,8 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
                     config::screenshot::Screenshot {
                         save_location: loc,
                         choice: (&mut portal.config.screenshot.choice).into(),
+                        max_dimension: portal.config.screenshot.max_dimension,
+                        hook: portal.config.screenshot.hook.clone(),
                     },
                 ))
             } else {
@@ -818,7 +831,532 @@ pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate:
                 cosmic::Command::none()
             }
         }
+        Msg::Ocr => {
+            let Some(args) = portal.screenshot_args.as_mut() else {
+                log::error!("Failed to find screenshot Args for Ocr message.");
+                return cosmic::Command::none();
+            };
+            if args.recognizing || args.ocr_languages.is_empty() {
+                return cosmic::Command::none();
+            }
+            let Some(img) = selected_image(
+                &args.choice,
+                &args.output_images,
+                &args.toplevel_images,
+                &portal.outputs,
+            ) else {
+                log::error!("Failed to find image for text recognition");
+                return cosmic::Command::none();
+            };
+            args.recognizing = true;
+            let languages = args.ocr_languages.clone();
+            cosmic::Command::perform(
+                async move {
+                    match tokio::task::spawn_blocking(move || {
+                        crate::ocr::recognize(&img, &languages)
+                    })
+                    .await
+                    {
+                        Ok(Ok(text)) => Some(text),
+                        Ok(Err(err)) => {
+                            log::error!("Failed to recognize text: {:?}", err);
+                            None
+                        }
+                        Err(err) => {
+                            log::error!("Text recognition task failed: {:?}", err);
+                            None
+                        }
+                    }
+                },
+                |text| crate::app::Msg::Screenshot(Msg::OcrResult(text)),
+            )
+        }
+        Msg::OcrResult(text) => {
+            let Some(args) = portal.screenshot_args.as_mut() else {
+                // Cancelled while recognizing
+                return cosmic::Command::none();
+            };
+            args.recognizing = false;
+            capture(portal, text)
+        }
+        Msg::Compare(compare) => {
+            if let Some(args) = portal.screenshot_args.as_mut() {
+                args.compare = compare;
+            } else {
+                log::error!("Failed to find screenshot Args for Compare message.");
+            }
+            cosmic::Command::none()
+        }
+        Msg::Nudge(output, step, resize) => {
+            let Some(args) = portal.screenshot_args.as_mut() else {
+                log::error!("Failed to find screenshot Args for Nudge message.");
+                return cosmic::Command::none();
+            };
+            let Choice::Rectangle(r, _) = args.choice else {
+                return cosmic::Command::none();
+            };
+            let output_rects: Vec<Rect> = portal
+                .outputs
+                .iter()
+                .map(|o| {
+                    let (left, top) = o.logical_pos;
+                    Rect {
+                        left,
+                        top,
+                        right: left + o.logical_size.0 as i32,
+                        bottom: top + o.logical_size.1 as i32,
+                    }
+                })
+                .collect();
+            let r = match r.dimensions() {
+                Some(_) => nudge(r, &output_rects, step, resize),
+                // Start with the middle of the output
+                None => match portal.outputs.iter().position(|o| o.name == output) {
+                    Some(i) => {
+                        let o = output_rects[i];
+                        let (width, height) = (o.right - o.left, o.bottom - o.top);
+                        Rect {
+                            left: o.left + width / 4,
+                            top: o.top + height / 4,
+                            right: o.right - width / 4,
+                            bottom: o.bottom - height / 4,
+                        }
+                    }
+                    None => return cosmic::Command::none(),
+                },
+            };
+            update_msg(portal, Msg::Choice(Choice::Rectangle(r, DragState::None)))
+        }
+        Msg::PointerSeen => {
+            if let Some(args) = portal.screenshot_args.as_mut() {
+                args.pointer_seen = true;
+            }
+            cosmic::Command::none()
+        }
+    }
+}
+
+/// Name of a screenshot taken at `taken`, with its time of day on a 24 or 12 hour clock
+fn file_name(taken: time::OffsetDateTime, clock_24h: bool) -> Option<String> {
+    let date_format = if clock_24h {
+        time::macros::format_description!("[year]-[month]-[day]-[hour]-[minute]-[second]")
+    } else {
+        time::macros::format_description!(
+            "[year]-[month]-[day]-[hour repr:12]-[minute]-[second]-[period]"
+        )
+    };
+    Some(format!(
+        "screenshot-{}.png",
+        taken.format(&date_format).ok()?
+    ))
+}
+
+/// Whether times are shown with a 24 hour clock, as chosen for the panel's clock.
+///
+/// Read each time, so a change applies to the next screenshot.
+fn clock_24h() -> bool {
+    use cosmic::cosmic_config::ConfigGet;
+
+    cosmic::cosmic_config::Config::new(CLOCK_CONFIG_ID, CLOCK_CONFIG_VERSION)
+        .and_then(|config| config.get::<bool>("military_time"))
+        .unwrap_or(true)
+}
+
+/// Step of the selected rectangle for an arrow key
+fn nudge_direction(key: &Key) -> Option<(i32, i32)> {
+    match key {
+        Key::Named(Named::ArrowLeft) => Some((-NUDGE_STEP, 0)),
+        Key::Named(Named::ArrowRight) => Some((NUDGE_STEP, 0)),
+        Key::Named(Named::ArrowUp) => Some((0, -NUDGE_STEP)),
+        Key::Named(Named::ArrowDown) => Some((0, NUDGE_STEP)),
+        _ => None,
+    }
+}
+
+/// `r` moved by `step`, or resized by moving its bottom right corner, kept within the bounds
+/// of `outputs`
+fn nudge(r: Rect, outputs: &[Rect], (dx, dy): (i32, i32), resize: bool) -> Rect {
+    // Dragging from the bottom right to the top left gives an inverted rectangle
+    let r = Rect {
+        left: r.left.min(r.right),
+        top: r.top.min(r.bottom),
+        right: r.left.max(r.right),
+        bottom: r.top.max(r.bottom),
+    };
+    let bounds = outputs.iter().fold(r, |bounds, o| Rect {
+        left: bounds.left.min(o.left),
+        top: bounds.top.min(o.top),
+        right: bounds.right.max(o.right),
+        bottom: bounds.bottom.max(o.bottom),
+    });
+    if resize {
+        return Rect {
+            right: (r.right + dx).clamp(r.left + 1, bounds.right),
+            bottom: (r.bottom + dy).clamp(r.top + 1, bounds.bottom),
+            ..r
+        };
+    }
+    let dx = dx.clamp(bounds.left - r.left, bounds.right - r.right);
+    let dy = dy.clamp(bounds.top - r.top, bounds.bottom - r.bottom);
+    r.translate(dx, dy)
+}
+
+/// The image for the current choice, cropped and composited from the captured outputs.
+///
+/// A rectangle spanning outputs with different scales is composited at the highest scale
+/// among them, so the image is the rectangle's logical size times that scale.
+fn selected_image(
+    choice: &Choice,
+    images: &HashMap<String, Arc<RgbaImage>>,
+    toplevel_images: &HashMap<String, Vec<Arc<RgbaImage>>>,
+    outputs: &[OutputState],
+) -> Option<Arc<RgbaImage>> {
+    match choice {
+        Choice::Output(name) => {
+            let img = images.get(name).cloned();
+            if img.is_none() {
+                log::error!("Failed to find output {}", name);
+            }
+            img
+        }
+        Choice::Rectangle(r, s) => {
+            let RectDimension { width, height } = r.dimensions()?;
+            // Part of each output the rect covers, with the output's scale
+            let mut parts = Vec::new();
+            for (name, raw_img) in images {
+                let Some(output) = outputs.iter().find(|o| &o.name == name) else {
+                    continue;
+                };
+                let pos = output.logical_pos;
+                let output_rect = Rect {
+                    left: pos.0,
+                    top: pos.1,
+                    right: pos.0 + output.logical_size.0 as i32,
+                    bottom: pos.1 + output.logical_size.1 as i32,
+                };
+                let Some(intersect) = r.intersect(output_rect) else {
+                    continue;
+                };
+                let scale = raw_img.width() as f32 / output.logical_size.0 as f32;
+                parts.push((
+                    raw_img,
+                    intersect.translate(-pos.0, -pos.1),
+                    intersect,
+                    scale,
+                ));
+            }
+            // Outputs with a lower scale are upscaled, so no detail of the others is lost
+            let max_scale = parts
+                .iter()
+                .map(|(_, _, _, scale)| *scale)
+                .fold(1., f32::max);
+            let scaled = |x: i32, scale: f32| (x as f32 * scale).round() as i32;
+
+            // Construct Rgba image with size of rect at the highest scale
+            // then overlay the part of each image that intersects with the rect
+            let mut img = RgbaImage::new(
+                scaled(width.get() as i32, max_scale) as u32,
+                scaled(height.get() as i32, max_scale) as u32,
+            );
+            for (raw_img, local, intersect, scale) in parts {
+                let left = scaled(local.left, scale);
+                let top = scaled(local.top, scale);
+                let overlay = image::imageops::crop_imm(
+                    raw_img.as_ref(),
+                    u32::try_from(left).unwrap_or_default(),
+                    u32::try_from(top).unwrap_or_default(),
+                    (scaled(local.right, scale) - left).unsigned_abs(),
+                    (scaled(local.bottom, scale) - top).unsigned_abs(),
+                );
+                let x = scaled(intersect.left - r.left, max_scale).into();
+                let y = scaled(intersect.top - r.top, max_scale).into();
+                if scale != max_scale {
+                    let overlay = image::imageops::resize(
+                        &overlay.to_image(),
+                        (scaled(intersect.right, max_scale) - scaled(intersect.left, max_scale))
+                            .unsigned_abs(),
+                        (scaled(intersect.bottom, max_scale) - scaled(intersect.top, max_scale))
+                            .unsigned_abs(),
+                        image::imageops::FilterType::Lanczos3,
+                    );
+                    image::imageops::overlay(&mut img, &overlay, x, y);
+                } else {
+                    image::imageops::overlay(&mut img, &*overlay, x, y);
+                }
+            }
+            Some(Arc::new(img))
+        }
+        Choice::Window(output, Some(window_i)) => toplevel_images
+            .get(output)
+            .and_then(|imgs| imgs.get(*window_i))
+            .cloned(),
+        Choice::Window(_, None) => None,
+    }
+}
+
+/// `img` downscaled so neither side is longer than `max_dimension`, and the scale it was
+/// downscaled by, or `None` if it already fits
+fn limit_dimension(img: &RgbaImage, max_dimension: u32) -> Option<(RgbaImage, f64)> {
+    let max_dimension = max_dimension.max(1);
+    let longest = img.width().max(img.height());
+    if longest <= max_dimension {
+        return None;
+    }
+    let scale = f64::from(max_dimension) / f64::from(longest);
+    let scaled = |x: u32| ((f64::from(x) * scale).round() as u32).clamp(1, max_dimension);
+    log::info!(
+        "downscaling {}x{} screenshot by {scale} to fit {max_dimension}",
+        img.width(),
+        img.height()
+    );
+    let img = image::imageops::resize(
+        img,
+        scaled(img.width()),
+        scaled(img.height()),
+        image::imageops::FilterType::Lanczos3,
+    );
+    Some((img, scale))
+}
+
+/// Keep `img` as the previous capture, and save its difference from the last capture of the
+/// same region next to `image_path`, or in Pictures if it was copied to the clipboard.
+///
+/// Returns the URI of the saved comparison.
+fn save_comparison(
+    portal: &mut CosmicPortal,
+    choice: &Choice,
+    img: &Arc<RgbaImage>,
+    image_path: Option<&PathBuf>,
+) -> Option<String> {
+    let region = Region::for_choice(choice);
+    let previous = match (portal.previous_capture.take(), &region) {
+        (Some((previous_region, previous)), Some(region))
+            if previous_region == *region && previous.dimensions() == img.dimensions() =>
+        {
+            Some(previous)
+        }
+        (Some(_), _) | (None, None) => {
+            tokio::spawn(async {
+                crate::notification::notify(
+                    &fl!("comparison-skipped"),
+                    &fl!("comparison-skipped", "description"),
+                )
+                .await;
+            });
+            None
+        }
+        // First capture of a comparison
+        (None, Some(_)) => None,
+    };
+    if let Some(region) = region {
+        portal.previous_capture = Some((region, img.clone()));
+    }
+
+    let previous = previous?;
+    let path = match image_path {
+        Some(image_path) => image_path.with_file_name(format!(
+            "{}-comparison.png",
+            image_path.file_stem()?.to_string_lossy()
+        )),
+        None => {
+            let path = Screenshot::get_img_path(ImageSaveLocation::Pictures)?;
+            path.with_file_name(format!(
+                "{}-comparison.png",
+                path.file_stem()?.to_string_lossy()
+            ))
+        }
+    };
+    let heatmap = crate::buffer::difference_heatmap(&previous, img);
+    if let Err(err) = Screenshot::save_rgba(&heatmap, &path) {
+        log::error!("Failed to save screenshot comparison: {:?}", err);
+        return None;
+    }
+    Some(format!("file:///{}", path.display()))
+}
+
+/// Save or copy the selected image, close the screenshot surfaces, and send the response.
+///
+/// `text` is the result of text recognition, which is also copied to the clipboard.
+fn capture(portal: &mut CosmicPortal, text: Option<String>) -> cosmic::Command<crate::app::Msg> {
+    let mut cmds: Vec<cosmic::Command<crate::app::Msg>> = portal
+        .outputs
+        .iter()
+        .map(|o| destroy_layer_surface(o.id))
+        .collect();
+    let Some(mut args) = portal.screenshot_args.take() else {
+        log::error!("Failed to find screenshot Args for Capture message.");
+        return cosmic::Command::batch(cmds);
+    };
+    if let Some(region_tx) = args.region_tx.take() {
+        let region = match &args.choice {
+            Choice::Rectangle(r, _) => r.dimensions().map(|_| *r),
+            Choice::Output(name) => portal.outputs.iter().find(|o| o.name == *name).map(|o| {
+                let (left, top) = o.logical_pos;
+                Rect {
+                    left,
+                    top,
+                    right: left + o.logical_size.0 as i32,
+                    bottom: top + o.logical_size.1 as i32,
+                }
+            }),
+            Choice::Window(..) => None,
+        };
+        if let Some(region) = region {
+            if let Err(err) = region_tx.try_send(region) {
+                log::error!("Failed to send selected region: {err}");
+            }
+        }
+        return cosmic::Command::batch(cmds);
+    }
+    let Args {
+        tx,
+        choice,
+        output_images,
+        toplevel_images,
+        location,
+        compare,
+        ..
+    } = args;
+
+    let mut success = true;
+    let image_path = Screenshot::get_img_path(location);
+    let mut comparison_uri = None;
+    let mut scale = None;
+
+    if let Some(mut img) =
+        selected_image(&choice, &output_images, &toplevel_images, &portal.outputs)
+    {
+        if compare {
+            comparison_uri = save_comparison(portal, &choice, &img, image_path.as_ref());
+        }
+        // TODO: Undo night light and other color adjustments, if the compositor ever bakes
+        // them into captures. Screencopy gives the content before the output's gamma ramp
+        // is applied, and no protocol we bind tells a client of the gamma or CTM of an
+        // output, so what's saved is already untinted as far as we can tell.
+        if let Some((limited, limit_scale)) =
+            limit_dimension(&img, portal.config.screenshot.max_dimension)
+        {
+            img = Arc::new(limited);
+            scale = Some(limit_scale);
+        }
+        if let Some(ref image_path) = image_path {
+            if let Err(err) = Screenshot::save_rgba(&img, image_path) {
+                log::error!("Failed to capture screenshot: {:?}", err);
+                success = false;
+            }
+            if let Some(text) = text.clone() {
+                cmds.push(clipboard::write(text));
+            }
+        } else {
+            let mut buffer = Vec::new();
+            if let Err(e) = Screenshot::save_rgba_to_buffer(&img, &mut buffer) {
+                log::error!("Failed to save screenshot to buffer: {:?}", e);
+                success = false;
+            } else {
+                cmds.push(clipboard::write_data(ScreenshotBytes::new(
+                    buffer,
+                    text.clone(),
+                )))
+            };
+        }
+    } else {
+        success = false;
     }
+
+    let result = |uri| ScreenshotResult {
+        uri,
+        text,
+        comparison_uri,
+        scale,
+    };
+    match image_path {
+        Some(image_path) if success => match portal.config.screenshot.hook.clone() {
+            // The response waits for the hook, which may replace the screenshot
+            Some(hook) => {
+                tokio::spawn(async move {
+                    let image_path = run_hook(&hook, image_path).await;
+                    let uri = format!("file:///{}", image_path.display());
+                    tx.send(PortalResponse::Success(result(uri)));
+                });
+            }
+            None => {
+                let uri = format!("file:///{}", image_path.display());
+                tx.send(PortalResponse::Success(result(uri)));
+            }
+        },
+        None if success => tx.send(PortalResponse::Success(result(format!("clipboard:///")))),
+        _ => tx.send(PortalResponse::Other),
+    }
+    cosmic::Command::batch(cmds)
+}
+
+/// Run `hook` on the screenshot saved at `path`, returning the path of the screenshot to
+/// return. That's `path` unless the hook printed another, or if it failed or timed out.
+async fn run_hook(hook: &config::screenshot::Hook, path: PathBuf) -> PathBuf {
+    match run_hook_inner(hook, &path).await {
+        Ok(Some(replaced)) => {
+            log::info!("screenshot hook replaced {:?} with {:?}", path, replaced);
+            replaced
+        }
+        Ok(None) => path,
+        Err(err) => {
+            log::error!("screenshot hook failed: {err:#}");
+            path
+        }
+    }
+}
+
+async fn run_hook_inner(
+    hook: &config::screenshot::Hook,
+    path: &Path,
+) -> anyhow::Result<Option<PathBuf>> {
+    let (program, args) = hook.command.split_first().context("no command")?;
+    crate::launch::set_cloexec_all();
+    let child = tokio::process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        // Killed if it times out, when the wait is dropped
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+    let timeout = Duration::from_secs(hook.timeout_secs.into());
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| anyhow::anyhow!("{program} timed out after {timeout:?}"))??;
+    if !output.status.success() {
+        anyhow::bail!("{program} exited with {}", output.status);
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let Some(line) = stdout
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+    else {
+        return Ok(None);
+    };
+    let replaced = PathBuf::from(line);
+    if !replaced.is_absolute() || !replaced.is_file() {
+        anyhow::bail!("{program} printed {line:?}, which isn't the path of a file");
+    }
+    Ok(Some(replaced))
+}
+
+/// Fail the request with `reason` after a selection surface was closed by the compositor
+pub fn surface_closed(
+    portal: &mut CosmicPortal,
+    reason: String,
+) -> cosmic::Command<crate::app::Msg> {
+    let Some(args) = portal.screenshot_args.take() else {
+        return cosmic::Command::none();
+    };
+    log::error!("Interactive screenshot for {:?}: {reason}", args.app_id);
+    let cmds = portal.outputs.iter().map(|o| destroy_layer_surface(o.id));
+    args.tx.send(PortalResponse::Error(reason));
+    cosmic::Command::batch(cmds)
 }
 
 pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<crate::app::Msg> {
@@ -833,6 +1371,10 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<cra
         action,
         location,
         toplevel_images,
+        ocr_languages,
+        recognizing,
+        compare,
+        region_tx,
     } = &args;
 
     if portal.outputs.len() != images.len() {
@@ -889,19 +1431,11 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<cra
                 |OutputState {
                      output, id, name, ..
                  }| {
-                    get_layer_surface(SctkLayerSurfaceSettings {
-                        id: *id,
-                        layer: Layer::Overlay,
-                        keyboard_interactivity: KeyboardInteractivity::Exclusive,
-                        pointer_interactivity: true,
-                        anchor: Anchor::all(),
-                        output: IcedOutput::Output(output.clone()),
-                        namespace: "screenshot".to_string(),
-                        size: Some((None, None)),
-                        exclusive_zone: -1,
-                        size_limits: Limits::NONE.min_height(1.0).min_width(1.0),
-                        ..Default::default()
-                    })
+                    get_layer_surface(crate::surfaces::fullscreen_overlay(
+                        *id,
+                        IcedOutput::Output(output.clone()),
+                        "screenshot",
+                    ))
                 },
             )
             .collect();
@@ -911,3 +1445,34 @@ pub fn update_args(portal: &mut CosmicPortal, args: Args) -> cosmic::Command<cra
         cosmic::Command::none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_round_trip() {
+        crate::assert_round_trips(ScreenshotResult {
+            uri: "file:///tmp/Screenshot.png".to_string(),
+            text: None,
+            comparison_uri: None,
+            scale: Some(0.5),
+        });
+        crate::assert_round_trips(PickColorResult {
+            color: (1., 0.5, 0.),
+        });
+    }
+
+    #[test]
+    fn file_names_follow_the_clock() {
+        let taken = time::macros::datetime!(2024-03-05 14:07:09 UTC);
+        assert_eq!(
+            file_name(taken, true).as_deref(),
+            Some("screenshot-2024-03-05-14-07-09.png")
+        );
+        assert_eq!(
+            file_name(taken, false).as_deref(),
+            Some("screenshot-2024-03-05-02-07-09-PM.png")
+        );
+    }
+}
@@ -0,0 +1,440 @@
+// Per-app master secrets, that apps derive their own encryption keys from.
+//
+// Each secret has an ID, returned to the frontend as the request token. An app
+// presenting the token of an older secret gets that secret back, so data encrypted
+// before a rotation can still be read. Secrets are stored in a file only the user
+// can read, replaced atomically on every change.
+
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::fs::DirBuilderExt,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use zbus::zvariant;
+
+use crate::PortalResponse;
+
+// Length of generated secrets, in bytes
+const SECRET_LEN: usize = 64;
+// Length of generated secret IDs, in bytes
+const ID_LEN: usize = 8;
+// How long a secret is still returned for its token after being rotated, in seconds
+const RETIRED_LIFETIME: u64 = 90 * 24 * 60 * 60;
+// Executables allowed to rotate secrets
+const ROTATE_ALLOWED: &[&str] = &["cosmic-settings"];
+
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug)]
+#[zvariant(signature = "a{sv}")]
+pub struct RetrieveSecretOptions {
+    /// ID of the secret returned to the app last time
+    token: Option<String>,
+}
+
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
+#[zvariant(signature = "a{sv}")]
+pub struct RetrieveSecretResult {
+    token: String,
+}
+
+#[derive(Clone, Debug)]
+struct StoredSecret {
+    app_id: String,
+    id: String,
+    /// Seconds since the Unix epoch the secret was replaced, if it was
+    retired: Option<u64>,
+    secret: Vec<u8>,
+}
+
+/// The secrets file, one secret per line as tab separated fields
+struct Store {
+    path: PathBuf,
+}
+
+impl Store {
+    fn new() -> Option<Self> {
+        let dir = dirs::data_dir()?.join("xdg-desktop-portal-cosmic");
+        Some(Self {
+            path: dir.join("secrets"),
+        })
+    }
+
+    fn load(&self) -> io::Result<Vec<StoredSecret>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut secrets = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            match parse_line(line) {
+                Some(secret) => secrets.push(secret),
+                None => log::error!("invalid secret on line {} of {:?}", number + 1, self.path),
+            }
+        }
+        Ok(secrets)
+    }
+
+    /// Replace the file with `secrets`, so a crash leaves either the old or new contents
+    fn save(&self, secrets: &[StoredSecret]) -> io::Result<()> {
+        let dir = self.path.parent().unwrap();
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+        crate::fs_util::write_atomic(&self.path, 0o600, |file| {
+            for secret in secrets {
+                writeln!(
+                    file,
+                    "{}\t{}\t{}\t{}",
+                    secret.app_id,
+                    secret.id,
+                    secret.retired.map_or("-".to_string(), |x| x.to_string()),
+                    to_hex(&secret.secret)
+                )?;
+            }
+            Ok(())
+        })
+        .map_err(io::Error::other)
+    }
+}
+
+fn parse_line(line: &str) -> Option<StoredSecret> {
+    let mut fields = line.split('\t');
+    let app_id = fields.next()?.to_string();
+    let id = fields.next()?.to_string();
+    let retired = match fields.next()? {
+        "-" => None,
+        retired => Some(retired.parse().ok()?),
+    };
+    let secret = from_hex(fields.next()?)?;
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(StoredSecret {
+        app_id,
+        id,
+        retired,
+        secret,
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn random_bytes(len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0; len];
+    let mut filled = 0;
+    while filled < len {
+        filled +=
+            rustix::rand::getrandom(&mut bytes[filled..], rustix::rand::GetRandomFlags::empty())?;
+    }
+    Ok(bytes)
+}
+
+fn new_secret(app_id: &str) -> io::Result<StoredSecret> {
+    Ok(StoredSecret {
+        app_id: app_id.to_string(),
+        id: to_hex(&random_bytes(ID_LEN)?),
+        retired: None,
+        secret: random_bytes(SECRET_LEN)?,
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+/// Drop secrets retired for longer than `RETIRED_LIFETIME` at `now`
+fn prune(secrets: &mut Vec<StoredSecret>, now: u64) -> bool {
+    let len = secrets.len();
+    secrets.retain(|x| {
+        x.retired.map_or(true, |retired| {
+            now < retired.saturating_add(RETIRED_LIFETIME)
+        })
+    });
+    secrets.len() != len
+}
+
+// Locked across each load and save of the secrets file
+type SharedStore = Arc<Mutex<Option<Store>>>;
+
+pub struct Secret {
+    store: SharedStore,
+}
+
+impl Secret {
+    pub fn new() -> Self {
+        Self {
+            store: Arc::new(Mutex::new(Store::new())),
+        }
+    }
+
+    pub fn rotation(&self) -> SecretRotation {
+        SecretRotation {
+            store: self.store.clone(),
+        }
+    }
+}
+
+/// Secret of `app_id` with the ID `token`, or its current secret
+fn retrieve(store: &SharedStore, app_id: &str, token: Option<&str>) -> io::Result<StoredSecret> {
+    let store = store.lock().unwrap();
+    let store = store.as_ref().ok_or_else(no_data_dir)?;
+    let mut secrets = store.load()?;
+    let mut changed = prune(&mut secrets, now());
+    let matching =
+        token.and_then(|token| secrets.iter().find(|x| x.app_id == app_id && x.id == token));
+    let secret = match matching.or_else(|| {
+        secrets
+            .iter()
+            .find(|x| x.app_id == app_id && x.retired.is_none())
+    }) {
+        Some(secret) => secret.clone(),
+        None => {
+            let secret = new_secret(app_id)?;
+            secrets.push(secret.clone());
+            changed = true;
+            secret
+        }
+    };
+    if changed {
+        store.save(&secrets)?;
+    }
+    Ok(secret)
+}
+
+/// Replace the current secret of `app_id`, keeping the old one for its token
+fn rotate(store: &SharedStore, app_id: &str) -> io::Result<String> {
+    let store = store.lock().unwrap();
+    let store = store.as_ref().ok_or_else(no_data_dir)?;
+    let mut secrets = store.load()?;
+    let now = now();
+    prune(&mut secrets, now);
+    for secret in secrets
+        .iter_mut()
+        .filter(|x| x.app_id == app_id && x.retired.is_none())
+    {
+        secret.retired = Some(now);
+    }
+    let secret = new_secret(app_id)?;
+    let id = secret.id.clone();
+    secrets.push(secret);
+    store.save(&secrets)?;
+    Ok(id)
+}
+
+fn no_data_dir() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "no data directory")
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.Secret")]
+impl Secret {
+    async fn retrieve_secret(
+        &self,
+        handle: zvariant::ObjectPath<'_>,
+        app_id: &str,
+        fd: zvariant::OwnedFd,
+        options: RetrieveSecretOptions,
+    ) -> PortalResponse<RetrieveSecretResult> {
+        log::debug!("retrieve secret {handle} {app_id} {options:?}");
+        if app_id.is_empty() {
+            // Unsandboxed apps have no stable identity to keep a secret for
+            return PortalResponse::Other;
+        }
+        let store = self.store.clone();
+        let (owner, token) = (app_id.to_string(), options.token);
+        let retrieved =
+            tokio::task::spawn_blocking(move || retrieve(&store, &owner, token.as_deref())).await;
+        let secret = match retrieved.unwrap_or_else(|err| Err(io::Error::other(err))) {
+            Ok(secret) => secret,
+            Err(err) => {
+                log::error!("failed to retrieve secret of {app_id}: {err}");
+                return PortalResponse::Other;
+            }
+        };
+        let mut file = fs::File::from(std::os::fd::OwnedFd::from(fd));
+        if let Err(err) = file.write_all(&secret.secret) {
+            log::error!("failed to send secret to {app_id}: {err}");
+            return PortalResponse::Other;
+        }
+        PortalResponse::Success(RetrieveSecretResult { token: secret.id })
+    }
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        1
+    }
+}
+
+/// Rotation of app secrets, for COSMIC Settings
+pub struct SecretRotation {
+    store: SharedStore,
+}
+
+#[zbus::interface(name = "com.system76.CosmicPortal.Secret")]
+impl SecretRotation {
+    /// Give `app_id` a new secret, returning its ID.
+    ///
+    /// The old secret is still returned to the app for its token for 90 days, so it
+    /// can re-encrypt its data.
+    async fn rotate_secret(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        app_id: &str,
+    ) -> zbus::fdo::Result<String> {
+        if !caller_allowed(connection, &header).await {
+            return Err(zbus::fdo::Error::AccessDenied(
+                "Not allowed to rotate secrets".to_string(),
+            ));
+        }
+        let store = self.store.clone();
+        let owner = app_id.to_string();
+        let rotated = tokio::task::spawn_blocking(move || rotate(&store, &owner)).await;
+        rotated
+            .unwrap_or_else(|err| Err(io::Error::other(err)))
+            .map_err(|err| {
+                log::error!("failed to rotate secret of {app_id}: {err}");
+                zbus::fdo::Error::Failed(err.to_string())
+            })
+    }
+}
+
+/// Whether the sender of `header` is an unsandboxed process in `ROTATE_ALLOWED`
+async fn caller_allowed(connection: &zbus::Connection, header: &zbus::message::Header<'_>) -> bool {
+    let Some(sender) = header.sender() else {
+        return false;
+    };
+    let pid = match zbus::fdo::DBusProxy::new(connection).await {
+        Ok(proxy) => {
+            proxy
+                .get_connection_unix_process_id(sender.to_owned().into())
+                .await
+        }
+        Err(err) => Err(err.into()),
+    };
+    let pid = match pid {
+        Ok(pid) => pid,
+        Err(err) => {
+            log::error!("failed to get process of {sender}: {err}");
+            return false;
+        }
+    };
+    let proc = PathBuf::from(format!("/proc/{pid}"));
+    if proc.join("root/.flatpak-info").exists() {
+        return false;
+    }
+    fs::read_link(proc.join("exe")).is_ok_and(|exe| {
+        exe.file_name()
+            .and_then(|x| x.to_str())
+            .is_some_and(|name| ROTATE_ALLOWED.contains(&name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_round_trips() {
+        crate::assert_round_trips(RetrieveSecretResult {
+            token: "token".to_string(),
+        });
+    }
+
+    #[test]
+    fn old_tokens_retrieve_rotated_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(Mutex::new(Some(Store {
+            path: dir.path().join("secrets"),
+        })));
+        let first = retrieve(&store, "org.example.App", None).unwrap();
+        assert_eq!(first.secret.len(), SECRET_LEN);
+        let again = retrieve(&store, "org.example.App", Some(&first.id)).unwrap();
+        assert_eq!(
+            (again.id, again.secret),
+            (first.id.clone(), first.secret.clone())
+        );
+
+        let id = rotate(&store, "org.example.App").unwrap();
+        assert_ne!(id, first.id);
+        let current = retrieve(&store, "org.example.App", None).unwrap();
+        assert_eq!(current.id, id);
+        assert_ne!(current.secret, first.secret);
+        // Until the app re-encrypts its data with the new one
+        let old = retrieve(&store, "org.example.App", Some(&first.id)).unwrap();
+        assert_eq!((old.id, old.secret), (first.id, first.secret));
+
+        // Other apps' tokens aren't honored
+        let other = retrieve(&store, "org.example.Other", Some(&id)).unwrap();
+        assert_ne!(other.id, id);
+    }
+
+    #[test]
+    fn retired_secrets_are_pruned() {
+        let secret = |id: &str, retired| StoredSecret {
+            app_id: "org.example.App".to_string(),
+            id: id.to_string(),
+            retired,
+            secret: vec![1, 2, 3],
+        };
+        let now = 1_700_000_000;
+        let mut secrets = vec![
+            secret("current", None),
+            secret("expired", Some(now - RETIRED_LIFETIME)),
+            secret("retired", Some(now - RETIRED_LIFETIME + 1)),
+        ];
+        assert!(prune(&mut secrets, now));
+        let ids: Vec<_> = secrets.iter().map(|x| x.id.as_str()).collect();
+        assert_eq!(ids, ["current", "retired"]);
+        assert!(!prune(&mut secrets, now));
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        let secret = parse_line("org.example.App\tab12\t-\t00ff10").unwrap();
+        assert_eq!(secret.app_id, "org.example.App");
+        assert_eq!(secret.id, "ab12");
+        assert_eq!(secret.retired, None);
+        assert_eq!(secret.secret, [0x00, 0xff, 0x10]);
+        let secret = parse_line("org.example.App\tab12\t1700000000\t00").unwrap();
+        assert_eq!(secret.retired, Some(1_700_000_000));
+
+        for line in [
+            "",
+            "org.example.App\tab12\t-",
+            "org.example.App\tab12\t-\t00\textra",
+            "org.example.App\tab12\tyesterday\t00",
+            "org.example.App\tab12\t-\t0g",
+        ] {
+            assert!(parse_line(line).is_none(), "{line:?}");
+        }
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes = [0x00, 0x7f, 0x80, 0xff];
+        assert_eq!(from_hex(&to_hex(&bytes)).unwrap(), bytes);
+        assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+        assert_eq!(from_hex("0"), None);
+        assert_eq!(from_hex("zz"), None);
+        // Not split inside a character
+        assert_eq!(from_hex("0é0"), None);
+    }
+}
@@ -0,0 +1,291 @@
+// Keyboard shortcuts for dialog actions, configured in addition to Enter and Escape
+
+use cosmic::iced::keyboard::{key::Named, Key, Modifiers};
+
+use crate::config::shortcuts::{Action, Shortcut};
+
+/// Key and modifiers of a shortcut like `Ctrl+Enter`
+#[derive(Clone, Debug, PartialEq)]
+struct Keys {
+    key: Key,
+    modifiers: Modifiers,
+}
+
+impl Keys {
+    fn parse(keys: &str) -> Option<Self> {
+        let mut parts: Vec<&str> = keys.split('+').map(str::trim).collect();
+        let key = parts.pop()?;
+        let mut modifiers = Modifiers::empty();
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" => Modifiers::ALT,
+                "super" | "logo" => Modifiers::LOGO,
+                _ => return None,
+            };
+        }
+        let key = match key.to_lowercase().as_str() {
+            "enter" | "return" => Key::Named(Named::Enter),
+            "escape" | "esc" => Key::Named(Named::Escape),
+            "space" => Key::Named(Named::Space),
+            "tab" => Key::Named(Named::Tab),
+            "backspace" => Key::Named(Named::Backspace),
+            "delete" => Key::Named(Named::Delete),
+            lower if lower.chars().count() == 1 => Key::Character(lower.into()),
+            _ => return None,
+        };
+        Some(Self { key, modifiers })
+    }
+
+    fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        let key_matches = match (&self.key, key) {
+            // Shift changes the character, so compare case-insensitively
+            (Key::Character(a), Key::Character(b)) => a.as_str() == b.to_lowercase(),
+            (a, b) => a == b,
+        };
+        key_matches && self.modifiers == modifiers
+    }
+
+    /// Whether pressing this would type or edit text in a focused text input
+    fn edits_text(&self) -> bool {
+        let modified = self
+            .modifiers
+            .intersects(Modifiers::CTRL | Modifiers::ALT | Modifiers::LOGO);
+        !modified
+            && matches!(
+                self.key,
+                Key::Character(_) | Key::Named(Named::Space | Named::Backspace | Named::Delete)
+            )
+    }
+}
+
+/// Action for a key press, from the configured `bindings`, then Enter and Escape if
+/// `defaults` is set.
+///
+/// Dialogs with a text input pass `has_text_input`, so bindings that would conflict with
+/// typing are ignored.
+pub fn action(
+    bindings: &[Shortcut],
+    defaults: bool,
+    has_text_input: bool,
+    key: &Key,
+    modifiers: Modifiers,
+) -> Option<Action> {
+    for binding in bindings {
+        let Some(keys) = Keys::parse(&binding.keys) else {
+            log::warn!("invalid shortcut {:?}", binding.keys);
+            continue;
+        };
+        if has_text_input && keys.edits_text() {
+            log::warn!(
+                "ignoring shortcut {:?}, which conflicts with text entry",
+                binding.keys
+            );
+            continue;
+        }
+        if keys.matches(key, modifiers) {
+            return Some(binding.action);
+        }
+    }
+    if !defaults {
+        return None;
+    }
+    match key {
+        Key::Named(Named::Enter) => Some(Action::Accept),
+        Key::Named(Named::Escape) => Some(Action::Cancel),
+        _ => None,
+    }
+}
+
+/// Keys of dialogs granting access, after the configured bindings. Ctrl+Enter accepts,
+/// since bare Enter is left to the focused widget, and Escape cancels.
+pub fn quick_grant(key: &Key, modifiers: Modifiers) -> Option<Action> {
+    match key {
+        Key::Named(Named::Enter) if modifiers == Modifiers::CTRL => Some(Action::Accept),
+        Key::Named(Named::Escape) if modifiers.is_empty() => Some(Action::Cancel),
+        _ => None,
+    }
+}
+
+/// Keys of dialogs whose grant is destructive, after the configured bindings. Enter and
+/// Escape both cancel, so only a deliberate click or accelerator grants.
+pub fn quick_deny(key: &Key, modifiers: Modifiers) -> Option<Action> {
+    match key {
+        Key::Named(Named::Enter | Named::Escape) if modifiers.is_empty() => Some(Action::Cancel),
+        _ => None,
+    }
+}
+
+/// Labels of buttons with Alt+letter accelerators, as `(label, preferred letter)`.
+///
+/// A label may mark its letter with `_`, like `_Allow`, which takes precedence over the
+/// preferred one. A button whose letter an earlier one took gets the first free letter of
+/// its label instead. Returns the labels without the marker, and each button's letter.
+pub fn mnemonics(labels: &[(&str, Option<char>)]) -> Vec<(String, Option<char>)> {
+    let mut taken = Vec::with_capacity(labels.len());
+    labels
+        .iter()
+        .map(|(label, preferred)| {
+            let (label, marked) = strip_marker(label);
+            let free = |c: &char| c.is_alphanumeric() && !taken.contains(&lowercase(*c));
+            let letter = marked
+                .or(*preferred)
+                .filter(free)
+                .or_else(|| label.chars().find(free))
+                .map(lowercase);
+            if let Some(letter) = letter {
+                taken.push(letter);
+            }
+            (label, letter)
+        })
+        .collect()
+}
+
+/// Whether `key` with `modifiers` is Alt and the accelerator `letter`
+pub fn mnemonic_matches(letter: char, key: &Key, modifiers: Modifiers) -> bool {
+    modifiers == Modifiers::ALT
+        && matches!(key, Key::Character(c) if c.to_lowercase().starts_with(letter))
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// `label` without its `_` mnemonic marker, and the letter it marks. `__` is a literal `_`.
+fn strip_marker(label: &str) -> (String, Option<char>) {
+    let mut stripped = String::with_capacity(label.len());
+    let mut marked = None;
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        if c != '_' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('_') => stripped.push('_'),
+            Some(next) => {
+                marked = marked.or(Some(next));
+                stripped.push(next);
+            }
+            None => {}
+        }
+    }
+    (stripped, marked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_parse() {
+        let keys = Keys::parse("Ctrl+Shift+Enter").unwrap();
+        assert_eq!(keys.key, Key::Named(Named::Enter));
+        assert_eq!(keys.modifiers, Modifiers::CTRL | Modifiers::SHIFT);
+
+        let keys = Keys::parse(" alt + D ").unwrap();
+        assert_eq!(keys.key, Key::Character("d".into()));
+        assert_eq!(keys.modifiers, Modifiers::ALT);
+
+        assert_eq!(Keys::parse("Esc").unwrap().key, Key::Named(Named::Escape));
+        assert!(Keys::parse("").is_none());
+        assert!(Keys::parse("Ctrl+").is_none());
+        assert!(Keys::parse("Hyper+A").is_none());
+        assert!(Keys::parse("F13").is_none());
+    }
+
+    #[test]
+    fn text_input_ignores_typing_shortcuts() {
+        let bindings = [
+            Shortcut {
+                keys: "Y".to_string(),
+                action: Action::Accept,
+            },
+            Shortcut {
+                keys: "Ctrl+Y".to_string(),
+                action: Action::Accept,
+            },
+        ];
+        let y = Key::Character("y".into());
+        assert_eq!(
+            action(&bindings, false, false, &y, Modifiers::empty()),
+            Some(Action::Accept)
+        );
+        assert_eq!(action(&bindings, false, true, &y, Modifiers::empty()), None);
+        assert_eq!(
+            action(&bindings, false, true, &y, Modifiers::CTRL),
+            Some(Action::Accept)
+        );
+    }
+
+    #[test]
+    fn defaults_follow_bindings() {
+        let enter = Key::Named(Named::Enter);
+        let escape = Key::Named(Named::Escape);
+        assert_eq!(
+            action(&[], true, false, &enter, Modifiers::empty()),
+            Some(Action::Accept)
+        );
+        assert_eq!(
+            action(&[], true, false, &escape, Modifiers::empty()),
+            Some(Action::Cancel)
+        );
+        assert_eq!(action(&[], false, false, &enter, Modifiers::empty()), None);
+    }
+
+    #[test]
+    fn mnemonics_are_unique() {
+        let letters = |labels: &[(&str, Option<char>)]| -> Vec<_> {
+            mnemonics(labels).into_iter().map(|(_, x)| x).collect()
+        };
+        assert_eq!(
+            letters(&[("Allow", Some('a')), ("Deny", Some('d'))]),
+            [Some('a'), Some('d')]
+        );
+        // A later button whose letter is taken gets its first free one
+        assert_eq!(
+            letters(&[("Allow", Some('a')), ("Always", Some('a'))]),
+            [Some('a'), Some('l')]
+        );
+        // Without a preferred letter, or any free one
+        assert_eq!(
+            letters(&[("Share", None), ("Sh", None)]),
+            [Some('s'), Some('h')]
+        );
+        assert_eq!(
+            letters(&[("OK", None), ("ok", None)]),
+            [Some('o'), Some('k')]
+        );
+        assert_eq!(letters(&[("A", None), ("a", None)]), [Some('a'), None]);
+    }
+
+    #[test]
+    fn mnemonic_markers_are_stripped() {
+        assert_eq!(
+            mnemonics(&[("_Allow", Some('l')), ("Don't _allow", None)]),
+            [
+                ("Allow".to_string(), Some('a')),
+                ("Don't allow".to_string(), Some('d'))
+            ]
+        );
+        assert_eq!(
+            mnemonics(&[("snake__case", None)]),
+            [("snake_case".to_string(), Some('s'))]
+        );
+    }
+
+    #[test]
+    fn mnemonics_need_alt() {
+        let a = Key::Character("a".into());
+        assert!(mnemonic_matches('a', &a, Modifiers::ALT));
+        assert!(mnemonic_matches(
+            'a',
+            &Key::Character("A".into()),
+            Modifiers::ALT
+        ));
+        assert!(!mnemonic_matches('a', &a, Modifiers::empty()));
+        assert!(!mnemonic_matches('a', &a, Modifiers::ALT | Modifiers::CTRL));
+        assert!(!mnemonic_matches('b', &a, Modifiers::ALT));
+    }
+}
@@ -0,0 +1,228 @@
+// Portal state kept across restarts, in `$XDG_STATE_HOME/xdg-desktop-portal-cosmic`.
+//
+// Each kind of state is a RON file named by its key, replaced atomically when saved. A
+// file that can't be parsed is moved aside and the state starts fresh, so corruption
+// only loses that state. A manifest records the directory's version, and `migrate`
+// converts what older versions wrote before anything is loaded.
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fs, io,
+    io::Write,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+// Version of the directory's layout, increased with each migration
+const VERSION: u32 = 1;
+const MANIFEST_KEY: &str = "manifest";
+// Keys once stored as cosmic-config state, moved here by the first migration
+const LEGACY_KEYS: &[&str] = &["file_chooser", "background_review"];
+
+// Missing from directories made before there was one, which are version 0
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+}
+
+// The state directory, once migrated. Locked across each load and save of a file, so
+// concurrent updates of the same state aren't lost.
+static STATE_DIR: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(open()));
+
+/// State stored under `key`, or the default if there is none
+pub fn load<T: DeserializeOwned + Default>(key: &str) -> T {
+    let dir = STATE_DIR.lock().unwrap();
+    match dir.as_deref() {
+        Some(dir) => read(dir, key),
+        None => T::default(),
+    }
+}
+
+/// Replace the state stored under `key`
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    let dir = STATE_DIR.lock().unwrap();
+    if let Some(dir) = dir.as_deref() {
+        write(dir, key, value);
+    }
+}
+
+/// Change the state stored under `key` with `f`, saving it if `f` returns true.
+///
+/// Nothing else can change the state in between.
+pub fn update<T, F>(key: &str, f: F)
+where
+    T: Serialize + DeserializeOwned + Default,
+    F: FnOnce(&mut T) -> bool,
+{
+    let dir = STATE_DIR.lock().unwrap();
+    let Some(dir) = dir.as_deref() else {
+        return;
+    };
+    let mut value = read(dir, key);
+    if f(&mut value) {
+        write(dir, key, &value);
+    }
+}
+
+fn path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{key}.ron"))
+}
+
+fn read<T: DeserializeOwned + Default>(dir: &Path, key: &str) -> T {
+    let path = path(dir, key);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return T::default(),
+        Err(err) => {
+            log::error!("failed to read state {}: {err}", path.display());
+            return T::default();
+        }
+    };
+    match ron::from_str(&contents) {
+        Ok(value) => value,
+        Err(err) => {
+            log::error!("state {} is corrupt: {err}", path.display());
+            set_aside(&path);
+            T::default()
+        }
+    }
+}
+
+fn write<T: Serialize>(dir: &Path, key: &str, value: &T) {
+    let path = path(dir, key);
+    let result = ron::to_string(value)
+        .map_err(anyhow::Error::from)
+        .and_then(|contents| {
+            crate::fs_util::write_atomic(&path, 0o600, |file| {
+                file.write_all(contents.as_bytes())?;
+                Ok(())
+            })
+        });
+    if let Err(err) = result {
+        log::error!("failed to save state {}: {err}", path.display());
+    }
+}
+
+// Keep a corrupt file for inspection, out of the way of a fresh one
+fn set_aside(path: &Path) {
+    let secs = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+    let mut aside = path.as_os_str().to_owned();
+    aside.push(format!(".corrupt-{secs}"));
+    if let Err(err) = fs::rename(path, &aside) {
+        log::error!("failed to move aside {}: {err}", path.display());
+    }
+}
+
+// Create the directory, and migrate it to `VERSION`
+fn open() -> Option<PathBuf> {
+    let Some(dir) = dirs::state_dir().map(|x| x.join("xdg-desktop-portal-cosmic")) else {
+        log::error!("no state directory");
+        return None;
+    };
+    if let Err(err) = fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+    {
+        log::error!("failed to create {}: {err}", dir.display());
+        return None;
+    }
+    let version = read::<Manifest>(&dir, MANIFEST_KEY).version;
+    if version > VERSION {
+        log::warn!(
+            "state in {} is from a newer version ({version}), and may not load",
+            dir.display()
+        );
+    } else if version < VERSION {
+        migrate(&dir, version);
+        write(&dir, MANIFEST_KEY, &Manifest { version: VERSION });
+    }
+    Some(dir)
+}
+
+/// Convert state written by version `from` of the directory's layout
+fn migrate(dir: &Path, from: u32) {
+    if from < 1 {
+        // Move state kept by cosmic-config, whose entries are RON files named by key
+        let legacy = dirs::state_dir().map(|x| {
+            x.join("cosmic")
+                .join(crate::config::APP_ID)
+                .join(format!("v{}", crate::config::CONFIG_VERSION))
+        });
+        for key in LEGACY_KEYS {
+            let Some(old) = legacy.as_ref().map(|x| x.join(key)) else {
+                break;
+            };
+            let new = path(dir, key);
+            if old.exists() && !new.exists() {
+                if let Err(err) = fs::rename(&old, &new).or_else(|_| {
+                    fs::copy(&old, &new)?;
+                    fs::remove_file(&old)
+                }) {
+                    log::error!("failed to migrate state {}: {err}", old.display());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Grants {
+        apps: BTreeMap<String, Vec<String>>,
+        reviewed: Option<u64>,
+    }
+
+    fn grants() -> Grants {
+        Grants {
+            apps: BTreeMap::from([(
+                "org.example.App".to_string(),
+                vec!["camera".to_string(), "background".to_string()],
+            )]),
+            reviewed: Some(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn state_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read::<Grants>(dir.path(), "grants"), Grants::default());
+        write(dir.path(), "grants", &grants());
+        assert_eq!(read::<Grants>(dir.path(), "grants"), grants());
+        assert!(path(dir.path(), "grants").exists());
+    }
+
+    #[test]
+    fn corrupt_state_is_set_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(path(dir.path(), "grants"), "(apps: {\"org.exa").unwrap();
+        assert_eq!(read::<Grants>(dir.path(), "grants"), Grants::default());
+        assert!(!path(dir.path(), "grants").exists());
+        let aside: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(aside.len(), 1);
+        assert!(aside[0].starts_with("grants.ron.corrupt-"), "{aside:?}");
+
+        // And the state starts fresh
+        write(dir.path(), "grants", &grants());
+        assert_eq!(read::<Grants>(dir.path(), "grants"), grants());
+    }
+
+    #[test]
+    fn state_of_another_shape_is_corrupt() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "grants", &vec![1, 2, 3]);
+        assert_eq!(read::<Grants>(dir.path(), "grants"), Grants::default());
+    }
+}
@@ -11,14 +11,29 @@ use tokio::sync::mpsc::Receiver;
 use zbus::{zvariant, Connection};
 
 use crate::{
-    access::Access, config, file_chooser::FileChooser, screencast::ScreenCast,
-    screenshot::Screenshot, wayland, ColorScheme, Contrast, Settings, ACCENT_COLOR_KEY,
-    APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY, CONTRAST_KEY, DBUS_NAME, DBUS_PATH,
+    access::{Access, Prompts},
+    app_chooser::AppChooser,
+    config,
+    file_chooser::FileChooser,
+    management::Management,
+    notification::Notification,
+    screencast::ScreenCast,
+    screenshot::Screenshot,
+    secret::Secret,
+    usb::Usb,
+    wayland, ColorScheme, Contrast, Settings, ACCENT_COLOR_KEY, APPEARANCE_NAMESPACE,
+    COLOR_SCHEME_KEY, CONTRAST_KEY, DBUS_NAME, DBUS_PATH, DISPLAY_NAMESPACE, SCALE_FACTOR_KEY,
 };
 
 #[derive(Clone)]
 pub enum Event {
     Access(crate::access::AccessDialogArgs),
+    CancelAccess(zvariant::ObjectPath<'static>),
+    /// Cancel the access prompt on screen, whichever request it's for
+    CancelShownAccess,
+    /// An access prompt became visible, for the request handle and app ID
+    PromptShown(zvariant::ObjectPath<'static>, String),
+    PromptHidden(zvariant::ObjectPath<'static>),
     FileChooser(crate::file_chooser::Args),
     Screenshot(crate::screenshot::Args),
     Screencast(crate::screencast_dialog::Args),
@@ -26,6 +41,7 @@ pub enum Event {
     Accent(Srgba),
     IsDark(bool),
     HighContrast(bool),
+    ScaleFactor(f64),
     Config(config::Config),
     Init(tokio::sync::mpsc::Sender<Event>),
 }
@@ -39,10 +55,21 @@ impl Debug for Event {
                 .field("subtitle", &args.subtitle)
                 .field("body", &args.body)
                 .field("options", &args.options)
+                .field("raw_options", &args.raw_options)
                 .field("app_id", &args.app_id)
                 .field("parent_window", &args.parent_window)
                 .field("handle", &args.handle)
+                .field(
+                    "attached",
+                    &args.attached.iter().map(|(h, _)| h).collect::<Vec<_>>(),
+                )
                 .finish(),
+            Event::CancelAccess(h) => f.debug_tuple("CancelAccess").field(h).finish(),
+            Event::CancelShownAccess => f.write_str("CancelShownAccess"),
+            Event::PromptShown(h, app_id) => {
+                f.debug_tuple("PromptShown").field(h).field(app_id).finish()
+            }
+            Event::PromptHidden(h) => f.debug_tuple("PromptHidden").field(h).finish(),
             Event::FileChooser(args) => f
                 .debug_struct("FileChooser")
                 .field("handle", &args.handle)
@@ -62,6 +89,11 @@ impl Debug for Event {
                 location,
                 tx: _tx,
                 toplevel_images,
+                ocr_languages,
+                recognizing,
+                compare,
+                region_tx,
+                pointer_seen,
             }) => f
                 .debug_struct("Screenshot")
                 .field("handle", handle)
@@ -73,12 +105,18 @@ impl Debug for Event {
                 .field("action", action)
                 .field("location", location)
                 .field("toplevel_images", toplevel_images)
+                .field("ocr_languages", ocr_languages)
+                .field("recognizing", recognizing)
+                .field("compare", compare)
+                .field("selecting_region", &region_tx.is_some())
+                .field("pointer_seen", pointer_seen)
                 .finish(),
             Event::Screencast(s) => s.fmt(f),
             Event::CancelScreencast(h) => f.debug_tuple("CancelScreencast").field(h).finish(),
             Event::Accent(a) => a.fmt(f),
             Event::IsDark(t) => t.fmt(f),
             Event::HighContrast(c) => c.fmt(f),
+            Event::ScaleFactor(s) => s.fmt(f),
             Event::Config(c) => c.fmt(f),
             Event::Init(tx) => tx.fmt(f),
         }
@@ -94,7 +132,6 @@ pub(crate) fn portal_subscription(
     helper: wayland::WaylandHelper,
 ) -> cosmic::iced::Subscription<Event> {
     struct PortalSubscription;
-    struct ConfigSubscription;
     subscription::Subscription::batch([
         subscription::channel(
             TypeId::of::<PortalSubscription>(),
@@ -109,21 +146,27 @@ pub(crate) fn portal_subscription(
                 }
             },
         ),
-        cosmic_config::config_subscription(
-            TypeId::of::<ConfigSubscription>(),
-            config::APP_ID.into(),
-            config::CONFIG_VERSION,
-        )
-        .map(|update| {
-            for error in update.errors {
-                log::warn!("Error updating config: {:?}", error);
-            }
-
-            Event::Config(update.config)
-        }),
+        config_subscription(),
     ])
 }
 
+/// Changes to the config, without serving the portal
+pub(crate) fn config_subscription() -> cosmic::iced::Subscription<Event> {
+    struct ConfigSubscription;
+    cosmic_config::config_subscription(
+        TypeId::of::<ConfigSubscription>(),
+        config::APP_ID.into(),
+        config::CONFIG_VERSION,
+    )
+    .map(|update| {
+        for error in update.errors {
+            log::warn!("Error updating config: {:?}", error);
+        }
+
+        Event::Config(update.config)
+    })
+}
+
 pub(crate) async fn process_changes(
     state: &mut State,
     output: &mut futures::channel::mpsc::Sender<Event>,
@@ -132,22 +175,49 @@ pub(crate) async fn process_changes(
     match state {
         State::Init => {
             let (tx, rx) = tokio::sync::mpsc::channel(10);
+            let screencast = ScreenCast::new(wayland_helper.clone(), tx.clone());
+            let screencast_sessions = screencast.sessions();
+            let secret = Secret::new();
+            let secret_rotation = secret.rotation();
 
             let connection = zbus::ConnectionBuilder::session()?
                 .name(DBUS_NAME)?
                 .serve_at(DBUS_PATH, Access::new(wayland_helper.clone(), tx.clone()))?
+                .serve_at(DBUS_PATH, Prompts::new(tx.clone()))?
+                .serve_at(DBUS_PATH, AppChooser::new(tx.clone()))?
                 .serve_at(DBUS_PATH, FileChooser::new(tx.clone()))?
                 .serve_at(
                     DBUS_PATH,
                     Screenshot::new(wayland_helper.clone(), tx.clone()),
                 )?
-                .serve_at(
-                    DBUS_PATH,
-                    ScreenCast::new(wayland_helper.clone(), tx.clone()),
-                )?
-                .serve_at(DBUS_PATH, Settings::new())?
+                .serve_at(DBUS_PATH, screencast)?
+                .serve_at(DBUS_PATH, screencast_sessions)?
+                .serve_at(DBUS_PATH, Notification::new())?
+                .serve_at(DBUS_PATH, Usb::new(tx.clone()))?
+                .serve_at(DBUS_PATH, secret)?
+                .serve_at(DBUS_PATH, secret_rotation)?
+                .serve_at(DBUS_PATH, Settings::new(wayland_helper))?
+                .serve_at(DBUS_PATH, Management::new(tx.clone()))?
+                // TODO: Print. Without a backend, the frontend falls back to another
+                // portal. Ours should hand jobs to CUPS and track them to a terminal
+                // state, notifying of failures like an offline printer or missing paper.
+                // TODO: Clipboard. The frontend only allows it for RemoteDesktop sessions,
+                // which we don't implement. It also needs a way for the portal to read and
+                // set the selection: COSMIC offers wlr-data-control only to privileged
+                // clients, which the portal isn't.
+                // TODO: Realtime. There's no impl interface for it: the frontend asks
+                // rtkit itself, so consent for realtime scheduling would need the frontend
+                // to ask through Access first.
+                // TODO: Location. There's no impl interface for it: the frontend serves
+                // the portal from geoclue itself, and asks through Access without a choice
+                // of accuracy. Offering exact, city or no location needs the frontend to
+                // pass such a choice, and cap what it delivers to the answer.
                 .build()
                 .await?;
+            tokio::spawn(crate::background_review::run());
+            tokio::spawn(crate::access::log_resolutions());
+            tokio::spawn(crate::metrics::count_resolutions());
+            tokio::spawn(crate::management::reload_on_hangup(connection.clone()));
             _ = output.send(Event::Init(tx)).await;
             *state = State::Waiting(connection, rx);
         }
@@ -159,6 +229,16 @@ pub(crate) async fn process_changes(
                             log::error!("Error sending access event: {:?}", err);
                         };
                     }
+                    Event::CancelAccess(handle) => {
+                        if let Err(err) = output.send(Event::CancelAccess(handle)).await {
+                            log::error!("Error sending access cancel: {:?}", err);
+                        };
+                    }
+                    Event::CancelShownAccess => {
+                        if let Err(err) = output.send(Event::CancelShownAccess).await {
+                            log::error!("Error sending access cancel: {:?}", err);
+                        };
+                    }
                     Event::FileChooser(args) => {
                         if let Err(err) = output.send(Event::FileChooser(args)).await {
                             log::error!("Error sending access event: {:?}", err);
@@ -234,6 +314,28 @@ pub(crate) async fn process_changes(
                             )
                             .await?;
                     }
+                    Event::PromptShown(handle, app_id) => {
+                        let ctxt = zbus::SignalContext::new(conn, DBUS_PATH)?;
+                        Prompts::prompt_shown(&ctxt, handle, &app_id).await?;
+                    }
+                    Event::PromptHidden(handle) => {
+                        let ctxt = zbus::SignalContext::new(conn, DBUS_PATH)?;
+                        Prompts::prompt_hidden(&ctxt, handle).await?;
+                    }
+                    Event::ScaleFactor(scale_factor) => {
+                        let object_server = conn.object_server();
+                        let iface_ref = object_server.interface::<_, Settings>(DBUS_PATH).await?;
+                        let mut iface = iface_ref.get_mut().await;
+                        iface.scale_factor = scale_factor;
+                        iface
+                            .setting_changed(
+                                iface_ref.signal_context(),
+                                DISPLAY_NAMESPACE,
+                                SCALE_FACTOR_KEY,
+                                zvariant::Value::from(iface.scale_factor),
+                            )
+                            .await?;
+                    }
                     Event::Config(config) => {
                         if let Err(err) = output.send(Event::Config(config)).await {
                             log::error!("Error sending config update: {:?}", err)
@@ -0,0 +1,148 @@
+// Layer surface settings shared by the portal's dialogs and overlays, so they
+// agree on layers, anchors, keyboard interactivity, and namespaces.
+//
+// Surfaces other than fullscreen overlays keep an exclusive zone of 0, so the
+// compositor places them inside the area not reserved by panels and docks.
+// Layer shell doesn't tell clients about other surfaces' exclusive zones, so
+// margins here are relative to that area rather than the edge of the output.
+
+#![allow(dead_code)]
+
+use cosmic::iced::wayland::actions::layer_surface::{
+    IcedMargin, IcedOutput, SctkLayerSurfaceSettings,
+};
+use cosmic::iced::{window, Limits};
+use cosmic_client_toolkit::sctk::shell::wlr_layer::{Anchor, KeyboardInteractivity, Layer};
+
+// Gap between an edge-anchored surface and the edge of the usable area
+const EDGE_MARGIN: i32 = 8;
+// Gap between a toast and the bottom of the usable area
+const TOAST_MARGIN: i32 = 32;
+
+/// Namespace for a portal surface, e.g. `access` or `screenshot`
+fn namespace(name: &str) -> String {
+    format!("xdg-desktop-portal-cosmic {name}")
+}
+
+/// Dialog centered on the active output, above panels
+pub fn centered_dialog(id: window::Id, name: &str) -> SctkLayerSurfaceSettings {
+    SctkLayerSurfaceSettings {
+        id,
+        layer: Layer::Overlay,
+        keyboard_interactivity: KeyboardInteractivity::OnDemand,
+        pointer_interactivity: true,
+        anchor: Anchor::empty(),
+        output: IcedOutput::Active,
+        namespace: namespace(name),
+        size: None,
+        exclusive_zone: 0,
+        ..Default::default()
+    }
+}
+
+/// Overlay covering all of `output`, including panels
+pub fn fullscreen_overlay(
+    id: window::Id,
+    output: IcedOutput,
+    name: &str,
+) -> SctkLayerSurfaceSettings {
+    SctkLayerSurfaceSettings {
+        id,
+        layer: Layer::Overlay,
+        keyboard_interactivity: KeyboardInteractivity::Exclusive,
+        pointer_interactivity: true,
+        anchor: Anchor::all(),
+        output,
+        namespace: namespace(name),
+        size: Some((None, None)),
+        exclusive_zone: -1,
+        size_limits: Limits::NONE.min_height(1.0).min_width(1.0),
+        ..Default::default()
+    }
+}
+
+/// Small indicator anchored to `anchor` on the active output, which doesn't take focus
+pub fn edge_indicator(id: window::Id, anchor: Anchor, name: &str) -> SctkLayerSurfaceSettings {
+    let margin = |edge| {
+        if anchor.contains(edge) {
+            EDGE_MARGIN
+        } else {
+            0
+        }
+    };
+    SctkLayerSurfaceSettings {
+        id,
+        layer: Layer::Overlay,
+        keyboard_interactivity: KeyboardInteractivity::None,
+        pointer_interactivity: true,
+        anchor,
+        output: IcedOutput::Active,
+        namespace: namespace(name),
+        size: None,
+        exclusive_zone: 0,
+        margin: IcedMargin {
+            top: margin(Anchor::TOP),
+            right: margin(Anchor::RIGHT),
+            bottom: margin(Anchor::BOTTOM),
+            left: margin(Anchor::LEFT),
+        },
+        ..Default::default()
+    }
+}
+
+/// Dialog anchored to `anchor` on the active output, beside any panel on that edge
+pub fn edge_dialog(id: window::Id, anchor: Anchor, name: &str) -> SctkLayerSurfaceSettings {
+    SctkLayerSurfaceSettings {
+        keyboard_interactivity: KeyboardInteractivity::OnDemand,
+        ..edge_indicator(id, anchor, name)
+    }
+}
+
+/// `settings` taking keyboard focus as soon as the surface is mapped, rather than when
+/// clicked
+pub fn focused(settings: SctkLayerSurfaceSettings) -> SctkLayerSurfaceSettings {
+    SctkLayerSurfaceSettings {
+        keyboard_interactivity: KeyboardInteractivity::Exclusive,
+        ..settings
+    }
+}
+
+/// Transient notice at the bottom center of the active output, which doesn't take focus
+pub fn toast(id: window::Id, name: &str) -> SctkLayerSurfaceSettings {
+    SctkLayerSurfaceSettings {
+        margin: IcedMargin {
+            top: 0,
+            right: 0,
+            bottom: TOAST_MARGIN,
+            left: 0,
+        },
+        ..edge_indicator(id, Anchor::BOTTOM, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edge_dialogs_keep_clear_of_panels() {
+        for anchor in [
+            Anchor::TOP,
+            Anchor::BOTTOM | Anchor::LEFT,
+            Anchor::TOP | Anchor::RIGHT,
+        ] {
+            let settings = edge_dialog(window::Id::unique(), anchor, "access");
+            assert_eq!(settings.anchor, anchor);
+            assert_eq!(settings.exclusive_zone, 0);
+            assert_eq!(
+                settings.keyboard_interactivity,
+                KeyboardInteractivity::OnDemand
+            );
+            let margin = settings.margin;
+            assert_eq!(margin.top != 0, anchor.contains(Anchor::TOP));
+            assert_eq!(margin.right != 0, anchor.contains(Anchor::RIGHT));
+            assert_eq!(margin.bottom != 0, anchor.contains(Anchor::BOTTOM));
+            assert_eq!(margin.left != 0, anchor.contains(Anchor::LEFT));
+        }
+    }
+}
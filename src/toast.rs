@@ -0,0 +1,119 @@
+// Brief notices at the bottom of the active output, like for a request answered without a
+// dialog. One is shown at a time. It goes away after a while, or sooner if nobody is there
+// to read it or the user has moved on to something else, as told by idle notifications.
+
+use cosmic::iced::{widget::row, window, Alignment, Length};
+use cosmic::iced_sctk::commands::layer_surface::{destroy_layer_surface, get_layer_surface};
+use cosmic::widget::{self, container, icon, text};
+use once_cell::sync::Lazy;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::app::CosmicPortal;
+use crate::wayland::{IdleEvent, IdleWatch, WaylandHelper};
+
+pub static TOAST_ID: Lazy<window::Id> = Lazy::new(window::Id::unique);
+
+// Shortest time a toast is shown, so it can be read
+const MIN_SHOWN: Duration = Duration::from_secs(2);
+// Longest time a toast is shown, for when the compositor can't tell whether the user is idle
+const MAX_SHOWN: Duration = Duration::from_secs(8);
+// Time without input after which nobody is reading the toast
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+// Input after a pause this long means the user has moved on
+const PAUSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct Toast {
+    icon: String,
+    text: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum Msg {
+    /// Show a toast, for text that takes a command to make
+    Show { icon: String, text: String },
+    /// Hide the toast, if it's still the one shown with this serial
+    Dismiss(u64),
+}
+
+/// Show `text` with `icon`, replacing any toast already shown
+pub fn show(
+    portal: &mut CosmicPortal,
+    icon: &str,
+    text: String,
+) -> cosmic::Command<crate::app::Msg> {
+    portal.toast_serial += 1;
+    let serial = portal.toast_serial;
+    let toast = Toast {
+        icon: icon.to_string(),
+        text,
+    };
+    let surface = match portal.toast.replace(toast) {
+        Some(_) => cosmic::iced::Command::none(),
+        None => get_layer_surface(crate::surfaces::toast(*TOAST_ID, "toast")),
+    };
+    let dismiss =
+        cosmic::iced::Command::perform(dismissed(portal.wayland_helper.clone()), move |()| {
+            crate::app::Msg::Toast(Msg::Dismiss(serial))
+        });
+    cosmic::iced::Command::batch([surface, dismiss])
+}
+
+// Wait until a toast should go away
+async fn dismissed(wayland_helper: WaylandHelper) {
+    tokio::time::sleep(MIN_SHOWN).await;
+    let idle = wayland_helper.watch_idle(IDLE_TIMEOUT);
+    let pause = wayland_helper.watch_idle(PAUSE_TIMEOUT);
+    tokio::select! {
+        _ = tokio::time::sleep(MAX_SHOWN - MIN_SHOWN) => {}
+        _ = idle_event(idle, IdleEvent::Idled) => {}
+        _ = idle_event(pause, IdleEvent::Resumed) => {}
+    }
+}
+
+// Wait for `event` from `watch`, or forever without one
+async fn idle_event(watch: Option<(IdleWatch, UnboundedReceiver<IdleEvent>)>, event: IdleEvent) {
+    if let Some((_watch, mut rx)) = watch {
+        while let Some(received) = rx.recv().await {
+            if received == event {
+                return;
+            }
+        }
+    }
+    std::future::pending::<()>().await;
+}
+
+pub fn update_msg(portal: &mut CosmicPortal, msg: Msg) -> cosmic::Command<crate::app::Msg> {
+    match msg {
+        Msg::Show { icon, text } => show(portal, &icon, text),
+        Msg::Dismiss(serial) => {
+            if serial != portal.toast_serial || portal.toast.take().is_none() {
+                return cosmic::iced::Command::none();
+            }
+            destroy_layer_surface(*TOAST_ID)
+        }
+    }
+}
+
+/// Forget the toast after the compositor closed its surface
+pub fn surface_closed(portal: &mut CosmicPortal) {
+    portal.toast = None;
+}
+
+pub fn view(portal: &CosmicPortal) -> cosmic::Element<Msg> {
+    let Some(toast) = portal.toast.as_ref() else {
+        return widget::horizontal_space(Length::Fixed(1.0)).into();
+    };
+    let spacing = portal.core.system_theme().cosmic().spacing;
+    container(
+        row![
+            icon::Icon::from(icon::from_name(toast.icon.as_str()).size(16)),
+            text(toast.text.as_str()),
+        ]
+        .spacing(spacing.space_xs as f32)
+        .align_items(Alignment::Center),
+    )
+    .padding([spacing.space_xs, spacing.space_s])
+    .style(cosmic::theme::Container::Card)
+    .into()
+}
@@ -0,0 +1,272 @@
+// Consent for apps to access USB devices. The frontend opens the granted devices.
+
+use futures::future::abortable;
+use std::{collections::HashMap, os::unix::fs::MetadataExt, sync::Mutex};
+use tokio::sync::mpsc::Sender;
+use zbus::zvariant;
+
+use crate::lru::{LruCache, APP_CACHE_CAPACITY};
+use crate::request_limit::RequestLimit;
+use crate::{access, fl, screencast_dialog, subscription, PortalResponse, Request};
+
+// ID of the choice to remember a grant
+const REMEMBER_CHOICE: &str = "remember";
+
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug)]
+#[zvariant(signature = "a{sv}")]
+pub struct DeviceInfo {
+    #[zvariant(rename = "device-file")]
+    device_file: Option<String>,
+    /// udev properties
+    properties: Option<HashMap<String, zvariant::OwnedValue>>,
+}
+
+impl DeviceInfo {
+    fn property(&self, key: &str) -> Option<&str> {
+        let value = self.properties.as_ref()?.get(key)?;
+        <&str>::try_from(value).ok()
+    }
+
+    /// Vendor and product, for the user to recognize the device
+    fn description(&self) -> String {
+        let vendor = self
+            .property("ID_VENDOR_FROM_DATABASE")
+            .or_else(|| self.property("ID_VENDOR"));
+        let model = self
+            .property("ID_MODEL_FROM_DATABASE")
+            .or_else(|| self.property("ID_MODEL"));
+        match (vendor, model) {
+            (Some(vendor), Some(model)) => format!("{vendor} {model}"),
+            (Some(name), None) | (None, Some(name)) => name.to_string(),
+            (None, None) => match (self.property("ID_VENDOR_ID"), self.property("ID_MODEL_ID")) {
+                (Some(vendor_id), Some(model_id)) => format!("{vendor_id}:{model_id}"),
+                _ => fl!("unknown-device"),
+            },
+        }
+    }
+
+    /// Identity of the device node, which changes when the device is reconnected
+    fn node(&self) -> Option<(u64, u64)> {
+        let metadata = std::fs::metadata(self.device_file.as_ref()?).ok()?;
+        Some((metadata.rdev(), metadata.ino()))
+    }
+}
+
+#[derive(
+    zvariant::DeserializeDict,
+    zvariant::SerializeDict,
+    zvariant::Type,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+)]
+#[zvariant(signature = "a{sv}")]
+pub struct AccessOptions {
+    writable: Option<bool>,
+}
+
+#[derive(zvariant::DeserializeDict, zvariant::Type, Debug)]
+#[zvariant(signature = "a{sv}")]
+pub struct AcquireDevicesOptions {}
+
+#[derive(zvariant::SerializeDict, zvariant::DeserializeDict, zvariant::Type, Debug, PartialEq)]
+#[zvariant(signature = "a{sv}")]
+pub struct AcquireDevicesResult {
+    devices: Vec<(String, AccessOptions)>,
+}
+
+/// Remembered grant of a device to an app
+#[derive(Clone, Copy, Debug)]
+struct Grant {
+    node: (u64, u64),
+    writable: bool,
+}
+
+pub struct Usb {
+    tx: Sender<subscription::Event>,
+    // Remembered grants, by app and device ID. Lost when the device is reconnected.
+    grants: Mutex<LruCache<(String, String), Grant>>,
+    limit: RequestLimit,
+}
+
+impl Usb {
+    pub fn new(tx: Sender<subscription::Event>) -> Self {
+        Self {
+            tx,
+            grants: Mutex::new(LruCache::new(APP_CACHE_CAPACITY)),
+            limit: RequestLimit::new("Usb"),
+        }
+    }
+
+    /// Whether remembered grants cover all of `devices`, dropping grants of reconnected devices
+    fn remembered(&self, app_id: &str, devices: &[(String, DeviceInfo, AccessOptions)]) -> bool {
+        let mut grants = self.grants.lock().unwrap();
+        let mut covered = true;
+        for (id, info, options) in devices {
+            let key = (app_id.to_string(), id.clone());
+            let Some(grant) = grants.get(&key).copied() else {
+                covered = false;
+                continue;
+            };
+            if info.node() != Some(grant.node) {
+                log::info!("USB device {id} was reconnected, forgetting grant to {app_id}");
+                grants.remove(&key);
+                covered = false;
+            } else if options.writable.unwrap_or(false) && !grant.writable {
+                covered = false;
+            }
+        }
+        covered
+    }
+}
+
+impl Usb {
+    async fn acquire_devices_inner(
+        &self,
+        connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        parent_window: &str,
+        app_id: &str,
+        devices: Vec<(String, DeviceInfo, AccessOptions)>,
+        _options: AcquireDevicesOptions,
+    ) -> PortalResponse<AcquireDevicesResult> {
+        log::debug!("USB access {handle} {app_id} {parent_window} {devices:?}");
+        let result = AcquireDevicesResult {
+            devices: devices
+                .iter()
+                .map(|(id, _, options)| (id.clone(), *options))
+                .collect(),
+        };
+        if devices.is_empty() || self.remembered(app_id, &devices) {
+            return PortalResponse::Success(result);
+        }
+
+        let app_name = screencast_dialog::app_name(app_id)
+            .await
+            .unwrap_or_else(|| fl!("unknown-application"));
+        let device_names = devices
+            .iter()
+            .map(|(_, info, _)| info.description())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let options = access::AccessDialogOptions {
+            grant_label: Some(fl!("allow")),
+            icon: Some("drive-removable-media-symbolic".to_string()),
+            choices: Some(vec![(
+                REMEMBER_CHOICE.to_string(),
+                fl!("usb-remember"),
+                Vec::new(),
+                "false".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let (abortable, abort_handle) = abortable(async {
+            let _slot = self.limit.acquire(&handle, &self.tx).await;
+            access::prompt(
+                &self.tx,
+                handle.to_owned(),
+                app_id,
+                fl!("usb-access"),
+                String::new(),
+                fl!(
+                    "usb-access",
+                    "description",
+                    app_name = app_name,
+                    devices = device_names
+                ),
+                options,
+            )
+            .await
+        });
+        let _ = connection
+            .object_server()
+            .at(&handle, Request(abort_handle))
+            .await;
+        let resp = abortable.await;
+        let _ = connection
+            .object_server()
+            .remove::<Request, _>(&handle)
+            .await;
+        let granted = match resp {
+            Ok(Some(granted)) => granted,
+            Ok(None) => return PortalResponse::Cancelled,
+            Err(_) => {
+                // Closed with `Request.Close`
+                if let Err(err) = self
+                    .tx
+                    .send(subscription::Event::CancelAccess(handle.to_owned()))
+                    .await
+                {
+                    log::error!("Failed to send access dialog cancel event, {err}");
+                }
+                return PortalResponse::Cancelled;
+            }
+        };
+
+        if granted.choice(REMEMBER_CHOICE) == Some("true") {
+            let mut grants = self.grants.lock().unwrap();
+            for (id, info, options) in &devices {
+                let Some(node) = info.node() else {
+                    continue;
+                };
+                grants.insert(
+                    (app_id.to_string(), id.clone()),
+                    Grant {
+                        node,
+                        writable: options.writable.unwrap_or(false),
+                    },
+                );
+            }
+        }
+        PortalResponse::Success(result)
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.impl.portal.Usb")]
+impl Usb {
+    async fn acquire_devices(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+        handle: zvariant::ObjectPath<'_>,
+        parent_window: &str,
+        app_id: &str,
+        devices: Vec<(String, DeviceInfo, AccessOptions)>,
+        options: AcquireDevicesOptions,
+    ) -> PortalResponse<AcquireDevicesResult> {
+        let response = self
+            .acquire_devices_inner(
+                connection,
+                handle.clone(),
+                parent_window,
+                app_id,
+                devices,
+                options,
+            )
+            .await;
+        crate::emit_response(connection, &handle, &response).await;
+        response
+    }
+
+    #[zbus(property, name = "version")]
+    async fn version(&self) -> u32 {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_round_trips() {
+        crate::assert_round_trips(AcquireDevicesResult {
+            devices: vec![(
+                "/org/freedesktop/usb/1".to_string(),
+                AccessOptions {
+                    writable: Some(true),
+                },
+            )],
+        });
+    }
+}
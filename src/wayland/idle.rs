@@ -0,0 +1,113 @@
+// Whether the user is idle, through ext-idle-notify, for transient surfaces that should go
+// away once nobody is looking at them or the user is busy with something else
+
+use cosmic_client_toolkit::sctk::globals::GlobalData;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use wayland_client::{protocol::wl_seat, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::ext::idle_notify::v1::client::{
+    ext_idle_notification_v1::{self, ExtIdleNotificationV1},
+    ext_idle_notifier_v1::ExtIdleNotifierV1,
+};
+
+use super::{AppData, WaylandHelper};
+
+pub(super) struct Idle {
+    notifier: ExtIdleNotifierV1,
+    seat: wl_seat::WlSeat,
+}
+
+impl Idle {
+    pub(super) fn new(notifier: ExtIdleNotifierV1, seat: wl_seat::WlSeat) -> Self {
+        Self { notifier, seat }
+    }
+}
+
+struct NotificationData {
+    tx: mpsc::UnboundedSender<IdleEvent>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleEvent {
+    /// There was no input for the watch's timeout
+    Idled,
+    /// There was input again after `Idled`
+    Resumed,
+}
+
+/// Watch of the user's input, which stops when dropped
+pub struct IdleWatch {
+    notification: ExtIdleNotificationV1,
+}
+
+impl Drop for IdleWatch {
+    fn drop(&mut self) {
+        self.notification.destroy();
+    }
+}
+
+impl WaylandHelper {
+    /// Watch for the user being idle for `timeout`, and for input again after that. `None`
+    /// if the compositor can't tell.
+    pub fn watch_idle(
+        &self,
+        timeout: Duration,
+    ) -> Option<(IdleWatch, mpsc::UnboundedReceiver<IdleEvent>)> {
+        let idle = self.inner.idle.as_ref()?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let timeout_ms = timeout.as_millis().try_into().unwrap_or(u32::MAX);
+        let notification = idle.notifier.get_idle_notification(
+            timeout_ms,
+            &idle.seat,
+            &self.inner.qh,
+            NotificationData { tx },
+        );
+        if let Err(err) = self.inner.conn.flush() {
+            log::error!("failed to watch for idle: {err}");
+        }
+        Some((IdleWatch { notification }, rx))
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, GlobalData> for AppData {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotifierV1,
+        _: <ExtIdleNotifierV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, NotificationData> for AppData {
+    fn event(
+        _: &mut Self,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        data: &NotificationData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let event = match event {
+            ext_idle_notification_v1::Event::Idled => IdleEvent::Idled,
+            ext_idle_notification_v1::Event::Resumed => IdleEvent::Resumed,
+            _ => return,
+        };
+        // Fails once the watch's receiver is dropped
+        let _ = data.tx.send(event);
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, GlobalData> for AppData {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
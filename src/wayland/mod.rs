@@ -21,7 +21,7 @@ use cosmic_protocols::{
         zcosmic_output_image_source_manager_v1::ZcosmicOutputImageSourceManagerV1,
         zcosmic_toplevel_image_source_manager_v1::ZcosmicToplevelImageSourceManagerV1,
     },
-    toplevel_info::v1::client::zcosmic_toplevel_handle_v1::ZcosmicToplevelHandleV1,
+    toplevel_info::v1::client::zcosmic_toplevel_handle_v1::{self, ZcosmicToplevelHandleV1},
     workspace::v1::client::zcosmic_workspace_handle_v1,
 };
 use futures::channel::oneshot;
@@ -39,7 +39,7 @@ use std::{
 };
 use wayland_client::{
     globals::registry_queue_init,
-    protocol::{wl_buffer, wl_output, wl_shm, wl_shm_pool},
+    protocol::{wl_buffer, wl_output, wl_seat, wl_shm, wl_shm_pool},
     Connection, Dispatch, Proxy, QueueHandle, WEnum,
 };
 use wayland_protocols::wp::linux_dmabuf::zv1::client::{
@@ -50,9 +50,12 @@ use wayland_protocols::wp::linux_dmabuf::zv1::client::{
 
 use crate::buffer;
 
+mod idle;
 mod toplevel;
 mod workspaces;
 
+pub use idle::{IdleEvent, IdleWatch};
+
 #[derive(Clone)]
 pub struct DmabufHelper {
     feedback: Arc<DmabufFeedback>,
@@ -94,6 +97,7 @@ struct WaylandHelperInner {
     wl_shm: wl_shm::WlShm,
     dmabuf: Mutex<Option<DmabufHelper>>,
     zwp_dmabuf: ZwpLinuxDmabufV1,
+    idle: Option<idle::Idle>,
 }
 
 // TODO seperate state object from what is passed to threads
@@ -211,6 +215,14 @@ impl Session {
         cb(data.formats.as_ref().unwrap())
     }
 
+    /// Size the source is currently rendered at, if the compositor has sent constraints.
+    ///
+    /// This changes with the scale of the output a toplevel is on.
+    pub fn buffer_size(&self) -> Option<(u32, u32)> {
+        let data = self.0.state.lock().unwrap();
+        data.formats.as_ref().map(|formats| formats.buffer_size)
+    }
+
     /// Capture to `wl_buffer`, blocking until capture either succeeds or fails
     pub async fn capture_wl_buffer(
         &self,
@@ -235,7 +247,42 @@ impl Session {
     }
 }
 
-#[derive(Clone, Debug)]
+/// Human-readable description of a protocol error sent by the compositor
+pub fn protocol_error_message(err: &wayland_client::backend::protocol::ProtocolError) -> String {
+    let reason = match (err.object_interface.as_str(), err.code) {
+        ("wl_display", 0) => Some("request on an invalid object"),
+        ("wl_display", 1) => Some("invalid method or arguments"),
+        ("wl_display", 2) => Some("compositor is out of memory"),
+        ("wl_display", 3) => Some("compositor implementation error"),
+        ("zwlr_layer_shell_v1", 0) => Some("surface already has another role"),
+        ("zwlr_layer_shell_v1", 1) => Some("invalid layer"),
+        ("zwlr_layer_shell_v1", 2) => Some("surface was already committed"),
+        ("zwlr_layer_surface_v1", 0) => Some("invalid surface state"),
+        ("zwlr_layer_surface_v1", 1) => Some("size is zero without anchors to opposing edges"),
+        ("zwlr_layer_surface_v1", 2) => Some("invalid anchor"),
+        ("zwlr_layer_surface_v1", 3) => Some("invalid keyboard interactivity"),
+        ("xdg_wm_base", 0) => Some("surface already has another role"),
+        ("xdg_wm_base", 1) => Some("xdg_wm_base destroyed before its surfaces"),
+        ("xdg_wm_base", 4) => Some("invalid surface state"),
+        ("xdg_wm_base", 5) => Some("invalid positioner"),
+        ("xdg_wm_base", 6) => Some("client didn't respond to ping in time"),
+        ("xdg_surface", 1) => Some("surface was already constructed"),
+        ("xdg_surface", 2) => Some("buffer attached before configure"),
+        ("xdg_toplevel", 1) => Some("invalid parent toplevel"),
+        ("xdg_toplevel", 2) => Some("invalid size"),
+        _ => None,
+    };
+    let reason = reason.map_or_else(
+        || err.message.clone(),
+        |reason| format!("{reason} ({})", err.message),
+    );
+    format!(
+        "{}@{} error {}: {}",
+        err.object_interface, err.object_id, err.code, reason
+    )
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum CaptureSource {
     Output(wl_output::WlOutput),
     Toplevel(ZcosmicToplevelHandleV1),
@@ -250,6 +297,13 @@ impl WaylandHelper {
         let screencopy_state = ScreencopyState::new(&globals, &qh);
         let shm_state = Shm::bind(&globals, &qh).unwrap();
         let zwp_dmabuf = globals.bind(&qh, 4..=4, sctk::globals::GlobalData).unwrap();
+        let seat = globals
+            .bind::<wl_seat::WlSeat, _, _>(&qh, 1..=1, sctk::globals::GlobalData)
+            .ok();
+        let idle = seat.and_then(|seat| {
+            let notifier = globals.bind(&qh, 1..=1, sctk::globals::GlobalData).ok()?;
+            Some(idle::Idle::new(notifier, seat))
+        });
         let wayland_helper = WaylandHelper {
             inner: Arc::new(WaylandHelperInner {
                 conn,
@@ -264,6 +318,7 @@ impl WaylandHelper {
                 wl_shm: shm_state.wl_shm().clone(),
                 dmabuf: Mutex::new(None),
                 zwp_dmabuf,
+                idle,
             }),
         };
         let dmabuf_state = DmabufState::new(&globals, &qh);
@@ -284,8 +339,18 @@ impl WaylandHelper {
 
         event_queue.roundtrip(&mut data).unwrap();
 
+        let conn = wayland_helper.inner.conn.clone();
         thread::spawn(move || loop {
-            event_queue.blocking_dispatch(&mut data).unwrap();
+            if let Err(err) = event_queue.blocking_dispatch(&mut data) {
+                match conn.protocol_error() {
+                    Some(err) => log::error!(
+                        "Wayland connection closed: {}",
+                        protocol_error_message(&err)
+                    ),
+                    None => log::error!("Wayland connection closed: {}", err),
+                }
+                break;
+            }
         });
 
         wayland_helper
@@ -304,10 +369,53 @@ impl WaylandHelper {
         self.inner.toplevels.lock().unwrap().clone()
     }
 
+    /// App ID of the focused toplevel, if it is fullscreen
+    pub fn focused_fullscreen_app_id(&self) -> Option<String> {
+        self.inner
+            .toplevels
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, info)| {
+                info.state
+                    .contains(&zcosmic_toplevel_handle_v1::State::Activated)
+                    && info
+                        .state
+                        .contains(&zcosmic_toplevel_handle_v1::State::Fullscreen)
+            })
+            .map(|(_, info)| info.app_id.clone())
+    }
+
+    pub fn toplevel_info(&self, toplevel: &ZcosmicToplevelHandleV1) -> Option<ToplevelInfo> {
+        self.inner
+            .toplevels
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(handle, _)| handle == toplevel)
+            .map(|(_, info)| info.clone())
+    }
+
     pub fn output_info(&self, output: &wl_output::WlOutput) -> Option<OutputInfo> {
         self.inner.output_infos.lock().unwrap().get(output).cloned()
     }
 
+    pub fn scale_factor(&self) -> f64 {
+        primary_scale_factor(
+            self.inner
+                .output_infos
+                .lock()
+                .unwrap()
+                .values()
+                .map(|info| {
+                    (
+                        info.logical_position.unwrap_or_default(),
+                        output_scale_factor(info),
+                    )
+                }),
+        )
+    }
+
     fn set_output_info(&self, output: &wl_output::WlOutput, output_info_opt: Option<OutputInfo>) {
         let mut output_infos = self.inner.output_infos.lock().unwrap();
         match output_info_opt {
@@ -485,6 +593,38 @@ impl WaylandHelper {
     }
 }
 
+/// Scale of an output, including fractional scaling
+///
+/// Computed from the current mode and logical size, since `wl_output` only advertises
+/// an integer scale.
+pub fn output_scale_factor(info: &OutputInfo) -> f64 {
+    let mode = info.modes.iter().find(|mode| mode.current);
+    match (mode, info.logical_size) {
+        // Compare the larger dimensions, so transforms don't matter
+        (Some(mode), Some((width, height))) if width > 0 && height > 0 => {
+            let physical = mode.dimensions.0.max(mode.dimensions.1);
+            physical as f64 / width.max(height) as f64
+        }
+        _ => info.scale_factor as f64,
+    }
+}
+
+/// Scale to report for the desktop as a whole, given the logical position and scale of every
+/// output.
+///
+/// Wayland has no notion of a primary output, so this is the output at the origin of the
+/// global space, or the first output if none is there. Defaults to 1.0 without outputs.
+pub fn primary_scale_factor(outputs: impl IntoIterator<Item = ((i32, i32), f64)>) -> f64 {
+    let mut first = None;
+    for (position, scale) in outputs {
+        if position == (0, 0) {
+            return scale;
+        }
+        first.get_or_insert(scale);
+    }
+    first.unwrap_or(1.0)
+}
+
 pub struct ShmImage<T: AsFd> {
     fd: T,
     pub width: u32,
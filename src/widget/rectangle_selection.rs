@@ -8,8 +8,10 @@ use cosmic::{
             wayland::{DataSourceEvent, DndOfferEvent},
             PlatformSpecific,
         },
+        keyboard,
         layout::Node,
         renderer::Quad,
+        widget::tree,
         Border, Color, Length, Point, Rectangle, Renderer, Shadow, Size,
     },
     iced_runtime::command::platform_specific::wayland::data_device::ActionInner,
@@ -61,6 +63,18 @@ impl From<u8> for DragState {
 
 const EDGE_GRAB_THICKNESS: f32 = 8.0;
 const CORNER_DIAMETER: f32 = 16.0;
+// Distance in logical pixels within which a dragged edge snaps to a guide. Logical pixels
+// grow with the output's scale, so the pull feels the same on every output.
+const SNAP_DISTANCE: i32 = 10;
+const GUIDE_THICKNESS: f32 = 2.0;
+
+#[derive(Debug, Default)]
+struct State {
+    /// Snapping is held off while Alt is down
+    alt: bool,
+    /// Guides dragged edges snapped to, as an x and a y in global logical coordinates
+    guides: (Option<i32>, Option<i32>),
+}
 
 pub struct RectangleSelection<Msg> {
     pub output_rect: Rect,
@@ -196,13 +210,53 @@ impl<Msg> RectangleSelection<Msg> {
         DragState::None
     }
 
-    fn handle_drag_pos(&mut self, x: i32, y: i32, shell: &mut iced_core::Shell<'_, Msg>) {
-        let prev = self.rectangle_selection;
+    /// Guides of the output edges snap to, as the x of vertical ones and the y of horizontal
+    /// ones: its edges and middle, which also bound its halves and quarters.
+    ///
+    /// TODO: Windows' edges too, once toplevel info gives their geometry. That bound here
+    /// only tells which outputs a window is on.
+    fn guides(&self) -> ([i32; 3], [i32; 3]) {
+        let o = self.output_rect;
+        (
+            [o.left, (o.left + o.right) / 2, o.right],
+            [o.top, (o.top + o.bottom) / 2, o.bottom],
+        )
+    }
 
-        let d_x = self.output_rect.left + x;
-        let d_y = self.output_rect.top + y;
+    /// `(x, y)` in global logical coordinates snapped to the nearest guides, and those
+    /// guides, unless snapping is held off
+    fn snap(&self, state: &State, (x, y): (i32, i32)) -> ((i32, i32), (Option<i32>, Option<i32>)) {
+        if state.alt {
+            return ((x, y), (None, None));
+        }
+        let nearest = |value: i32, guides: [i32; 3]| {
+            guides
+                .into_iter()
+                .filter(|guide| (guide - value).abs() <= SNAP_DISTANCE)
+                .min_by_key(|guide| (guide - value).abs())
+        };
+        let (xs, ys) = self.guides();
+        let guides = (nearest(x, xs), nearest(y, ys));
+        ((guides.0.unwrap_or(x), guides.1.unwrap_or(y)), guides)
+    }
+
+    fn handle_drag_pos(
+        &mut self,
+        state: &mut State,
+        x: i32,
+        y: i32,
+        shell: &mut iced_core::Shell<'_, Msg>,
+    ) {
+        let prev = self.rectangle_selection;
 
         let prev_state = self.drag_state;
+        // Only the edges being dragged snap, which reflecting across the selection keeps
+        let ((d_x, d_y), guides) =
+            self.snap(state, (self.output_rect.left + x, self.output_rect.top + y));
+        let drags_x = !matches!(prev_state, DragState::N | DragState::S);
+        let drags_y = !matches!(prev_state, DragState::E | DragState::W);
+        state.guides = (guides.0.filter(|_| drags_x), guides.1.filter(|_| drags_y));
+
         // the point of reflection is where, when crossed, the drag state changes to the opposit direction
         // for edge drags, only one of the x or y coordinate is used, for corner drags, both are used
         // the new dimensions are calculated by subtracting the reflection point from the drag point
@@ -288,6 +342,14 @@ impl<Msg> RectangleSelection<Msg> {
 impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
     for RectangleSelection<Msg>
 {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
     fn size(&self) -> Size<Length> {
         Size::new(Length::Fill, Length::Fill)
     }
@@ -335,7 +397,7 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
 
     fn on_event(
         &mut self,
-        _state: &mut iced_core::widget::Tree,
+        tree: &mut iced_core::widget::Tree,
         event: iced_core::Event,
         layout: iced_core::Layout<'_>,
         cursor: iced_core::mouse::Cursor,
@@ -344,7 +406,15 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
         shell: &mut iced_core::Shell<'_, Msg>,
         _viewport: &Rectangle,
     ) -> iced_core::event::Status {
+        let state = tree.state.downcast_mut::<State>();
         match event {
+            cosmic::iced_core::Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                state.alt = modifiers.alt();
+                if state.alt {
+                    state.guides = (None, None);
+                }
+                cosmic::iced_core::event::Status::Ignored
+            }
             cosmic::iced_core::Event::PlatformSpecific(PlatformSpecific::Wayland(
                 iced_core::event::wayland::Event::DndOffer(e),
             )) => {
@@ -360,7 +430,7 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
                             return cosmic::iced_core::event::Status::Ignored;
                         }
 
-                        self.handle_drag_pos(x as i32, y as i32, shell);
+                        self.handle_drag_pos(state, x as i32, y as i32, shell);
                         cosmic::iced_core::event::Status::Captured
                     }
                     DndOfferEvent::Motion { x, y } => {
@@ -369,11 +439,12 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
                         if !cursor.is_over(layout.bounds()) {
                             return cosmic::iced_core::event::Status::Ignored;
                         }
-                        self.handle_drag_pos(x as i32, y as i32, shell);
+                        self.handle_drag_pos(state, x as i32, y as i32, shell);
                         cosmic::iced_core::event::Status::Captured
                     }
                     DndOfferEvent::DropPerformed => {
                         self.drag_state = DragState::None;
+                        state.guides = (None, None);
                         shell.publish((self.on_rectangle)(
                             DragState::None,
                             self.rectangle_selection,
@@ -393,6 +464,7 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
                         | DataSourceEvent::DndDropPerformed
                 ) {
                     self.drag_state = DragState::None;
+                    state.guides = (None, None);
                     shell.publish((self.on_rectangle)(
                         DragState::None,
                         self.rectangle_selection,
@@ -421,17 +493,23 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
 
                     let s = self.drag_state(cursor);
                     if let DragState::None = s {
-                        let mut pos = cursor.position().unwrap_or_default();
-                        pos.x += self.output_rect.left as f32;
-                        pos.y += self.output_rect.top as f32;
+                        let pos = cursor.position().unwrap_or_default();
+                        let ((x, y), guides) = self.snap(
+                            state,
+                            (
+                                self.output_rect.left + pos.x as i32,
+                                self.output_rect.top + pos.y as i32,
+                            ),
+                        );
+                        state.guides = guides;
                         self.drag_state = DragState::SE;
                         shell.publish((self.on_rectangle)(
                             DragState::SE,
                             Rect {
-                                left: pos.x as i32,
-                                top: pos.y as i32,
-                                right: pos.x as i32 + 1,
-                                bottom: pos.y as i32 + 1,
+                                left: x,
+                                top: y,
+                                right: x + 1,
+                                bottom: y + 1,
                             },
                         ));
                     } else {
@@ -448,7 +526,7 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
 
     fn draw(
         &self,
-        _tree: &cosmic::iced_core::widget::Tree,
+        tree: &cosmic::iced_core::widget::Tree,
         renderer: &mut cosmic::Renderer,
         theme: &cosmic::Theme,
         _style: &cosmic::iced_core::renderer::Style,
@@ -586,6 +664,31 @@ impl<Msg: 'static + Clone> Widget<Msg, cosmic::Theme, cosmic::Renderer>
             };
             renderer.fill_quad(quad, accent);
         }
+
+        // Guides snapped to, across the whole output
+        let state = tree.state.downcast_ref::<State>();
+        let mut guide_color = accent;
+        guide_color.a = 0.6;
+        let vertical = state.guides.0.map(|x| {
+            Rectangle::new(
+                Point::new(x as f32 - outer_rect.x - GUIDE_THICKNESS / 2.0, 0.0),
+                Size::new(GUIDE_THICKNESS, outer_size.height),
+            )
+        });
+        let horizontal = state.guides.1.map(|y| {
+            Rectangle::new(
+                Point::new(0.0, y as f32 - outer_rect.y - GUIDE_THICKNESS / 2.0),
+                Size::new(outer_size.width, GUIDE_THICKNESS),
+            )
+        });
+        for bounds in vertical.into_iter().chain(horizontal) {
+            let quad = Quad {
+                bounds,
+                border: Border::default(),
+                shadow: Shadow::default(),
+            };
+            renderer.fill_quad(quad, guide_color);
+        }
     }
 }
 
@@ -87,6 +87,11 @@ where
         save_locations: &'a Vec<String>,
         selected_save_location: usize,
         dropdown_selected: impl Fn(usize) -> Msg + 'static + Clone,
+        on_ocr: Option<Msg>,
+        recognizing: bool,
+        on_compare: Msg,
+        comparing: bool,
+        keyboard_hint: bool,
         spacing: Spacing,
     ) -> Self {
         let space_l = spacing.space_l;
@@ -222,6 +227,48 @@ where
             cosmic::theme::Svg::Custom(Rc::new(|t| cosmic::iced_style::svg::Appearance {
                 color: Some(t.cosmic().accent_color().into()),
             }));
+        // Text recognition needs a non-empty region or a chosen window
+        let has_selection = match &choice {
+            Choice::Rectangle(r, ..) => r.dimensions().is_some(),
+            Choice::Window(_, window_i) => window_i.is_some(),
+            Choice::Output(_) => true,
+        };
+        let mut capture_buttons: Vec<Element<'a, Msg>> = Vec::new();
+        if keyboard_hint {
+            // The pointer may be locked, so selecting a region needs the keyboard
+            capture_buttons.push(text(fl!("keyboard-selection-hint")).into());
+        }
+        capture_buttons.push(
+            button::custom(text(fl!("capture")))
+                .on_press_maybe(
+                    if let Choice::Rectangle(r, ..) = choice {
+                        // Disable button on empty selection
+                        r.dimensions().is_some().then_some(on_capture)
+                    } else {
+                        Some(on_capture)
+                    }
+                    .filter(|_| !recognizing),
+                )
+                .into(),
+        );
+        if let Some(on_ocr) = on_ocr {
+            let label = if recognizing {
+                fl!("recognizing-text")
+            } else {
+                fl!("copy-text")
+            };
+            capture_buttons.push(
+                button::custom(text(label))
+                    .on_press_maybe((has_selection && !recognizing).then_some(on_ocr))
+                    .into(),
+            );
+        }
+        capture_buttons.push(
+            button::custom(text(fl!("compare")))
+                .selected(comparing)
+                .on_press(on_compare)
+                .into(),
+        );
         Self {
             id: cosmic::widget::Id::unique(),
             choices: Vec::new(),
@@ -291,14 +338,9 @@ where
                     .spacing(space_s)
                     .align_items(cosmic::iced_core::Alignment::Center),
                     vertical::light().height(Length::Fixed(64.0)),
-                    button::custom(text(fl!("capture"))).on_press_maybe(
-                        if let Choice::Rectangle(r, ..) = choice {
-                            // Disable button on empty selection
-                            r.dimensions().is_some().then_some(on_capture)
-                        } else {
-                            Some(on_capture)
-                        }
-                    ),
+                    Row::with_children(capture_buttons)
+                        .spacing(space_s)
+                        .align_items(cosmic::iced_core::Alignment::Center),
                     vertical::light().height(Length::Fixed(64.0)),
                     dropdown(
                         save_locations.as_slice(),